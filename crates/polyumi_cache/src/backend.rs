@@ -0,0 +1,60 @@
+use futures::future::{ self, BoxFuture };
+use serde::{ de::DeserializeOwned, Serialize };
+use std::{
+	fmt::Display,
+	hash::Hash,
+	sync::Arc
+};
+
+use crate::{
+	bounded::{ BoundedCache, CacheConfig, CacheMetrics },
+	shared::Redis,
+	Result
+};
+
+/// A map kept in redis when there is one, so every instance sees the same entries, and only in this process otherwise.
+pub fn shared<K, V>(redis: Option<&Arc<Redis>>, name: &'static str, config: CacheConfig) -> Box<dyn CacheBackend<K, V>>
+where
+	K: Display + Eq + Hash + Send + Sync + 'static,
+	V: DeserializeOwned + Serialize + Send + Sync + 'static
+{
+	match redis {
+		Some(redis) => Box::new(redis.backend(name, config)),
+		None => Box::new(BoundedCache::new(name, config))
+	}
+}
+
+/// Where a cache map keeps its entries, either in this process or somewhere shared between instances.
+pub trait CacheBackend<K, V>: Send + Sync {
+	fn get<'a>(&'a self, key: &'a K) -> BoxFuture<'a, Result<Option<Arc<V>>>>;
+
+	fn insert(&self, key: K, value: V) -> BoxFuture<'_, Result<Arc<V>>>;
+
+	/// Removes an entry everywhere it's cached, including other instances.
+	fn remove<'a>(&'a self, key: &'a K) -> BoxFuture<'a, Result<()>>;
+
+	fn metrics(&self) -> CacheMetrics;
+}
+
+impl<K, V> CacheBackend<K, V> for BoundedCache<K, V>
+where
+	K: Eq + Hash + Send + Sync + 'static,
+	V: Send + Sync + 'static
+{
+	fn get<'a>(&'a self, key: &'a K) -> BoxFuture<'a, Result<Option<Arc<V>>>> {
+		Box::pin(future::ready(Ok(BoundedCache::get(self, key))))
+	}
+
+	fn insert(&self, key: K, value: V) -> BoxFuture<'_, Result<Arc<V>>> {
+		Box::pin(future::ready(Ok(BoundedCache::insert(self, key, value))))
+	}
+
+	fn remove<'a>(&'a self, key: &'a K) -> BoxFuture<'a, Result<()>> {
+		BoundedCache::remove(self, key);
+		Box::pin(future::ready(Ok(())))
+	}
+
+	fn metrics(&self) -> CacheMetrics {
+		BoundedCache::metrics(self)
+	}
+}
\ No newline at end of file
@@ -0,0 +1,105 @@
+use futures::future::BoxFuture;
+use std::{
+	collections::HashMap,
+	hash::Hash,
+	sync::{ Arc, Mutex },
+	time::Duration
+};
+use tokio::sync::oneshot;
+
+use crate::{ Error, Result };
+
+/// How long a batch waits for more keys before it's loaded.
+pub const BATCH_DELAY: Duration = Duration::from_millis(2);
+pub const MAX_BATCH_SIZE: usize = 100;
+
+type LoadMany<K, V> = dyn Fn(Vec<K>) -> BoxFuture<'static, Result<Vec<(K, V)>>> + Send + Sync;
+type Waiter<V> = oneshot::Sender<core::result::Result<Option<V>, Arc<Error>>>;
+
+/// Combines single keys loaded around the same time into one query for all of them, much like a DataLoader.
+pub struct BatchLoader<K, V> {
+	load_many: Arc<LoadMany<K, V>>,
+	pending: Arc<Mutex<HashMap<K, Vec<Waiter<V>>>>>
+}
+
+impl<K, V> BatchLoader<K, V>
+where
+	K: Clone + Eq + Hash + Send + 'static,
+	V: Clone + Send + 'static
+{
+	pub fn new<F>(load_many: impl Fn(Vec<K>) -> F + Send + Sync + 'static) -> Self
+	where
+		F: Future<Output = Result<Vec<(K, V)>>> + Send + 'static
+	{
+		Self {
+			load_many: Arc::new(move |keys| Box::pin(load_many(keys))),
+			pending: Arc::default()
+		}
+	}
+
+	pub async fn load(&self, key: K) -> Result<Option<V>> {
+		let (sender, receiver) = oneshot::channel();
+		let (is_first, full_batch) = {
+			let mut pending = self.pending.lock().unwrap();
+			let is_first = pending.is_empty();
+			pending
+				.entry(key)
+				.or_default()
+				.push(sender);
+
+			// taken while still locked, so nothing else can squeeze into it
+			let full_batch = (pending.len() >= MAX_BATCH_SIZE).then(|| std::mem::take(&mut *pending));
+			(is_first, full_batch)
+		};
+
+		// a full batch doesn't wait around for the rest, otherwise the first key in a batch schedules it
+		let load_many = self.load_many.clone();
+		if let Some(waiters) = full_batch {
+			tokio::spawn(run_batch(load_many, waiters));
+		} else if is_first {
+			let pending = self.pending.clone();
+			tokio::spawn(async move {
+				tokio::time::sleep(BATCH_DELAY).await;
+
+				// could've already been taken if it filled up in the meantime
+				let waiters = std::mem::take(&mut *pending.lock().unwrap());
+				run_batch(load_many, waiters).await;
+			});
+		}
+
+		receiver
+			.await
+			.map_err(|_| Error::BatchCancelled)?
+			.map_err(Error::Batch)
+	}
+}
+
+async fn run_batch<K, V>(load_many: Arc<LoadMany<K, V>>, waiters: HashMap<K, Vec<Waiter<V>>>)
+where
+	K: Clone + Eq + Hash,
+	V: Clone
+{
+	if waiters.is_empty() {
+		return;
+	}
+
+	match load_many(waiters.keys().cloned().collect()).await {
+		Ok(values) => {
+			let mut values: HashMap<K, V> = values
+				.into_iter()
+				.collect();
+			for (key, waiters) in waiters {
+				let value = values.remove(&key);
+				for waiter in waiters {
+					waiter.send(Ok(value.clone())).ok();
+				}
+			}
+		},
+		Err(error) => {
+			let error = Arc::new(error);
+			for waiter in waiters.into_values().flatten() {
+				waiter.send(Err(error.clone())).ok();
+			}
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,117 @@
+use moka::{
+	notification::RemovalCause,
+	sync::Cache
+};
+use std::{
+	hash::Hash,
+	sync::{
+		atomic::{ AtomicU64, Ordering },
+		Arc
+	},
+	time::Duration
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct CacheConfig {
+	pub capacity: u64,
+	pub ttl: Duration
+}
+
+impl CacheConfig {
+	pub const fn new(capacity: u64, ttl: Duration) -> Self {
+		Self { capacity, ttl }
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct CacheMetrics {
+	pub name: &'static str,
+	pub entries: u64,
+	pub hits: u64,
+	pub misses: u64,
+	pub evictions: u64
+}
+
+#[derive(Default)]
+struct Counters {
+	hits: AtomicU64,
+	misses: AtomicU64,
+	evictions: AtomicU64
+}
+
+/// A map that holds at most `capacity` entries for at most `ttl` each,
+/// evicting whatever's least likely to be used again (TinyLFU) once full.
+pub struct BoundedCache<K, V> {
+	name: &'static str,
+	inner: Cache<K, Arc<V>>,
+	counters: Arc<Counters>
+}
+
+impl<K, V> BoundedCache<K, V>
+where
+	K: Eq + Hash + Send + Sync + 'static,
+	V: Send + Sync + 'static
+{
+	pub fn new(name: &'static str, config: CacheConfig) -> Self {
+		let counters = Arc::new(Counters::default());
+		let eviction_counters = counters.clone();
+		Self {
+			name,
+			inner: Cache::builder()
+				.max_capacity(config.capacity)
+				.time_to_live(config.ttl)
+				.eviction_listener(move |_, _, cause| {
+					// removals and replacements are us, anything else is the cache making room or expiring
+					if !matches!(cause, RemovalCause::Explicit | RemovalCause::Replaced) {
+						eviction_counters.evictions.fetch_add(1, Ordering::Relaxed);
+					}
+				})
+				.build(),
+			counters
+		}
+	}
+
+	pub fn get(&self, key: &K) -> Option<Arc<V>> {
+		let value = self.inner.get(key);
+		let counter = match value {
+			Some(_) => &self.counters.hits,
+			None => &self.counters.misses
+		};
+		counter.fetch_add(1, Ordering::Relaxed);
+
+		value
+	}
+
+	pub fn contains(&self, key: &K) -> bool {
+		self.inner.contains_key(key)
+	}
+
+	pub fn insert(&self, key: K, value: V) -> Arc<V> {
+		let value = Arc::new(value);
+		self.inner.insert(key, value.clone());
+		value
+	}
+
+	pub fn remove(&self, key: &K) -> Option<Arc<V>> {
+		self.inner.remove(key)
+	}
+
+	pub fn clear(&self) {
+		self.inner.invalidate_all();
+	}
+
+	/// Evictions happen lazily in the background, this applies any pending ones now.
+	pub fn run_pending_tasks(&self) {
+		self.inner.run_pending_tasks();
+	}
+
+	pub fn metrics(&self) -> CacheMetrics {
+		CacheMetrics {
+			name: self.name,
+			entries: self.inner.entry_count(),
+			hits: self.counters.hits.load(Ordering::Relaxed),
+			misses: self.counters.misses.load(Ordering::Relaxed),
+			evictions: self.counters.evictions.load(Ordering::Relaxed)
+		}
+	}
+}
\ No newline at end of file
@@ -4,7 +4,19 @@ pub enum Error {
 	SqlError(#[from] sqlx::Error),
 
 	#[error("Model Error: {0}")]
-	ModelError(#[from] polyumi_models::Error)
+	ModelError(#[from] polyumi_models::Error),
+
+	#[error("Redis Error: {0}")]
+	Redis(#[from] redis::RedisError),
+
+	#[error("JSON Error: {0}")]
+	Json(#[from] serde_json::Error),
+
+	#[error("Batch Error: {0}")]
+	Batch(std::sync::Arc<Error>),
+
+	#[error("batch was dropped before it loaded")]
+	BatchCancelled
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
\ No newline at end of file
@@ -1,12 +1,78 @@
-use dashmap::{ DashMap, DashSet };
 use polyumi_models::hakumi::user::connection::ConnectionModel;
 use polyumi_util::id::{
 	marker::{ ConnectionMarker, UserMarker },
 	Id
 };
+use std::{
+	sync::Arc,
+	time::Duration
+};
+
+use crate::{
+	batch::BatchLoader,
+	bounded::{ BoundedCache, CacheConfig },
+	loading::LoadingCache,
+	Result
+};
 
-#[derive(Default)]
+pub const CONNECTIONS: CacheConfig = CacheConfig::new(10_000, Duration::from_secs(600));
+pub const USER_CONNECTIONS: CacheConfig = CacheConfig::new(10_000, Duration::from_secs(600));
+
+// connections carry oauth tokens, so they're only ever kept in this process, and never in redis
 pub struct HakumiCache {
-	pub connections: DashMap<Id<ConnectionMarker>, ConnectionModel>,
-	pub user_connections: DashMap<Id<UserMarker>, DashSet<Id<ConnectionMarker>>>
+	pub connections: LoadingCache<Id<ConnectionMarker>, ConnectionModel>,
+	pub user_connections: BoundedCache<Id<UserMarker>, Vec<Id<ConnectionMarker>>>,
+	connection_loader: BatchLoader<Id<ConnectionMarker>, ConnectionModel>
+}
+
+impl Default for HakumiCache {
+	fn default() -> Self {
+		Self {
+			connections: LoadingCache::new("connections", Box::new(BoundedCache::new("connections", CONNECTIONS)), CONNECTIONS),
+			user_connections: BoundedCache::new("user_connections", USER_CONNECTIONS),
+			connection_loader: BatchLoader::new(|connection_ids: Vec<_>| async move {
+				Ok(ConnectionModel::get_many(&connection_ids)
+					.await?
+					.into_iter()
+					.map(|x| (x.id, x))
+					.collect()
+				)
+			})
+		}
+	}
+}
+
+impl HakumiCache {
+	pub async fn connection(&self, connection_id: Id<ConnectionMarker>) -> Result<Option<Arc<ConnectionModel>>> {
+		self.connections
+			.get_or_load(&connection_id, || self.connection_loader.load(connection_id))
+			.await
+	}
+
+	pub async fn user_connection_ids(&self, user_id: Id<UserMarker>) -> Result<Arc<Vec<Id<ConnectionMarker>>>> {
+		if let Some(x) = self.user_connections.get(&user_id) {
+			return Ok(x);
+		}
+
+		let generation = self.connections.generation();
+		let models = ConnectionModel::get_user_many(user_id)
+			.await?;
+		let connection_ids: Vec<_> = models
+			.iter()
+			.map(|x| x.id)
+			.collect();
+		for model in models {
+			self.connections
+				.fill(model.id, model, generation)
+				.await?;
+		}
+
+		// same as LoadingCache::fill, the list is only kept if no connection was removed while reading it
+		let connection_ids = self.user_connections.insert(user_id, connection_ids);
+		if self.connections.generation() != generation {
+			self.user_connections.remove(&user_id);
+		}
+
+		Ok(connection_ids)
+	}
 }
\ No newline at end of file
@@ -1,21 +1,104 @@
+use log::error;
 use once_cell::sync::Lazy;
+use polyumi_models::mellow::model_event::{ ModelKind, MODEL_EVENT_BUS };
+use std::sync::Arc;
 
+pub mod backend;
+pub mod batch;
+pub mod bounded;
 pub mod error;
 pub mod hakumi;
+pub mod loading;
 pub mod mellow;
 pub mod polyumi;
+pub mod shared;
 
 use hakumi::HakumiCache;
 use mellow::MellowCache;
 use polyumi::PolyumiCache;
+use shared::Redis;
 
+pub use backend::CacheBackend;
+pub use bounded::{ BoundedCache, CacheConfig, CacheMetrics };
 pub use error::{ Error, Result };
 
-#[derive(Default)]
 pub struct Cache {
 	pub hakumi: HakumiCache,
 	pub mellow: MellowCache,
-	pub polyumi: PolyumiCache
+	pub polyumi: PolyumiCache,
+	redis: Option<Arc<Redis>>
 }
 
-pub static CACHE: Lazy<Cache> = Lazy::new(Cache::default);
\ No newline at end of file
+impl Cache {
+	/// Keeps shared maps in `redis` when given one, see [`backend::shared`].
+	pub fn new(redis: Option<Arc<Redis>>) -> Self {
+		Self {
+			hakumi: HakumiCache::default(),
+			mellow: MellowCache::new(redis.as_ref()),
+			polyumi: PolyumiCache::new(redis.as_ref()),
+			redis
+		}
+	}
+
+	/// Starts listening for entries removed by other instances, when shared with them.
+	pub async fn listen(&self) -> Result<()> {
+		if let Some(redis) = &self.redis {
+			redis
+				.listen()
+				.await?;
+		}
+
+		Ok(())
+	}
+
+	/// Drops anything a change to this model could've made stale, it'll be fetched again on the next read.
+	pub async fn invalidate(&self, model: &ModelKind) {
+		let result = match model {
+			ModelKind::Server(server_id) => self.mellow.servers
+				.remove(server_id)
+				.await,
+			// the connection goes first, that's what tells a user's list being read right now not to keep it
+			ModelKind::UserConnection(user_id, connection_id) => {
+				let result = self.hakumi.connections
+					.remove(connection_id)
+					.await;
+				self.hakumi.user_connections.remove(user_id);
+				result
+			},
+			ModelKind::Cafe(..) |
+			ModelKind::CafeOrder(..) |
+			ModelKind::Group(..) |
+			ModelKind::GroupMembership(..) |
+			ModelKind::GroupRole(..) |
+			ModelKind::UserSettings(..) |
+			ModelKind::VisualScriptingDocument(..) => Ok(())
+		};
+		if let Err(error) = result {
+			error!("failed to invalidate cache for {model:?}: {error}");
+		}
+	}
+
+	pub fn metrics(&self) -> Vec<CacheMetrics> {
+		vec![
+			self.hakumi.connections.metrics(),
+			self.hakumi.user_connections.metrics(),
+			self.mellow.servers.metrics(),
+			self.polyumi.passkeys.metrics(),
+			self.polyumi.passkey_challenges.metrics(),
+			self.polyumi.sessions.metrics()
+		]
+	}
+}
+
+pub static CACHE: Lazy<Cache> = Lazy::new(|| {
+	// every write goes through a model event, so this is the only place entries are ever invalidated
+	MODEL_EVENT_BUS.on_event(|_, model| {
+		let model = model.clone();
+		Box::pin(async move { CACHE.invalidate(&model).await })
+	});
+
+	let redis = std::env::var("REDIS_URL")
+		.ok()
+		.map(|x| Redis::open(&x).expect("REDIS_URL is invalid"));
+	Cache::new(redis)
+});
\ No newline at end of file
@@ -0,0 +1,153 @@
+use std::{
+	collections::HashMap,
+	hash::Hash,
+	sync::{
+		atomic::{ AtomicU64, Ordering },
+		Arc, Mutex
+	},
+	time::Duration
+};
+
+use crate::{
+	backend::CacheBackend,
+	bounded::{ BoundedCache, CacheConfig, CacheMetrics },
+	Result
+};
+
+/// How long a key with nothing behind it is remembered for, kept short since rows can be created without a model event.
+pub const NEGATIVE_TTL: Duration = Duration::from_secs(30);
+
+/// A cache map that's filled from the database on a miss, and remembers keys that turned out not to exist.
+///
+/// Concurrent misses for the same key share a single load, the rest wait for it and read its result from the cache.
+///
+/// A load can read a row right before a write commits and finish after that write's invalidation,
+/// so anything loaded while something was removed is handed back but not kept, see [`Self::fill`].
+pub struct LoadingCache<K, V> {
+	entries: Box<dyn CacheBackend<K, V>>,
+	missing: BoundedCache<K, ()>,
+	loading: Mutex<HashMap<K, Arc<tokio::sync::Mutex<()>>>>,
+	removals: AtomicU64
+}
+
+impl<K, V> LoadingCache<K, V>
+where
+	K: Clone + Eq + Hash + Send + Sync + 'static,
+	V: Send + Sync + 'static
+{
+	pub fn new(name: &'static str, entries: Box<dyn CacheBackend<K, V>>, config: CacheConfig) -> Self {
+		Self {
+			entries,
+			missing: BoundedCache::new(name, CacheConfig::new(config.capacity, NEGATIVE_TTL.min(config.ttl))),
+			loading: Mutex::default(),
+			removals: AtomicU64::new(0)
+		}
+	}
+
+	pub async fn get_or_load<F: Future<Output = Result<Option<V>>>>(&self, key: &K, load: impl FnOnce() -> F) -> Result<Option<Arc<V>>> {
+		if let Some(x) = self.cached(key).await? {
+			return Ok(x);
+		}
+
+		let lock = self.loading
+			.lock()
+			.unwrap()
+			.entry(key.clone())
+			.or_default()
+			.clone();
+		let result = {
+			let _guard = lock.lock().await;
+			// whoever held the lock before us may have loaded it already
+			match self.cached(key).await {
+				Ok(Some(x)) => Ok(x),
+				Ok(None) => self.load(key, load).await,
+				Err(error) => Err(error)
+			}
+		};
+
+		// the last one out cleans up, one held by the map and one by us
+		let mut loading = self.loading
+			.lock()
+			.unwrap();
+		if loading.get(key).is_some_and(|x| Arc::strong_count(x) == 2) {
+			loading.remove(key);
+		}
+
+		result
+	}
+
+	/// `Some` if the cache knows the answer, whether that's a value or that there isn't one.
+	async fn cached(&self, key: &K) -> Result<Option<Option<Arc<V>>>> {
+		if let Some(x) = self.entries.get(key).await? {
+			return Ok(Some(Some(x)));
+		}
+
+		Ok(self.missing
+			.contains(key)
+			.then_some(None)
+		)
+	}
+
+	async fn load<F: Future<Output = Result<Option<V>>>>(&self, key: &K, load: impl FnOnce() -> F) -> Result<Option<Arc<V>>> {
+		let generation = self.generation();
+		Ok(match load().await? {
+			Some(value) => Some(self
+				.fill(key.clone(), value, generation)
+				.await?
+			),
+			None => {
+				self.missing.insert(key.clone(), ());
+				if self.generation() != generation {
+					self.missing.remove(key);
+				}
+				None
+			}
+		})
+	}
+
+	/// Counts removals, take it before reading what's going to be passed to [`Self::fill`].
+	pub fn generation(&self) -> u64 {
+		self.removals.load(Ordering::SeqCst)
+	}
+
+	/// Caches a value read from the database, unless something was removed since `generation` was taken.
+	/// It's inserted first and checked after, removals count before they remove, so one can't slip in between.
+	pub async fn fill(&self, key: K, value: V, generation: u64) -> Result<Arc<V>> {
+		self.missing.remove(&key);
+		let value = self.entries
+			.insert(key.clone(), value)
+			.await?;
+		if self.generation() != generation {
+			self.entries
+				.remove(&key)
+				.await?;
+		}
+
+		Ok(value)
+	}
+
+	pub async fn get(&self, key: &K) -> Result<Option<Arc<V>>> {
+		self.entries
+			.get(key)
+			.await
+	}
+
+	pub async fn insert(&self, key: K, value: V) -> Result<Arc<V>> {
+		self.missing.remove(&key);
+		self.entries
+			.insert(key, value)
+			.await
+	}
+
+	pub async fn remove(&self, key: &K) -> Result<()> {
+		self.removals.fetch_add(1, Ordering::SeqCst);
+		self.missing.remove(key);
+		self.entries
+			.remove(key)
+			.await
+	}
+
+	pub fn metrics(&self) -> CacheMetrics {
+		self.entries.metrics()
+	}
+}
\ No newline at end of file
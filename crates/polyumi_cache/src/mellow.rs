@@ -1,33 +1,47 @@
-use dashmap::{
-	mapref::one::Ref,
-	DashMap
-};
 use polyumi_models::mellow::ServerModel;
+use std::{
+	sync::Arc,
+	time::Duration
+};
 use twilight_model::id::{
 	marker::GuildMarker as DiscordGuildMarker,
 	Id as DiscordId
 };
 
-use crate::Result;
+use crate::{
+	backend,
+	batch::BatchLoader,
+	bounded::CacheConfig,
+	loading::LoadingCache,
+	shared::Redis,
+	Result
+};
+
+pub const SERVERS: CacheConfig = CacheConfig::new(10_000, Duration::from_secs(600));
 
-#[derive(Default)]
 pub struct MellowCache {
-	servers: DashMap<DiscordId<DiscordGuildMarker>, ServerModel>
+	pub(crate) servers: LoadingCache<DiscordId<DiscordGuildMarker>, ServerModel>,
+	server_loader: BatchLoader<DiscordId<DiscordGuildMarker>, ServerModel>
 }
 
 impl MellowCache {
-	pub async fn server(&self, server_id: DiscordId<DiscordGuildMarker>) -> Result<Ref<'_, DiscordId<DiscordGuildMarker>, ServerModel>> {
-		Ok(match self.servers.get(&server_id) {
-			Some(x) => x,
-			None => {
-				let new_model = ServerModel::get(server_id)
+	pub fn new(redis: Option<&Arc<Redis>>) -> Self {
+		Self {
+			servers: LoadingCache::new("servers", backend::shared(redis, "servers", SERVERS), SERVERS),
+			server_loader: BatchLoader::new(|server_ids: Vec<_>| async move {
+				Ok(ServerModel::get_many(&server_ids)
 					.await?
-					.unwrap();
-				self.servers
-					.entry(server_id)
-					.insert(new_model)
-					.downgrade()
-			}
-		})
+					.into_iter()
+					.map(|x| (x.id, x))
+					.collect()
+				)
+			})
+		}
+	}
+
+	pub async fn server(&self, server_id: DiscordId<DiscordGuildMarker>) -> Result<Option<Arc<ServerModel>>> {
+		self.servers
+			.get_or_load(&server_id, || self.server_loader.load(server_id))
+			.await
 	}
 }
\ No newline at end of file
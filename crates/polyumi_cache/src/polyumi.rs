@@ -1,7 +1,3 @@
-use dashmap::{
-	mapref::one::Ref,
-	DashMap
-};
 use polyumi_models::polyumi::{
 	auth::{ PasskeyModel, PasskeyChallengeModel },
 	SessionModel
@@ -10,33 +6,49 @@ use polyumi_util::id::{
 	marker::PasskeyMarker,
 	Id
 };
+use std::{
+	sync::Arc,
+	time::Duration
+};
+
+use crate::{
+	backend::{ self, CacheBackend },
+	bounded::CacheConfig,
+	loading::LoadingCache,
+	shared::Redis,
+	Result
+};
 
-use crate::Result;
+pub const PASSKEYS: CacheConfig = CacheConfig::new(10_000, Duration::from_secs(3600));
+// challenges are only valid for as long as they're cached
+pub const PASSKEY_CHALLENGES: CacheConfig = CacheConfig::new(10_000, Duration::from_secs(300));
+pub const SESSIONS: CacheConfig = CacheConfig::new(50_000, Duration::from_secs(3600));
 
-#[derive(Default)]
 pub struct PolyumiCache {
-	pub passkeys: DashMap<String, PasskeyModel>,
-	pub passkey_challenges: DashMap<Id<PasskeyMarker>, PasskeyChallengeModel>,
-	pub sessions: DashMap<String, SessionModel>
+	pub passkeys: LoadingCache<String, PasskeyModel>,
+	pub passkey_challenges: Box<dyn CacheBackend<Id<PasskeyMarker>, PasskeyChallengeModel>>,
+	/// keyed by [`polyumi_models::polyumi::audit_log::session_id`], never the token itself.
+	pub sessions: Box<dyn CacheBackend<String, SessionModel>>
 }
 
 impl PolyumiCache {
-	pub async fn passkey(&self, passkey_id: &str) -> Result<Ref<'_, String, PasskeyModel>> {
-		Ok(match self.passkeys.get(passkey_id) {
-			Some(x) => x,
-			None => {
-				let model = PasskeyModel::get(passkey_id)
-					.await?
-					.unwrap();
-				self.passkeys
-					.entry(passkey_id.to_string())
-					.insert(model)
-					.downgrade()
-			}
-		})
+	pub fn new(redis: Option<&Arc<Redis>>) -> Self {
+		Self {
+			passkeys: LoadingCache::new("passkeys", backend::shared(redis, "passkeys", PASSKEYS), PASSKEYS),
+			passkey_challenges: backend::shared(redis, "passkey_challenges", PASSKEY_CHALLENGES),
+			sessions: backend::shared(redis, "sessions", SESSIONS)
+		}
+	}
+
+	pub async fn passkey(&self, passkey_id: &str) -> Result<Option<Arc<PasskeyModel>>> {
+		self.passkeys
+			.get_or_load(&passkey_id.to_string(), || async move { Ok(PasskeyModel::get(passkey_id).await?) })
+			.await
 	}
 
-	pub fn passkey_challenge(&self, challenge_id: Id<PasskeyMarker>) -> Option<Ref<'_, Id<PasskeyMarker>, PasskeyChallengeModel>> {
-		self.passkey_challenges.get(&challenge_id)
+	pub async fn passkey_challenge(&self, challenge_id: Id<PasskeyMarker>) -> Result<Option<Arc<PasskeyChallengeModel>>> {
+		self.passkey_challenges
+			.get(&challenge_id)
+			.await
 	}
 }
\ No newline at end of file
@@ -0,0 +1,217 @@
+use futures::{
+	future::BoxFuture,
+	StreamExt
+};
+use log::{ error, warn };
+use redis::{
+	aio::{ MultiplexedConnection, PubSub },
+	AsyncCommands, Client
+};
+use serde::{ de::DeserializeOwned, Serialize };
+use std::{
+	fmt::Display,
+	marker::PhantomData,
+	sync::{ Arc, RwLock },
+	time::Duration
+};
+use tokio::sync::OnceCell;
+
+use crate::{
+	backend::CacheBackend,
+	bounded::{ BoundedCache, CacheConfig, CacheMetrics },
+	Result
+};
+
+/// Every key removed from a shared map is published here, so other instances drop their local copy.
+pub const INVALIDATION_CHANNEL: &str = "polyumi:cache:invalidate";
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+type Evictor = Box<dyn Fn(Option<&str>) + Send + Sync>;
+
+/// A connection to a redis-compatible server shared by every map backed by it.
+pub struct Redis {
+	client: Client,
+	connection: OnceCell<MultiplexedConnection>,
+	evictors: RwLock<Vec<Evictor>>,
+	listener: OnceCell<()>
+}
+
+impl Redis {
+	pub fn open(url: &str) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			client: Client::open(url)?,
+			connection: OnceCell::new(),
+			evictors: RwLock::default(),
+			listener: OnceCell::new()
+		}))
+	}
+
+	async fn connection(&self) -> Result<MultiplexedConnection> {
+		Ok(self.connection
+			.get_or_try_init(|| self.client.get_multiplexed_tokio_connection())
+			.await?
+			.clone()
+		)
+	}
+
+	/// Creates a map stored in redis, with a local copy of whatever this instance has read recently.
+	pub fn backend<K, V>(self: &Arc<Self>, name: &'static str, config: CacheConfig) -> RedisBackend<K, V>
+	where
+		V: Send + Sync + 'static
+	{
+		let prefix = format!("polyumi:{name}:");
+		let local = Arc::new(BoundedCache::new(name, config));
+		let evictor_prefix = prefix.clone();
+		let evictor_local = local.clone();
+		self.evictors
+			.write()
+			.unwrap()
+			.push(Box::new(move |key| match key {
+				Some(key) => if key.starts_with(&evictor_prefix) {
+					evictor_local.remove(&key.to_string());
+				},
+				None => evictor_local.clear()
+			}));
+
+		RedisBackend {
+			redis: self.clone(),
+			prefix,
+			ttl: config.ttl,
+			local,
+			_key: PhantomData
+		}
+	}
+
+	/// Starts listening for keys removed by other instances, and waits until it is.
+	pub async fn listen(self: &Arc<Self>) -> Result<()> {
+		self.listener
+			.get_or_try_init(|| async {
+				let pubsub = self.subscribe()
+					.await?;
+				tokio::spawn(run_subscriber(self.clone(), pubsub));
+
+				Ok::<_, crate::Error>(())
+			})
+			.await?;
+
+		Ok(())
+	}
+
+	async fn subscribe(&self) -> Result<PubSub> {
+		let mut pubsub = self.client
+			.get_async_pubsub()
+			.await?;
+		pubsub
+			.subscribe(INVALIDATION_CHANNEL)
+			.await?;
+
+		Ok(pubsub)
+	}
+
+	fn evict_local(&self, key: Option<&str>) {
+		for evictor in self.evictors.read().unwrap().iter() {
+			evictor(key);
+		}
+	}
+}
+
+async fn run_subscriber(redis: Arc<Redis>, mut pubsub: PubSub) {
+	loop {
+		let mut messages = pubsub.on_message();
+		while let Some(message) = messages.next().await {
+			match message.get_payload::<String>() {
+				Ok(key) => redis.evict_local(Some(&key)),
+				Err(error) => warn!("invalid cache invalidation message: {error}")
+			}
+		}
+		drop(messages);
+
+		// anything could've been removed while we weren't listening
+		redis.evict_local(None);
+		pubsub = loop {
+			tokio::time::sleep(RECONNECT_DELAY).await;
+			match redis.subscribe().await {
+				Ok(x) => break x,
+				Err(error) => error!("failed to resubscribe to cache invalidations: {error}")
+			}
+		};
+		redis.evict_local(None);
+	}
+}
+
+pub struct RedisBackend<K, V> {
+	redis: Arc<Redis>,
+	prefix: String,
+	ttl: Duration,
+	local: Arc<BoundedCache<String, V>>,
+	_key: PhantomData<fn(K)>
+}
+
+impl<K: Display, V> RedisBackend<K, V> {
+	fn key(&self, key: &K) -> String {
+		format!("{}{key}", self.prefix)
+	}
+}
+
+impl<K, V> CacheBackend<K, V> for RedisBackend<K, V>
+where
+	K: Display + Send + Sync,
+	V: DeserializeOwned + Serialize + Send + Sync + 'static
+{
+	fn get<'a>(&'a self, key: &'a K) -> BoxFuture<'a, Result<Option<Arc<V>>>> {
+		Box::pin(async move {
+			let key = self.key(key);
+			if let Some(x) = self.local.get(&key) {
+				return Ok(Some(x));
+			}
+
+			let value: Option<Vec<u8>> = self.redis
+				.connection()
+				.await?
+				.get(&key)
+				.await?;
+			// anything that doesn't decode was written by an older version, and is as good as missing
+			Ok(value
+				.and_then(|x| serde_json::from_slice(&x).ok())
+				.map(|x| self.local.insert(key, x))
+			)
+		})
+	}
+
+	fn insert(&self, key: K, value: V) -> BoxFuture<'_, Result<Arc<V>>> {
+		Box::pin(async move {
+			let key = self.key(&key);
+			self.redis
+				.connection()
+				.await?
+				.pset_ex::<_, _, ()>(&key, serde_json::to_vec(&value)?, self.ttl.as_millis() as u64)
+				.await?;
+
+			Ok(self.local.insert(key, value))
+		})
+	}
+
+	fn remove<'a>(&'a self, key: &'a K) -> BoxFuture<'a, Result<()>> {
+		Box::pin(async move {
+			let key = self.key(key);
+			self.local.remove(&key);
+
+			let mut connection = self.redis
+				.connection()
+				.await?;
+			connection
+				.del::<_, ()>(&key)
+				.await?;
+			connection
+				.publish::<_, _, ()>(INVALIDATION_CHANNEL, &key)
+				.await?;
+
+			Ok(())
+		})
+	}
+
+	fn metrics(&self) -> CacheMetrics {
+		self.local.metrics()
+	}
+}
\ No newline at end of file
@@ -0,0 +1,54 @@
+use actix_web::HttpRequest;
+use polyumi_models::polyumi::{
+	audit_log::{ self, AuditLogAction, AuditLogEntry },
+	SessionModel
+};
+use once_cell::sync::Lazy;
+use polyumi_util::id::{ marker::UserMarker, Id };
+use std::net::{ IpAddr, SocketAddr };
+
+/// Comma-separated addresses of reverse proxies whose forwarding headers say who the request actually came from.
+pub static TRUSTED_PROXIES: Lazy<Vec<IpAddr>> = Lazy::new(|| std::env::var("TRUSTED_PROXIES")
+	.map(|x| x
+		.split(',')
+		.filter_map(|x| x.trim().parse().ok())
+		.collect()
+	)
+	.unwrap_or_default()
+);
+
+fn ip_address(request: &HttpRequest) -> Option<String> {
+	let peer_ip = request.peer_addr()?.ip();
+	// anyone else could put whatever they like in those headers
+	if !TRUSTED_PROXIES.contains(&peer_ip) {
+		return Some(peer_ip.to_string());
+	}
+
+	request
+		.connection_info()
+		.realip_remote_addr()
+		.map(|x| x
+			.parse::<SocketAddr>()
+			.map(|x| x.ip().to_string())
+			.unwrap_or_else(|_| x.to_string())
+		)
+}
+
+/// Starts an audit log entry for something the session did during this request.
+pub fn entry(request: &HttpRequest, session: &SessionModel, action: AuditLogAction) -> AuditLogEntry {
+	action
+		.build(session.user_id)
+		.session(
+			request
+				.cookie("auth-token")
+				.map(|x| audit_log::session_id(x.value())),
+			ip_address(request)
+		)
+}
+
+/// Same as [`entry`], for requests acting on someone's behalf without their session (e.g. connection callbacks started from mellow).
+pub fn sessionless_entry(request: &HttpRequest, actor_id: Id<UserMarker>, action: AuditLogAction) -> AuditLogEntry {
+	action
+		.build(actor_id)
+		.session(None, ip_address(request))
+}
\ No newline at end of file
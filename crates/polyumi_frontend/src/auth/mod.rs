@@ -2,23 +2,25 @@ use actix_web::{
 	cookie::{ time::Duration, Cookie },
 	HttpRequest
 };
-use dashmap::mapref::one::Ref;
 use jsonwebtoken::{ Algorithm, DecodingKey, EncodingKey, Validation };
 use once_cell::sync::Lazy;
 use polyumi_util::id::{ marker::UserMarker, Id };
 use polyumi_cache::CACHE;
-use polyumi_models::polyumi::{ error::ErrorModelKind, SessionModel };
+use polyumi_models::polyumi::{ audit_log, error::ErrorModelKind, SessionModel };
 use serde::Deserialize;
-use std::ops::Deref;
+use std::{
+	ops::Deref,
+	sync::Arc
+};
 
 use crate::Result;
 
 pub mod passkey;
 
 pub const AUTH_JWT_DURATION: Duration = Duration::days(365);
-pub const AUTH_JWT_KEY: &[u8] = env!("AUTH_JWT_KEY").as_bytes();
-pub static DECODING_KEY: Lazy<DecodingKey> = Lazy::new(|| DecodingKey::from_secret(AUTH_JWT_KEY));
-pub static ENCODING_KEY: Lazy<EncodingKey> = Lazy::new(|| EncodingKey::from_secret(AUTH_JWT_KEY));
+pub static AUTH_JWT_KEY: Lazy<String> = Lazy::new(|| std::env::var("AUTH_JWT_KEY").expect("AUTH_JWT_KEY is not defined"));
+pub static DECODING_KEY: Lazy<DecodingKey> = Lazy::new(|| DecodingKey::from_secret(AUTH_JWT_KEY.as_bytes()));
+pub static ENCODING_KEY: Lazy<EncodingKey> = Lazy::new(|| EncodingKey::from_secret(AUTH_JWT_KEY.as_bytes()));
 pub static VALIDATION: Lazy<Validation> = Lazy::new(|| {
 	let mut validation = Validation::new(Algorithm::HS256);
 	validation.required_spec_claims.clear();
@@ -26,42 +28,49 @@ pub static VALIDATION: Lazy<Validation> = Lazy::new(|| {
 	validation
 });
 
-pub struct SessionOption<'a> {
-	inner: Option<Ref<'a, String, SessionModel>>
+pub struct SessionOption {
+	inner: Option<Arc<SessionModel>>
 }
 
-impl<'a> SessionOption<'a> {
-	pub fn required(self) -> Result<Ref<'a, String, SessionModel>> {
+impl SessionOption {
+	pub fn required(self) -> Result<Arc<SessionModel>> {
 		self
 			.inner
 			.ok_or(ErrorModelKind::MissingCredentials.model())
 	}
 }
 
-impl<'a> Deref for SessionOption<'a> {
-	type Target = Option<Ref<'a, String, SessionModel>>;
+impl Deref for SessionOption {
+	type Target = Option<Arc<SessionModel>>;
 
 	fn deref(&self) -> &Self::Target {
 		&self.inner
 	}
 }
 
-impl<'a> From<Option<Ref<'a, String, SessionModel>>> for SessionOption<'a> {
-	fn from(value: Option<Ref<'a, String, SessionModel>>) -> Self {
+impl From<Option<Arc<SessionModel>>> for SessionOption {
+	fn from(value: Option<Arc<SessionModel>>) -> Self {
 		Self { inner: value }
 	}
 }
 
-pub async fn get_session_from_request(request: &HttpRequest) -> Result<SessionOption<'_>> {
+pub async fn get_session_from_request(request: &HttpRequest) -> Result<SessionOption> {
 	Ok(if let Some(jwt_token_cookie) = get_authorisation_header(request) {
+		// the cache may be shared over redis, so it only ever sees a hash of the token
 		let jwt_token = jwt_token_cookie.value();
-		Some(match CACHE.polyumi.sessions.get(jwt_token) {
+		let session_id = audit_log::session_id(jwt_token);
+		let cached = CACHE.polyumi.sessions
+			.get(&session_id)
+			.await
+			.map_err(|_| ErrorModelKind::Cache.model())?;
+		Some(match cached {
 			Some(x) => x,
 			None => {
 				let session = get_session_from_jwt_token(jwt_token).await?;
-				CACHE.polyumi.sessions.entry(jwt_token.to_string())
-					.insert(session)
-					.downgrade()
+				CACHE.polyumi.sessions
+					.insert(session_id, session)
+					.await
+					.map_err(|_| ErrorModelKind::Cache.model())?
 			}
 		})
 		// TODO: other stuff here, like scope checking, fancy stuff....
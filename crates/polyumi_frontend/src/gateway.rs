@@ -0,0 +1,258 @@
+use polyumi_models::{
+	hakumi::{
+		group::{ GroupMembershipModel, GroupPermissions },
+		CafeModel
+	},
+	mellow::model_event::{
+		stream::{ StreamedEvent, Topic },
+		ModelEventEnvelope, ModelKind, MODEL_EVENT_BUS
+	},
+	polyumi::error::ErrorModelKind
+};
+use polyumi_util::id::{ marker::UserMarker, Id };
+use serde::{ Deserialize, Serialize };
+use std::{
+	collections::{ HashSet, VecDeque },
+	sync::Arc,
+	time::Duration
+};
+use tokio::sync::broadcast::{ self, error::RecvError };
+
+use crate::{
+	routes::v1::mellow::server::verify_membership,
+	Result
+};
+
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+pub const MAX_TOPICS: usize = 64;
+
+const REPLAY_LIMIT: i64 = 1000;
+
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum GatewayMessage {
+	Ready {
+		topics: Vec<Topic>,
+		heartbeat_interval: u64
+	},
+	Event {
+		topics: Vec<Topic>,
+		event: ModelEventEnvelope
+	},
+	Heartbeat,
+	HeartbeatAck,
+	Subscribed {
+		topics: Vec<Topic>
+	},
+	/// Also sent unprompted, when the user isn't allowed to see a topic anymore.
+	Unsubscribed {
+		topics: Vec<Topic>
+	},
+	/// The requested sequence can't be resumed from anymore, the client should refetch whatever it cares about.
+	ResumeFailed,
+	/// This connection fell too far behind, the client should reconnect with `since` set to `sequence`.
+	Reconnect {
+		sequence: Option<i64>
+	},
+	Error {
+		error: ErrorModelKind
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum GatewayCommand {
+	Subscribe {
+		topics: Vec<Topic>
+	},
+	Unsubscribe {
+		topics: Vec<Topic>
+	},
+	Heartbeat
+}
+
+pub struct GatewaySession {
+	user_id: Id<UserMarker>,
+	topics: HashSet<Topic>,
+	receiver: broadcast::Receiver<Arc<StreamedEvent>>,
+	last_sequence: Option<i64>,
+	queued: VecDeque<GatewayMessage>,
+	needs_recheck: bool
+}
+
+impl GatewaySession {
+	/// Subscribes to `topics`, returning the session along with the messages to send before anything else,
+	/// that is `ready` followed by every event after `since`.
+	pub async fn new(user_id: Id<UserMarker>, topics: Vec<Topic>, since: Option<i64>) -> Result<(Self, Vec<GatewayMessage>)> {
+		let mut session = Self {
+			user_id,
+			topics: HashSet::new(),
+			// subscribed before replaying, so nothing can fall in between the two
+			receiver: MODEL_EVENT_BUS.subscribe().await?,
+			last_sequence: since,
+			queued: VecDeque::new(),
+			needs_recheck: false
+		};
+		session
+			.subscribe(topics)
+			.await?;
+
+		let mut messages = vec![GatewayMessage::Ready {
+			topics: session.topics.iter().copied().collect(),
+			heartbeat_interval: HEARTBEAT_INTERVAL.as_millis() as u64
+		}];
+		if let Some(since) = since {
+			let oldest_sequence = StreamedEvent::oldest_sequence().await?;
+			let events = StreamedEvent::get_since(since, REPLAY_LIMIT).await?;
+			if oldest_sequence.is_some_and(|x| since < x - 1) || events.len() as i64 == REPLAY_LIMIT {
+				messages.push(GatewayMessage::ResumeFailed);
+			} else {
+				for event in events {
+					session.last_sequence = Some(event.envelope.sequence);
+					if let Some(message) = session.filter(&event) {
+						messages.push(message);
+					}
+				}
+			}
+		}
+
+		Ok((session, messages))
+	}
+
+	pub async fn subscribe(&mut self, topics: Vec<Topic>) -> Result<Vec<Topic>> {
+		if self.topics.len() + topics.len() > MAX_TOPICS {
+			return Err(ErrorModelKind::InvalidParams.model());
+		}
+		for topic in &topics {
+			if !can_subscribe(topic, self.user_id).await? {
+				return Err(ErrorModelKind::MissingPermission.model());
+			}
+		}
+
+		self.topics.extend(topics.iter().copied());
+		Ok(topics)
+	}
+
+	pub fn unsubscribe(&mut self, topics: Vec<Topic>) -> Vec<Topic> {
+		for topic in &topics {
+			self.topics.remove(topic);
+		}
+		topics
+	}
+
+	pub async fn handle(&mut self, command: GatewayCommand) -> GatewayMessage {
+		match command {
+			GatewayCommand::Subscribe { topics } => match self.subscribe(topics).await {
+				Ok(topics) => GatewayMessage::Subscribed { topics },
+				Err(error) => GatewayMessage::Error { error: error.error }
+			},
+			GatewayCommand::Unsubscribe { topics } => GatewayMessage::Unsubscribed {
+				topics: self.unsubscribe(topics)
+			},
+			GatewayCommand::Heartbeat => GatewayMessage::HeartbeatAck
+		}
+	}
+
+	/// Waits for the next event this session is subscribed to, this is cancel safe.
+	pub async fn next_message(&mut self) -> Option<GatewayMessage> {
+		loop {
+			// the flag is only cleared once the check is done, so being cancelled halfway just means checking again
+			if self.needs_recheck {
+				let topics = self.recheck_topics().await;
+				self.needs_recheck = false;
+				if !topics.is_empty() {
+					self.queued.push_back(GatewayMessage::Unsubscribed { topics });
+				}
+			}
+			if let Some(message) = self.queued.pop_front() {
+				return Some(message);
+			}
+
+			match self.receiver.recv().await {
+				Ok(event) => {
+					// sequences are published in order and the bus passes them on in order,
+					// so anything at or before the last one was already replayed
+					let sequence = event.envelope.sequence;
+					if self.last_sequence.is_some_and(|x| sequence <= x) {
+						continue;
+					}
+					self.last_sequence = Some(sequence);
+					self.needs_recheck = self.affects_access(&event);
+					if let Some(message) = self.filter(&event) {
+						self.queued.push_back(message);
+					}
+				},
+				Err(RecvError::Lagged(_)) => return Some(GatewayMessage::Reconnect {
+					sequence: self.last_sequence
+				}),
+				Err(RecvError::Closed) => return None
+			}
+		}
+	}
+
+	/// Whether this event could've taken away the user's access to something they're subscribed to,
+	/// like them leaving or being removed from a group, a server changing owners, or a group going private.
+	/// roles aren't here since no topic needs any permissions.
+	fn affects_access(&self, event: &StreamedEvent) -> bool {
+		match event.envelope.model {
+			ModelKind::GroupMembership(_, user_id) => user_id == self.user_id,
+			ModelKind::Group(..) => self.topics
+				.iter()
+				.any(|x| matches!(x, Topic::Cafe(_)) || event.topics.contains(x)),
+			ModelKind::Cafe(..) |
+			ModelKind::Server(..) => event.topics
+				.iter()
+				.any(|x| self.topics.contains(x)),
+			_ => false
+		}
+	}
+
+	/// Drops every topic that doesn't pass [`can_subscribe`] anymore, returning them.
+	async fn recheck_topics(&mut self) -> Vec<Topic> {
+		let mut topics = Vec::new();
+		for topic in &self.topics {
+			// if it can't be checked right now, it's safer to assume the worst
+			if !can_subscribe(topic, self.user_id).await.unwrap_or(false) {
+				topics.push(*topic);
+			}
+		}
+		for topic in &topics {
+			self.topics.remove(topic);
+		}
+
+		topics
+	}
+
+	fn filter(&self, event: &StreamedEvent) -> Option<GatewayMessage> {
+		let topics: Vec<Topic> = event.topics
+			.iter()
+			.filter(|x| self.topics.contains(x))
+			.copied()
+			.collect();
+		if topics.is_empty() {
+			return None;
+		}
+
+		Some(GatewayMessage::Event {
+			topics,
+			event: event.envelope.clone()
+		})
+	}
+}
+
+async fn can_subscribe(topic: &Topic, user_id: Id<UserMarker>) -> Result<bool> {
+	Ok(match topic {
+		// cafes can be read by anyone who can see whatever they belong to
+		Topic::Cafe(cafe_id) => match CafeModel::get(*cafe_id).await? {
+			Some(cafe) => cafe.is_visible_to(Some(user_id)).await?,
+			None => false
+		},
+		Topic::Group(group_id) => GroupMembershipModel::get_user(*group_id, user_id)
+			.await?
+			.is_some_and(|x| !x.is_pending),
+		Topic::MellowServer(server_id) => verify_membership(*server_id, user_id, GroupPermissions::NONE)
+			.await
+			.is_ok(),
+		Topic::User(id) => *id == user_id
+	})
+}
\ No newline at end of file
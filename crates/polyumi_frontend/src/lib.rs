@@ -1,5 +1,5 @@
 #![feature(duration_constructors, let_chains)]
-use log::info;
+use log::{ info, warn };
 use std::pin::Pin;
 use actix_web::{
 	error::InternalError,
@@ -8,12 +8,20 @@ use actix_web::{
 	App, HttpResponse, HttpServer
 };
 use once_cell::sync::Lazy;
-use polyumi_util::PG_POOL;
+use polyumi_util::{ MIGRATOR, PG_POOL };
 use polyumi_cache::CACHE;
-use polyumi_models::polyumi::ErrorModel;
+use polyumi_models::{
+	mellow::model_event::{ outbox, ABSOLUTESOLVER, MODEL_EVENT_BUS, MODEL_EVENT_ENDPOINTS },
+	polyumi::{ error::ErrorModelKind, ErrorModel }
+};
 
+pub mod audit;
 pub mod auth;
+pub mod gateway;
+pub mod opengraph;
+pub mod pagination;
 pub mod routes;
+pub mod storage;
 mod templates;
 
 pub type Result<T> = core::result::Result<T, ErrorModel>;
@@ -26,21 +34,55 @@ pub async fn setup_frontend() -> std::io::Result<()> {
 	Lazy::force(&CACHE);
 	Lazy::force(&auth::DECODING_KEY);
 	Lazy::force(&auth::VALIDATION);
-	Pin::static_ref(&PG_POOL).await;
+	Lazy::force(&routes::v1::connection_callbacks::API_URL);
+	Lazy::force(&routes::v1::connection_callbacks::DISCORD_APP_ID);
+	Lazy::force(&routes::v1::connection_callbacks::DISCORD_APP_SECRET);
+	Lazy::force(&routes::v1::connection_callbacks::PATREON_APP_ID);
+	Lazy::force(&routes::v1::connection_callbacks::PATREON_APP_SECRET);
+	Lazy::force(&routes::v1::connection_callbacks::WEBSITE_URL);
+	Lazy::force(&storage::STORAGE);
+	Lazy::force(&ABSOLUTESOLVER);
+
+	let pinned = Pin::static_ref(&PG_POOL).await;
+	MIGRATOR
+		.run(pinned.get_ref())
+		.await
+		.expect("failed to run database migrations");
+
+	if MODEL_EVENT_ENDPOINTS.is_empty() {
+		warn!("MODEL_EVENT_ENDPOINTS is empty, model events will only be delivered to webhooks");
+	}
+	tokio::spawn(outbox::run_outbox());
+
+	// keeps the cache in line with writes made by other instances
+	MODEL_EVENT_BUS
+		.listen()
+		.await
+		.expect("failed to listen for model events");
+	CACHE
+		.listen()
+		.await
+		.expect("failed to listen for cache invalidations");
 
 	HttpServer::new(|| {
         App::new()
 			.wrap(Logger::new("%r  →  %s, %b bytes, took %Dms"))
-            .configure(routes::v1::config)
-			.app_data(web::JsonConfig::default().error_handler(|error,_| InternalError::from_response(
-				"",
-				HttpResponse::BadRequest()
-					.content_type("application/json")
-					.body(format!(r#"{{"error":"json error: {error}"}}"#)),
-			).into()))
-			.default_service(web::get().wrap(polyumi_util::default_cors()).to(routes::default::default))
+			.configure(config)
     })
 		.bind(bind_addr)?
 		.run()
 		.await
+}
+
+pub fn config(config: &mut web::ServiceConfig) {
+	config
+		.configure(routes::v1::config)
+		.app_data(web::JsonConfig::default().error_handler(|error,_| InternalError::from_response(
+			"",
+			HttpResponse::BadRequest()
+				.content_type("application/json")
+				.body(format!(r#"{{"error":"json error: {error}"}}"#)),
+		).into()))
+		.app_data(web::QueryConfig::default().error_handler(|_, _| ErrorModelKind::InvalidQuery.model().into()))
+		.default_service(web::get().to(routes::default::default).wrap(polyumi_util::default_cors()));
 }
\ No newline at end of file
@@ -0,0 +1,203 @@
+use log::warn;
+use std::time::Duration;
+use reqwest::{
+	header::{ CONTENT_TYPE, LOCATION },
+	Response, Url
+};
+use polyumi_models::{
+	hakumi::cafe::order::CafeOrderEmbed,
+	polyumi::error::ErrorModelKind
+};
+use polyumi_util::{ pinned_client, public_addresses };
+
+use crate::Result;
+
+const MAX_REDIRECTS: usize = 3;
+const MAX_BODY_SIZE: usize = 512 * 1024;
+const MAX_TITLE_LENGTH: usize = 256;
+const MAX_DESCRIPTION_LENGTH: usize = 1024;
+
+/// The opengraph metadata of `url`, reused for a day after it's first fetched.
+/// Links to anything that isn't on the public internet are refused.
+pub async fn embed(url: &str) -> Result<CafeOrderEmbed> {
+	if let Some(embed) = CafeOrderEmbed::get_cached(url, chrono::Duration::days(1)).await? {
+		return Ok(embed);
+	}
+
+	let mut embed = CafeOrderEmbed {
+		url: url.to_string(),
+		title: None,
+		description: None,
+		image_url: None,
+		site_name: None
+	};
+
+	// a link that doesn't load is still embedded, just without anything to show for it
+	match fetch(url).await? {
+		Ok(response) => {
+			let content_type = response
+				.headers()
+				.get(CONTENT_TYPE)
+				.and_then(|x| x.to_str().ok())
+				.unwrap_or_default()
+				.to_ascii_lowercase();
+			if content_type.starts_with("image/") {
+				embed.image_url = Some(response.url().to_string());
+			} else if content_type.starts_with("text/html") {
+				let base_url = response.url().clone();
+				match read_body(response).await {
+					Ok(html) => parse(&mut embed, &base_url, &html),
+					Err(error) => warn!("failed to read {url} for embedding: {error}")
+				}
+			}
+		},
+		Err(error) => warn!("failed to fetch {url} for embedding: {error}")
+	}
+
+	embed.cache().await?;
+	Ok(embed)
+}
+
+// redirects are followed by hand, so wherever they lead is held to the same rules as the original link.
+// every request is pinned to the addresses that were checked, so the name can't resolve somewhere else in the meantime.
+async fn fetch(url: &str) -> Result<reqwest::Result<Response>> {
+	let mut url = Url::parse(url)
+		.map_err(|_| ErrorModelKind::InvalidParams.model())?;
+	for _ in 0..=MAX_REDIRECTS {
+		let addresses = public_addresses(&url, &[])
+			.await
+			.ok_or(ErrorModelKind::InvalidParams.model())?;
+		let client = pinned_client(&url, &addresses)
+			.timeout(Duration::from_secs(5))
+			.user_agent("polyumi (link embeds)")
+			.build()?;
+
+		let response = match client.get(url.clone()).send().await {
+			Ok(response) => response,
+			Err(error) => return Ok(Err(error))
+		};
+		if !response.status().is_redirection() {
+			return Ok(response.error_for_status());
+		}
+
+		let Some(location) = response
+			.headers()
+			.get(LOCATION)
+			.and_then(|x| x.to_str().ok())
+			.and_then(|x| url.join(x).ok())
+		else {
+			return Ok(response.error_for_status());
+		};
+		url = location;
+	}
+
+	Err(ErrorModelKind::InvalidParams.model())
+}
+
+async fn read_body(mut response: Response) -> reqwest::Result<String> {
+	let mut body = Vec::new();
+	while body.len() < MAX_BODY_SIZE && let Some(chunk) = response.chunk().await? {
+		body.extend_from_slice(&chunk);
+	}
+	body.truncate(MAX_BODY_SIZE);
+
+	Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Fills in `embed` from the `<meta>` tags in `html`, falling back to `<title>` and the plain description when there's no opengraph equivalent.
+pub fn parse(embed: &mut CafeOrderEmbed, base_url: &Url, html: &str) {
+	// lowercasing ascii doesn't move anything around, so offsets found in one work in the other
+	let lowercase = html.to_ascii_lowercase();
+	let mut description = None;
+	let mut offset = 0;
+	while let Some(start) = lowercase[offset..].find("<meta") {
+		let start = offset + start + "<meta".len();
+		let end = lowercase[start..]
+			.find('>')
+			.map_or(html.len(), |x| start + x);
+		offset = end;
+
+		let attributes = attributes(&html[start..end]);
+		let attribute = |name: &str| attributes
+			.iter()
+			.find(|x| x.0 == name)
+			.map(|x| x.1.clone());
+		let (Some(key), Some(content)) = (attribute("property").or_else(|| attribute("name")), attribute("content")) else {
+			continue;
+		};
+		match key.to_ascii_lowercase().as_str() {
+			"og:title" => embed.title = Some(content),
+			"og:description" => embed.description = Some(content),
+			"og:image" | "og:image:url" => embed.image_url = base_url
+				.join(&content)
+				.ok()
+				.filter(|x| matches!(x.scheme(), "http" | "https"))
+				.map(Into::into),
+			"og:site_name" => embed.site_name = Some(content),
+			"description" => description = Some(content),
+			_ => ()
+		}
+	}
+
+	if embed.title.is_none() && let Some(start) = lowercase.find("<title") {
+		let start = lowercase[start..]
+			.find('>')
+			.map_or(html.len(), |x| start + x + 1);
+		if let Some(end) = lowercase[start..].find("</title") {
+			embed.title = Some(decode_entities(html[start..start + end].trim()));
+		}
+	}
+	if embed.description.is_none() {
+		embed.description = description;
+	}
+
+	embed.title = truncated(embed.title.take(), MAX_TITLE_LENGTH);
+	embed.description = truncated(embed.description.take(), MAX_DESCRIPTION_LENGTH);
+	embed.site_name = truncated(embed.site_name.take(), MAX_TITLE_LENGTH);
+}
+
+// name="value", name='value' and name=value, in whatever order they come
+fn attributes(tag: &str) -> Vec<(String, String)> {
+	let mut attributes = Vec::new();
+	let mut chars = tag.chars().peekable();
+	loop {
+		while chars.next_if(|x| x.is_whitespace() || *x == '/').is_some() {}
+		let name: String = std::iter::from_fn(|| chars.next_if(|x| !x.is_whitespace() && *x != '=' && *x != '/'))
+			.collect();
+		if name.is_empty() {
+			return attributes;
+		}
+
+		while chars.next_if(|x| x.is_whitespace()).is_some() {}
+		let mut value = String::new();
+		if chars.next_if_eq(&'=').is_some() {
+			while chars.next_if(|x| x.is_whitespace()).is_some() {}
+			match chars.next_if(|x| *x == '"' || *x == '\'') {
+				Some(quote) => value.extend(std::iter::from_fn(|| chars.next_if(|x| *x != quote))),
+				None => value.extend(std::iter::from_fn(|| chars.next_if(|x| !x.is_whitespace())))
+			}
+			chars.next_if(|x| *x == '"' || *x == '\'');
+		}
+		attributes.push((name.to_ascii_lowercase(), decode_entities(value.trim())));
+	}
+}
+
+fn decode_entities(value: &str) -> String {
+	value
+		.replace("&quot;", "\"")
+		.replace("&#39;", "'")
+		.replace("&#x27;", "'")
+		.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&amp;", "&")
+}
+
+fn truncated(value: Option<String>, length: usize) -> Option<String> {
+	value
+		.filter(|x| !x.is_empty())
+		.map(|x| x
+			.chars()
+			.take(length)
+			.collect()
+		)
+}
\ No newline at end of file
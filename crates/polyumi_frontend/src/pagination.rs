@@ -0,0 +1,114 @@
+use actix_web::{ dev::Payload, web, FromRequest, HttpRequest };
+use base64::{ prelude::BASE64_URL_SAFE_NO_PAD, Engine };
+use futures::future::{ ready, Ready };
+use polyumi_models::{
+	pagination::SortDirection,
+	polyumi::{ error::ErrorModelKind, ErrorModel }
+};
+use serde::{ de::DeserializeOwned, Deserialize, Serialize };
+
+use crate::Result;
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 100;
+
+#[derive(Deserialize)]
+struct RawPageQuery {
+	cursor: Option<String>,
+	limit: Option<i64>,
+	direction: Option<SortDirection>
+}
+
+/// The `cursor`, `limit` and `direction` query parameters every list route takes,
+/// `C` being whatever a route needs to know to carry on from where the last page ended.
+pub struct PageQuery<C> {
+	pub cursor: Option<C>,
+	pub limit: i64,
+	direction: Option<SortDirection>
+}
+
+impl<C> PageQuery<C> {
+	pub fn direction_or(&self, default: SortDirection) -> SortDirection {
+		self.direction.unwrap_or(default)
+	}
+
+	/// One more than the limit, the extra row only tells us whether there's another page.
+	pub fn fetch_limit(&self) -> i64 {
+		self.limit + 1
+	}
+
+	fn parse(query_string: &str) -> Result<Self>
+	where
+		C: DeserializeOwned
+	{
+		let query = web::Query::<RawPageQuery>::from_query(query_string)
+			.map_err(|_| ErrorModelKind::InvalidQuery.model())?
+			.into_inner();
+		let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+		if !(1..=MAX_LIMIT).contains(&limit) {
+			return Err(ErrorModelKind::InvalidQuery.model());
+		}
+
+		Ok(Self {
+			cursor: query.cursor
+				.map(|x| decode_cursor(&x).ok_or_else(|| ErrorModelKind::InvalidQuery.model()))
+				.transpose()?,
+			limit,
+			direction: query.direction
+		})
+	}
+}
+
+impl<C: DeserializeOwned> FromRequest for PageQuery<C> {
+	type Error = ErrorModel;
+	type Future = Ready<Result<Self>>;
+
+	fn from_request(request: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+		ready(Self::parse(request.query_string()))
+	}
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+	pub items: Vec<T>,
+	pub total: i64,
+	pub next_cursor: Option<String>
+}
+
+impl<T> Page<T> {
+	/// Builds a page out of rows fetched with [`PageQuery::fetch_limit`], `cursor` picks out where the next page starts from.
+	pub fn new<C: Serialize, Q>(mut items: Vec<T>, query: &PageQuery<Q>, total: i64, cursor: impl FnOnce(&T) -> C) -> Self {
+		let next_cursor = if items.len() as i64 > query.limit {
+			items.truncate(query.limit as usize);
+			items
+				.last()
+				.map(|x| encode_cursor(&cursor(x)))
+		} else { None };
+
+		Self {
+			items,
+			total,
+			next_cursor
+		}
+	}
+
+	pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+		Page {
+			items: self.items
+				.into_iter()
+				.map(f)
+				.collect(),
+			total: self.total,
+			next_cursor: self.next_cursor
+		}
+	}
+}
+
+// cursors are opaque to clients, so what's inside can change without breaking anyone
+fn encode_cursor<C: Serialize>(cursor: &C) -> String {
+	BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).unwrap_or_default())
+}
+
+fn decode_cursor<C: DeserializeOwned>(value: &str) -> Option<C> {
+	serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(value).ok()?).ok()
+}
\ No newline at end of file
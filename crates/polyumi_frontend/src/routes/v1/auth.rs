@@ -1,6 +1,6 @@
 use actix_web::{ web, post, HttpResponse };
 use polyumi_cache::CACHE;
-use polyumi_models::polyumi::auth::PasskeyChallengeModel;
+use polyumi_models::polyumi::{ auth::PasskeyChallengeModel, error::ErrorModelKind };
 
 use crate::Result;
 
@@ -24,7 +24,9 @@ async fn create_passkey_challenge() -> Result<HttpResponse> {
 	CACHE
 		.polyumi
 		.passkey_challenges
-		.insert(challenge.id, PasskeyChallengeModel::default());
+		.insert(challenge.id, PasskeyChallengeModel::default())
+		.await
+		.map_err(|_| ErrorModelKind::Cache.model())?;
 
 	Ok(HttpResponse::Ok().body(response))
 }
@@ -43,13 +45,16 @@ async fn sign_in_with_passkey(/*payload: web::Json<SignInWithPasskey>*/) -> Resu
 	/*let challenge = CACHE
 		.polyumi
 		.passkey_challenge(payload.challenge_id)
+		.await
+		.map_err(|_| ErrorModelKind::Cache.model())?
 		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::PasskeyChallenge, Some(payload.challenge_id)))?;
 
 	let passkey = CACHE
 		.polyumi
 		.passkey(&payload.passkey_id)
 		.await
-		.unwrap();
+		.map_err(|_| ErrorModelKind::Cache.model())?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::Passkey, Some(&payload.passkey_id)))?;
 
 	verify_sign_in(challenge.challenge.clone(), &passkey.public_key.clone(), &payload.response)?;
 
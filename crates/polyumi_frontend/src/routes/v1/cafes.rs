@@ -1,30 +1,1014 @@
-use actix_web::{ get, web, HttpResponse };
+use std::pin::Pin;
+use actix_web::{ delete, get, patch, post, put, web, HttpRequest, HttpResponse };
+use bytes::Bytes;
+use chrono::Utc;
+use log::warn;
+use serde::{ Deserialize, Serialize };
+use serde_json::json;
+use sqlx::PgConnection;
+use polyumi_util::{
+	id::{ marker::{ GroupMarker, UserMarker }, Id },
+	PG_POOL
+};
 use polyumi_models::{
-	hakumi::cafe::{ CafeModel, CafeOrderModel },
-	polyumi::error::{ ResourceKind, ErrorModelKind }
+	hakumi::{
+		cafe::{
+			order::{ CafeOrderAttachment, CafeOrderKind, CafeOrderMessage },
+			report::{ CafeOrderReportModel, CafeOrderReportResolution },
+			CafeKind, CafeModel, CafeOrderModel, CafePostPermission, CafeWordFilterAction
+		},
+		group::{ GroupMembershipModel, GroupPermissions },
+		user::block::UserBlockModel
+	},
+	mellow::model_event::{ ModelEventKind, ModelKind },
+	pagination::SortDirection,
+	polyumi::{
+		audit_log::{ AuditLogAction, AuditLogEntryModel, AuditLogScope },
+		error::{ ResourceKind, ErrorModelKind },
+		SessionModel
+	}
+};
+use uuid::Uuid;
+use validator::{ Validate, ValidationError };
+
+use crate::{
+	audit,
+	auth::get_session_from_request,
+	opengraph,
+	pagination::{ Page, PageQuery },
+	storage::STORAGE,
+	Result
 };
 
-use crate::Result;
+const MAX_ATTACHMENT_SIZE: usize = 8 * 1024 * 1024;
 
 pub fn config(config: &mut web::ServiceConfig) {
 	config.service(web::scope("cafe")
+		.service(create_cafe)
 		.service(cafe_get)
+		.service(cafe_get_group)
 		.service(web::scope("{cafe_id}")
+			.app_data(web::PayloadConfig::new(MAX_ATTACHMENT_SIZE))
+			.service(update_cafe)
 			.service(get_cafe_orders)
+			.service(get_cafe_order_replies)
+			.service(create_cafe_order)
+			.service(create_cafe_attachment)
+			.service(create_cafe_embed)
+			.service(update_cafe_order)
+			.service(delete_cafe_order)
+			.service(add_cafe_order_reaction)
+			.service(remove_cafe_order_reaction)
+			.service(report_cafe_order)
+			.service(get_cafe_reports)
+			.service(resolve_cafe_report)
+			.service(get_cafe_audit_log)
 		)
 	);
 }
 
 #[get("{cafe_ref}")]
-async fn cafe_get(path: web::Path<u64>) -> Result<HttpResponse> {
-	match CafeModel::get(*path).await? {
-		Some(model) => Ok(HttpResponse::Ok().json(model)),
-		None => Err(ErrorModelKind::not_found(ResourceKind::Group, Some(path)))
+async fn cafe_get(request: HttpRequest, path: web::Path<u64>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request).await?;
+	cafe_response(&request, &get_cafe(*path, session.as_ref().map(|x| x.user_id)).await?).await
+}
+
+#[get("group/{group_id}")]
+async fn cafe_get_group(request: HttpRequest, path: web::Path<Id<GroupMarker>>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request).await?;
+	let viewer_id = session.as_ref().map(|x| x.user_id);
+	match CafeModel::get_group(*path).await? {
+		Some(model) if model.is_visible_to(viewer_id).await? => cafe_response(&request, &model).await,
+		_ => Err(ErrorModelKind::not_found(ResourceKind::Cafe, Some(path)))
+	}
+}
+
+/// Moderators get to see the word filter, nobody else does.
+async fn cafe_response(request: &HttpRequest, cafe: &CafeModel) -> Result<HttpResponse> {
+	let session = get_session_from_request(request).await?;
+	if let Some(session) = session.as_ref() && can_moderate(cafe, session.user_id).await? {
+		return Ok(HttpResponse::Ok().json(cafe.moderator_view()));
+	}
+
+	Ok(HttpResponse::Ok().json(cafe))
+}
+
+#[derive(Deserialize, Validate)]
+struct CreateCafe {
+	kind: CafeKind,
+	group_id: Option<Id<GroupMarker>>,
+	#[validate(length(max = 64), custom(function = "validate_content"))]
+	name: Option<String>
+}
+
+/// Group cafes belong to the group, topic cafes to the group they're made for or whoever made them.
+/// Profile cafes come with users, so they can't be made here.
+#[post("")]
+async fn create_cafe(request: HttpRequest, payload: web::Json<CreateCafe>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	payload.validate()?;
+
+	match (&payload.kind, payload.group_id, &payload.name) {
+		(CafeKind::Group, Some(group_id), _) => {
+			if let Some(cafe) = CafeModel::get_group(group_id).await? {
+				return Err(ErrorModelKind::GroupCafeExists { cafe_id: cafe.id }.model());
+			}
+		},
+		(CafeKind::Topic, _, Some(_)) => (),
+		_ => return Err(ErrorModelKind::InvalidParams.model())
+	}
+	if let Some(group_id) = payload.group_id && !GroupMembershipModel::get_permissions(group_id, session.user_id)
+		.await?
+		.is_some_and(|x| x.contains(GroupPermissions::MANAGE_CAFE))
+	{
+		return Err(ErrorModelKind::MissingPermission.model());
 	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let Some(record) = sqlx::query!(
+		"
+		INSERT INTO cafes (kind, creator_user_id, owner_group_id, owner_user_id, name)
+		VALUES ($1, $2, $3, $4, $5)
+		ON CONFLICT (owner_group_id) WHERE kind = 'group' DO NOTHING
+		RETURNING id
+		",
+		payload.kind.name(),
+		session.user_id.value,
+		payload.group_id.map(|x| x.value),
+		payload.group_id.is_none().then_some(session.user_id.value),
+		payload.name
+	)
+		.fetch_optional(&mut *transaction)
+		.await?
+	else {
+		// someone else made the group's cafe since we checked
+		let cafe_id = match payload.group_id {
+			Some(group_id) => CafeModel::get_group(group_id).await?.map(|x| x.id),
+			None => None
+		};
+		return Err(cafe_id
+			.map(|cafe_id| ErrorModelKind::GroupCafeExists { cafe_id }.model())
+			.unwrap_or_else(|| ErrorModelKind::InternalError.model())
+		);
+	};
+	let cafe_id = record.id as u64;
+
+	audit::entry(&request, &session, AuditLogAction::CafeCreated)
+		.target(ModelKind::Cafe(cafe_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Created
+		.build(ModelKind::Cafe(cafe_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	cafe_response(&request, &get_cafe(cafe_id, Some(session.user_id)).await?).await
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+struct UpdateCafe {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[validate(length(max = 64), custom(function = "validate_content"))]
+	name: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	post_permission: Option<CafePostPermission>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[validate(range(max = 21600))]
+	slow_mode_seconds: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	locked: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[validate(length(max = 100), custom(function = "validate_word_filter"))]
+	word_filter: Option<Vec<String>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	word_filter_action: Option<CafeWordFilterAction>
+}
+
+fn validate_word_filter(words: &[String]) -> core::result::Result<(), ValidationError> {
+	for word in words {
+		if word.chars().count() > 64 {
+			return Err(ValidationError::new("length"));
+		}
+		validate_content(word)?;
+	}
+
+	Ok(())
+}
+
+#[patch("")]
+async fn update_cafe(request: HttpRequest, path: web::Path<u64>, payload: web::Json<UpdateCafe>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	payload.validate()?;
+
+	let cafe = get_cafe(*path, Some(session.user_id)).await?;
+	if !can_moderate(&cafe, session.user_id).await? {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+	// only topic cafes have names of their own
+	if payload.name.is_some() && cafe.kind != CafeKind::Topic {
+		return Err(ErrorModelKind::InvalidParams.model());
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		UPDATE cafes
+		SET
+			name = COALESCE($2, name),
+			post_permission = COALESCE($3, post_permission),
+			slow_mode_seconds = COALESCE($4, slow_mode_seconds),
+			locked = COALESCE($5, locked),
+			word_filter = COALESCE($6, word_filter),
+			word_filter_action = COALESCE($7, word_filter_action)
+		WHERE id = $1
+		",
+		cafe.id as i64,
+		payload.name,
+		payload.post_permission.map(|x| x.name()),
+		payload.slow_mode_seconds.map(|x| x as i32),
+		payload.locked,
+		payload.word_filter.as_deref(),
+		payload.word_filter_action.map(|x| x.name())
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::CafeUpdated)
+		.target(ModelKind::Cafe(cafe.id))
+		.changes(&json!({
+			"name": cafe.name,
+			"post_permission": cafe.settings.post_permission,
+			"slow_mode_seconds": cafe.settings.slow_mode_seconds,
+			"locked": cafe.settings.locked,
+			"word_filter": cafe.settings.word_filter,
+			"word_filter_action": cafe.settings.word_filter_action
+		}), &*payload)?
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Updated
+		.build(ModelKind::Cafe(cafe.id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().json(get_cafe(cafe.id, Some(session.user_id)).await?.moderator_view()))
 }
 
 #[get("orders")]
-async fn get_cafe_orders(path: web::Path<u64>) -> Result<HttpResponse> {
-	let orders = CafeOrderModel::get_cafe_many(*path).await?;
-	Ok(HttpResponse::Ok().json(orders))
+async fn get_cafe_orders(request: HttpRequest, path: web::Path<u64>, page: PageQuery<u64>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request).await?;
+	let viewer_id = session.as_ref().map(|x| x.user_id);
+
+	let cafe = get_cafe(*path, viewer_id).await?;
+	let orders = CafeOrderModel::get_cafe_page(cafe.id, viewer_id, page.direction_or(SortDirection::Desc), page.cursor, page.fetch_limit()).await?;
+	let total = CafeOrderModel::count_cafe(cafe.id, viewer_id).await?;
+	Ok(HttpResponse::Ok().json(Page::new(orders, &page, total, |x| x.id)))
+}
+
+#[get("orders/{order_id}/replies")]
+async fn get_cafe_order_replies(request: HttpRequest, path: web::Path<(u64, u64)>, page: PageQuery<u64>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request).await?;
+	let viewer_id = session.as_ref().map(|x| x.user_id);
+
+	// threads read top to bottom, unlike the cafe itself
+	let (cafe_id, order_id) = *path;
+	get_cafe(cafe_id, viewer_id).await?;
+	let order = get_cafe_order(cafe_id, order_id, viewer_id).await?;
+	let replies = CafeOrderModel::get_reply_page(cafe_id, order.id, viewer_id, page.direction_or(SortDirection::Asc), page.cursor, page.fetch_limit()).await?;
+	let total = CafeOrderModel::count_replies(cafe_id, order.id, viewer_id).await?;
+	Ok(HttpResponse::Ok().json(Page::new(replies, &page, total, |x| x.id)))
+}
+
+/// Finds a cafe the viewer is allowed to see, a private group's cafes are as hidden as the group is.
+async fn get_cafe(cafe_id: u64, viewer_id: Option<Id<UserMarker>>) -> Result<CafeModel> {
+	match CafeModel::get(cafe_id).await? {
+		Some(cafe) if cafe.is_visible_to(viewer_id).await? => Ok(cafe),
+		_ => Err(ErrorModelKind::not_found(ResourceKind::Cafe, Some(cafe_id)))
+	}
+}
+
+async fn get_cafe_order(cafe_id: u64, order_id: u64, viewer_id: Option<Id<UserMarker>>) -> Result<CafeOrderModel> {
+	CafeOrderModel::get(cafe_id, order_id, viewer_id)
+		.await?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::CafeOrder, Some(order_id)))
+}
+
+/// Whoever owns the cafe can remove anything in it, as can group members who manage the cafe.
+async fn can_moderate(cafe: &CafeModel, user_id: Id<UserMarker>) -> Result<bool> {
+	if cafe.owner_user_id == Some(user_id) {
+		return Ok(true);
+	}
+	if let Some(owner_group_id) = cafe.owner_group_id {
+		return Ok(GroupMembershipModel::get_permissions(owner_group_id, user_id)
+			.await?
+			.is_some_and(|x| x.contains(GroupPermissions::MANAGE_CAFE))
+		);
+	}
+
+	Ok(false)
+}
+
+/// Moderators can always place orders, everyone else has to be let in by the cafe's settings and keep to its slow mode.
+async fn verify_can_place_orders(cafe: &CafeModel, user_id: Id<UserMarker>, is_reaction: bool) -> Result<()> {
+	if can_moderate(cafe, user_id).await? {
+		return Ok(());
+	}
+	// people blocked by a user can't place orders on their profile, though they can still see it
+	if cafe.kind == CafeKind::Profile && let Some(owner_user_id) = cafe.owner_user_id && UserBlockModel::is_blocked(owner_user_id, user_id).await? {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+	if cafe.settings.locked {
+		return Err(ErrorModelKind::CafeLocked.model());
+	}
+
+	let is_allowed = match (cafe.settings.post_permission, cafe.owner_group_id) {
+		(CafePostPermission::Everyone, _) => true,
+		(CafePostPermission::Members, Some(owner_group_id)) => GroupMembershipModel::get_permissions(owner_group_id, user_id)
+			.await?
+			.is_some(),
+		_ => false
+	};
+	if !is_allowed {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+
+	if !is_reaction && cafe.settings.slow_mode_seconds > 0 &&
+		let Some(last_placed_at) = CafeOrderModel::last_placed_at(cafe.id, user_id).await?
+	{
+		let retry_after = (last_placed_at - Utc::now()).num_seconds() + cafe.settings.slow_mode_seconds as i64;
+		if retry_after > 0 {
+			return Err(ErrorModelKind::CafeSlowMode { retry_after: retry_after as u64 }.model());
+		}
+	}
+
+	Ok(())
+}
+
+/// Runs `content` through the cafe's word filter, returning the word it should be flagged for, if any.
+async fn apply_word_filter(cafe: &CafeModel, user_id: Id<UserMarker>, content: &str) -> Result<Option<String>> {
+	let Some(word) = cafe.settings.filtered_word(content) else {
+		return Ok(None);
+	};
+	if can_moderate(cafe, user_id).await? {
+		return Ok(None);
+	}
+
+	match cafe.settings.word_filter_action {
+		CafeWordFilterAction::Flag => Ok(Some(word.to_string())),
+		CafeWordFilterAction::Reject => Err(ErrorModelKind::CafeWordFiltered { word: word.to_string() }.model())
+	}
+}
+
+/// Puts an order in the moderation queue on the word filter's behalf, edits that still trip it only update the reason.
+async fn flag_cafe_order(connection: &mut PgConnection, cafe_id: u64, order_id: u64, word: &str) -> Result<()> {
+	sqlx::query!(
+		"
+		INSERT INTO cafe_order_reports (cafe_id, order_id, reason)
+		VALUES ($1, $2, $3)
+		ON CONFLICT (order_id) WHERE reporter_id IS NULL AND resolved_at IS NULL DO UPDATE
+		SET reason = excluded.reason
+		",
+		cafe_id as i64,
+		order_id as i64,
+		format!("word filter: {word}")
+	)
+		.execute(connection)
+		.await?;
+
+	Ok(())
+}
+
+fn validate_content(content: &str) -> core::result::Result<(), ValidationError> {
+	if content.trim().is_empty() {
+		Err(ValidationError::new("blank"))
+	} else {
+		Ok(())
+	}
+}
+
+// no custom emoji (yet), so anything that's made up of more than ascii and fits in a handful of codepoints will do
+fn validate_emoji(emoji: &str) -> Result<()> {
+	if emoji.is_empty() || emoji.len() > 32 || emoji.is_ascii() || emoji.chars().any(|x| x.is_whitespace() || x.is_control()) {
+		return Err(ErrorModelKind::InvalidParams.model());
+	}
+
+	Ok(())
+}
+
+/// Only messages, attachments and embeds that aren't replies themselves can be replied to, threads don't nest.
+async fn get_reply_parent(cafe_id: u64, parent_id: u64) -> Result<CafeOrderModel> {
+	let parent = get_cafe_order(cafe_id, parent_id, None).await?;
+	match parent.kind {
+		CafeOrderKind::Message(CafeOrderMessage::Basic { .. }) |
+		CafeOrderKind::Attachment(..) |
+		CafeOrderKind::Embed(..) => Ok(parent),
+		_ => Err(ErrorModelKind::InvalidParams.model())
+	}
+}
+
+/// What the body of an image attachment actually is, going by its first few bytes rather than what the client claims.
+fn image_type(body: &[u8]) -> Option<(&'static str, &'static str)> {
+	if body.starts_with(b"\x89PNG\r\n\x1a\n") {
+		Some(("image/png", "png"))
+	} else if body.starts_with(b"\xff\xd8\xff") {
+		Some(("image/jpeg", "jpg"))
+	} else if body.starts_with(b"GIF87a") || body.starts_with(b"GIF89a") {
+		Some(("image/gif", "gif"))
+	} else if body.len() >= 12 && body.starts_with(b"RIFF") && &body[8..12] == b"WEBP" {
+		Some(("image/webp", "webp"))
+	} else {
+		None
+	}
+}
+
+/// Places an order of any kind, returning its id, or nothing if it's a reaction that was already there.
+async fn place_cafe_order(request: &HttpRequest, session: &SessionModel, cafe_id: u64, kind: CafeOrderKind, flagged_word: Option<String>) -> Result<Option<u64>> {
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let Some(order_id) = sqlx::query!(
+		"
+		INSERT INTO cafe_orders (cafe_id, author_id, kind, payload)
+		VALUES ($1, $2, $3, $4)
+		ON CONFLICT DO NOTHING
+		RETURNING id
+		",
+		cafe_id as i64,
+		session.user_id.value,
+		kind.name(),
+		kind.payload()?
+	)
+		.fetch_optional(&mut *transaction)
+		.await?
+		.map(|x| x.id as u64)
+	else {
+		return Ok(None);
+	};
+
+	if let Some(word) = flagged_word {
+		flag_cafe_order(&mut transaction, cafe_id, order_id, &word).await?;
+	}
+
+	audit::entry(request, session, AuditLogAction::CafeOrderCreated)
+		.target(ModelKind::CafeOrder(cafe_id, order_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Created
+		.build(ModelKind::CafeOrder(cafe_id, order_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(Some(order_id))
+}
+
+#[derive(Deserialize, Validate)]
+struct CreateCafeOrder {
+	#[validate(length(max = 2000), custom(function = "validate_content"))]
+	content: String,
+	parent_id: Option<u64>
+}
+
+#[post("orders")]
+async fn create_cafe_order(request: HttpRequest, path: web::Path<u64>, payload: web::Json<CreateCafeOrder>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	payload.validate()?;
+
+	let cafe = get_cafe(*path, Some(session.user_id)).await?;
+	verify_can_place_orders(&cafe, session.user_id, false).await?;
+	let flagged_word = apply_word_filter(&cafe, session.user_id, &payload.content).await?;
+
+	let content = payload.content.clone();
+	let kind = CafeOrderKind::Message(match payload.parent_id {
+		Some(parent_id) => CafeOrderMessage::Reply {
+			content,
+			parent_id: get_reply_parent(cafe.id, parent_id).await?.id
+		},
+		None => CafeOrderMessage::Basic { content }
+	});
+
+	let order_id = place_cafe_order(&request, &session, cafe.id, kind, flagged_word)
+		.await?
+		.ok_or(ErrorModelKind::InternalError.model())?;
+	Ok(HttpResponse::Ok().json(get_cafe_order(cafe.id, order_id, Some(session.user_id)).await?))
+}
+
+#[post("attachments")]
+async fn create_cafe_attachment(request: HttpRequest, path: web::Path<u64>, body: Bytes) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	let Some((content_type, extension)) = image_type(&body) else {
+		return Err(ErrorModelKind::InvalidParams.model());
+	};
+
+	let cafe = get_cafe(*path, Some(session.user_id)).await?;
+	verify_can_place_orders(&cafe, session.user_id, false).await?;
+
+	let key = format!("cafes/{}/{}.{extension}", cafe.id, Uuid::new_v4().simple());
+	let size = body.len() as u64;
+	let url = STORAGE
+		.put(&key, content_type, body)
+		.await?;
+	let kind = CafeOrderKind::Attachment(CafeOrderAttachment {
+		key: key.clone(),
+		url,
+		content_type: content_type.to_string(),
+		size
+	});
+
+	// nothing refers to the upload if the order didn't make it, so it shouldn't stick around either
+	let order_id = match place_cafe_order(&request, &session, cafe.id, kind, None).await {
+		Ok(order_id) => order_id.ok_or(ErrorModelKind::InternalError.model())?,
+		Err(error) => {
+			if let Err(error) = STORAGE.delete(&key).await {
+				warn!("failed to remove orphaned attachment {key}: {error}");
+			}
+			return Err(error);
+		}
+	};
+	Ok(HttpResponse::Ok().json(get_cafe_order(cafe.id, order_id, Some(session.user_id)).await?))
+}
+
+#[derive(Deserialize, Validate)]
+struct CreateCafeEmbed {
+	#[validate(length(max = 2048), url)]
+	url: String
+}
+
+#[post("embeds")]
+async fn create_cafe_embed(request: HttpRequest, path: web::Path<u64>, payload: web::Json<CreateCafeEmbed>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	payload.validate()?;
+
+	let cafe = get_cafe(*path, Some(session.user_id)).await?;
+	verify_can_place_orders(&cafe, session.user_id, false).await?;
+
+	// whatever the page says about itself shows up in the cafe too, so it goes through the filter with the url
+	let embed = opengraph::embed(&payload.url).await?;
+	let content = [Some(&embed.url), embed.title.as_ref(), embed.description.as_ref(), embed.site_name.as_ref()]
+		.into_iter()
+		.flatten()
+		.map(String::as_str)
+		.collect::<Vec<_>>()
+		.join("\n");
+	let flagged_word = apply_word_filter(&cafe, session.user_id, &content).await?;
+
+	let kind = CafeOrderKind::Embed(embed);
+
+	let order_id = place_cafe_order(&request, &session, cafe.id, kind, flagged_word)
+		.await?
+		.ok_or(ErrorModelKind::InternalError.model())?;
+	Ok(HttpResponse::Ok().json(get_cafe_order(cafe.id, order_id, Some(session.user_id)).await?))
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+struct UpdateCafeOrder {
+	#[validate(length(max = 2000), custom(function = "validate_content"))]
+	content: String
+}
+
+#[patch("orders/{order_id}")]
+async fn update_cafe_order(request: HttpRequest, path: web::Path<(u64, u64)>, payload: web::Json<UpdateCafeOrder>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	payload.validate()?;
+
+	// nobody can put words in someone else's mouth, not even moderators
+	let (cafe_id, order_id) = *path;
+	let cafe = get_cafe(cafe_id, Some(session.user_id)).await?;
+	let order = get_cafe_order(cafe_id, order_id, None).await?;
+	if order.author_id != Some(session.user_id) {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+	let CafeOrderKind::Message(message) = &order.kind else {
+		return Err(ErrorModelKind::InvalidParams.model());
+	};
+	if cafe.settings.locked && !can_moderate(&cafe, session.user_id).await? {
+		return Err(ErrorModelKind::CafeLocked.model());
+	}
+	let flagged_word = apply_word_filter(&cafe, session.user_id, &payload.content).await?;
+	let content = payload.content.clone();
+	let kind = CafeOrderKind::Message(match message {
+		CafeOrderMessage::Basic { .. } => CafeOrderMessage::Basic { content },
+		CafeOrderMessage::Reply { parent_id, .. } => CafeOrderMessage::Reply { content, parent_id: *parent_id }
+	});
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		UPDATE cafe_orders
+		SET payload = $2, edited_at = now()
+		WHERE id = $1
+		",
+		order_id as i64,
+		kind.payload()?
+	)
+		.execute(&mut *transaction)
+		.await?;
+	if let Some(word) = flagged_word {
+		flag_cafe_order(&mut transaction, cafe_id, order_id, &word).await?;
+	}
+
+	audit::entry(&request, &session, AuditLogAction::CafeOrderUpdated)
+		.target(ModelKind::CafeOrder(cafe_id, order_id))
+		.changes(&json!({ "content": message.content() }), &*payload)?
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Updated
+		.build(ModelKind::CafeOrder(cafe_id, order_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().json(get_cafe_order(cafe_id, order_id, Some(session.user_id)).await?))
+}
+
+#[delete("orders/{order_id}")]
+async fn delete_cafe_order(request: HttpRequest, path: web::Path<(u64, u64)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (cafe_id, order_id) = *path;
+	let cafe = get_cafe(cafe_id, Some(session.user_id)).await?;
+	let order = get_cafe_order(cafe_id, order_id, None).await?;
+	if order.author_id != Some(session.user_id) && !can_moderate(&cafe, session.user_id).await? {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let attachment_keys = delete_order_and_replies(&request, &session, &mut transaction, &order).await?;
+	transaction
+		.commit()
+		.await?;
+	remove_attachments(attachment_keys).await;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+/// Deletes an order along with its replies and reactions, reactions on replies included, and closes any reports about them.
+/// Returns the storage keys of attachments that went with it, for [`remove_attachments`] once the transaction is committed.
+async fn delete_order_and_replies(request: &HttpRequest, session: &SessionModel, connection: &mut PgConnection, order: &CafeOrderModel) -> Result<Vec<String>> {
+	let order_ids: Vec<i64> = sqlx::query!(
+		"
+		SELECT id
+		FROM cafe_orders
+		WHERE id = $1 OR (payload->>'parent_id')::int8 = $1 OR (payload->>'parent_id')::int8 IN (
+			SELECT id FROM cafe_orders WHERE (payload->>'parent_id')::int8 = $1
+		)
+		",
+		order.id as i64
+	)
+		.fetch_all(&mut *connection)
+		.await?
+		.into_iter()
+		.map(|x| x.id)
+		.collect();
+
+	// reports lose track of the order once it's gone, so they have to be closed first
+	sqlx::query!(
+		"
+		UPDATE cafe_order_reports
+		SET resolver_id = $2, resolution = $3, resolved_at = now()
+		WHERE order_id = ANY($1) AND resolved_at IS NULL
+		",
+		&order_ids,
+		session.user_id.value,
+		CafeOrderReportResolution::OrderDeleted.name()
+	)
+		.execute(&mut *connection)
+		.await?;
+
+	let deleted = sqlx::query!(
+		"
+		DELETE FROM cafe_orders
+		WHERE id = ANY($1)
+		RETURNING kind, payload
+		",
+		&order_ids
+	)
+		.fetch_all(&mut *connection)
+		.await?;
+
+	// whoever placed it is kept around, so it's clear when a moderator took something down
+	let mut entry = audit::entry(request, session, AuditLogAction::CafeOrderDeleted)
+		.target(ModelKind::CafeOrder(order.cafe_id, order.id));
+	if let Some(author_id) = order.author_id {
+		entry = entry.target_user(author_id);
+	}
+	entry
+		.insert(&mut *connection)
+		.await?;
+	ModelEventKind::Deleted
+		.build(ModelKind::CafeOrder(order.cafe_id, order.id))
+		.actionee(session.user_id)
+		.enqueue(&mut *connection)
+		.await?;
+
+	Ok(deleted
+		.into_iter()
+		.filter_map(|x| match CafeOrderKind::decode(x.kind, x.payload) {
+			CafeOrderKind::Attachment(attachment) => Some(attachment.key),
+			_ => None
+		})
+		.collect()
+	)
+}
+
+async fn remove_attachments(keys: Vec<String>) {
+	for key in keys {
+		if let Err(error) = STORAGE.delete(&key).await {
+			warn!("failed to remove attachment {key}: {error}");
+		}
+	}
+}
+
+#[put("orders/{order_id}/reactions/{emoji}")]
+async fn add_cafe_order_reaction(request: HttpRequest, path: web::Path<(u64, u64, String)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (cafe_id, order_id, emoji) = path.into_inner();
+	validate_emoji(&emoji)?;
+	verify_can_place_orders(&get_cafe(cafe_id, Some(session.user_id)).await?, session.user_id, true).await?;
+
+	let order = get_cafe_order(cafe_id, order_id, None).await?;
+	if matches!(order.kind, CafeOrderKind::Reaction { .. } | CafeOrderKind::Unknown) {
+		return Err(ErrorModelKind::InvalidParams.model());
+	}
+
+	// reacting twice with the same emoji is a no-op
+	place_cafe_order(&request, &session, cafe_id, CafeOrderKind::Reaction {
+		parent_id: order.id,
+		emoji
+	}, None).await?;
+
+	Ok(HttpResponse::Ok().json(get_cafe_order(cafe_id, order_id, Some(session.user_id)).await?))
+}
+
+#[delete("orders/{order_id}/reactions/{emoji}")]
+async fn remove_cafe_order_reaction(request: HttpRequest, path: web::Path<(u64, u64, String)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (cafe_id, order_id, emoji) = path.into_inner();
+	get_cafe(cafe_id, Some(session.user_id)).await?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let Some(reaction_id) = sqlx::query!(
+		"
+		DELETE FROM cafe_orders
+		WHERE cafe_id = $1 AND kind = 'reaction' AND (payload->>'parent_id')::int8 = $2 AND author_id = $3 AND payload->>'emoji' = $4
+		RETURNING id
+		",
+		cafe_id as i64,
+		order_id as i64,
+		session.user_id.value,
+		emoji
+	)
+		.fetch_optional(&mut *transaction)
+		.await?
+		.map(|x| x.id as u64)
+	else {
+		return Err(ErrorModelKind::not_found(ResourceKind::CafeOrder, Some(order_id)));
+	};
+
+	audit::entry(&request, &session, AuditLogAction::CafeOrderDeleted)
+		.target(ModelKind::CafeOrder(cafe_id, reaction_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Deleted
+		.build(ModelKind::CafeOrder(cafe_id, reaction_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().json(get_cafe_order(cafe_id, order_id, Some(session.user_id)).await?))
+}
+
+#[derive(Deserialize, Validate)]
+struct ReportCafeOrder {
+	#[validate(length(max = 500), custom(function = "validate_content"))]
+	reason: String
+}
+
+/// Asks the cafe's moderators to look at an order, reporting it again while the first is still open only updates the reason.
+#[post("orders/{order_id}/reports")]
+async fn report_cafe_order(request: HttpRequest, path: web::Path<(u64, u64)>, payload: web::Json<ReportCafeOrder>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	payload.validate()?;
+
+	let (cafe_id, order_id) = *path;
+	get_cafe(cafe_id, Some(session.user_id)).await?;
+	let order = get_cafe_order(cafe_id, order_id, None).await?;
+	if order.author_id == Some(session.user_id) {
+		return Err(ErrorModelKind::InvalidParams.model());
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let report_id = sqlx::query!(
+		"
+		INSERT INTO cafe_order_reports (cafe_id, order_id, reporter_id, reason)
+		VALUES ($1, $2, $3, $4)
+		ON CONFLICT (order_id, reporter_id) WHERE resolved_at IS NULL DO UPDATE
+		SET reason = excluded.reason
+		RETURNING id
+		",
+		cafe_id as i64,
+		order_id as i64,
+		session.user_id.value,
+		payload.reason
+	)
+		.fetch_one(&mut *transaction)
+		.await?
+		.id as u64;
+
+	// the author isn't told who reported them, so they're left out of the entry
+	audit::entry(&request, &session, AuditLogAction::CafeOrderReported)
+		.target(ModelKind::CafeOrder(cafe_id, order_id))
+		.insert(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().json(get_cafe_report(cafe_id, report_id).await?))
+}
+
+async fn get_cafe_report(cafe_id: u64, report_id: u64) -> Result<CafeOrderReportModel> {
+	CafeOrderReportModel::get(cafe_id, report_id)
+		.await?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::CafeOrderReport, Some(report_id)))
+}
+
+async fn get_moderated_cafe(cafe_id: u64, user_id: Id<UserMarker>) -> Result<CafeModel> {
+	let cafe = get_cafe(cafe_id, Some(user_id)).await?;
+	if !can_moderate(&cafe, user_id).await? {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+
+	Ok(cafe)
+}
+
+#[derive(Deserialize)]
+struct CafeReportsQuery {
+	#[serde(default)]
+	resolved: bool
+}
+
+/// The moderation queue, open reports by default and dealt with ones with `?resolved=true`.
+#[get("reports")]
+async fn get_cafe_reports(request: HttpRequest, path: web::Path<u64>, query: web::Query<CafeReportsQuery>, page: PageQuery<u64>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let cafe = get_moderated_cafe(*path, session.user_id).await?;
+	let reports = CafeOrderReportModel::get_cafe_page(cafe.id, query.resolved, page.direction_or(SortDirection::Desc), page.cursor, page.fetch_limit()).await?;
+	let total = CafeOrderReportModel::count_cafe(cafe.id, query.resolved).await?;
+	Ok(HttpResponse::Ok().json(Page::new(reports, &page, total, |x| x.id)))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ResolveCafeReportAction {
+	Dismiss,
+	DeleteOrder
+}
+
+#[derive(Deserialize)]
+struct ResolveCafeReport {
+	action: ResolveCafeReportAction
+}
+
+/// Deals with a report, along with every other open report about the same order.
+#[post("reports/{report_id}/resolve")]
+async fn resolve_cafe_report(request: HttpRequest, path: web::Path<(u64, u64)>, payload: web::Json<ResolveCafeReport>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (cafe_id, report_id) = *path;
+	let cafe = get_moderated_cafe(cafe_id, session.user_id).await?;
+	let report = get_cafe_report(cafe.id, report_id).await?;
+	if report.resolved_at.is_some() {
+		return Err(ErrorModelKind::InvalidParams.model());
+	}
+
+	// deleting an order resolves its reports too, so an open report always has one
+	let order = report
+		.order
+		.ok_or(ErrorModelKind::InternalError.model())?;
+	let resolution = match payload.action {
+		ResolveCafeReportAction::Dismiss => CafeOrderReportResolution::Dismissed,
+		ResolveCafeReportAction::DeleteOrder => CafeOrderReportResolution::OrderDeleted
+	};
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let attachment_keys = match resolution {
+		CafeOrderReportResolution::Dismissed => {
+			sqlx::query!(
+				"
+				UPDATE cafe_order_reports
+				SET resolver_id = $2, resolution = $3, resolved_at = now()
+				WHERE order_id = $1 AND resolved_at IS NULL
+				",
+				order.id as i64,
+				session.user_id.value,
+				resolution.name()
+			)
+				.execute(&mut *transaction)
+				.await?;
+			vec![]
+		},
+		CafeOrderReportResolution::OrderDeleted => delete_order_and_replies(&request, &session, &mut transaction, &order).await?
+	};
+
+	audit::entry(&request, &session, AuditLogAction::CafeOrderReportResolved)
+		.target(ModelKind::CafeOrder(cafe.id, order.id))
+		.changes(&json!({ "resolution": null }), &json!({ "resolution": resolution }))?
+		.insert(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+	remove_attachments(attachment_keys).await;
+
+	Ok(HttpResponse::Ok().json(get_cafe_report(cafe.id, report_id).await?))
+}
+
+#[get("audit_log")]
+async fn get_cafe_audit_log(request: HttpRequest, path: web::Path<u64>, page: PageQuery<u64>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let cafe = get_moderated_cafe(*path, session.user_id).await?;
+	let scope = AuditLogScope::Cafe(cafe.id);
+	let entries: Vec<_> = AuditLogEntryModel::get_page(scope, page.direction_or(SortDirection::Desc), page.cursor, page.fetch_limit())
+		.await?
+		.into_iter()
+		.map(AuditLogEntryModel::redacted)
+		.collect();
+	let total = AuditLogEntryModel::count(scope).await?;
+	Ok(HttpResponse::Ok().json(Page::new(entries, &page, total, |x| x.id)))
 }
\ No newline at end of file
@@ -7,11 +7,14 @@ use actix_web::{
 };
 use chrono::{ TimeDelta, Utc };
 use jsonwebtoken::{ encode, Header };
-use polyumi_cache::CACHE;
+use once_cell::sync::Lazy;
 use polyumi_models::{
 	hakumi::user::connection::{ ConnectionKind, ConnectionModel },
 	mellow::model_event::{ ModelEventKind, ModelKind },
-	polyumi::error::ErrorModelKind
+	polyumi::{
+		audit_log::AuditLogAction,
+		error::ErrorModelKind
+	}
 };
 use polyumi_util::{
 	id::{
@@ -32,6 +35,7 @@ use twilight_model::id::{
 };
 
 use crate::{
+	audit,
 	auth::{ AUTH_JWT_DURATION, ENCODING_KEY, get_session_from_request },
 	Result
 };
@@ -90,12 +94,12 @@ struct CallbackResponse {
 	website_url: Option<String>
 }
 
-const API_URL: &str = env!("API_URL");
-const DISCORD_APP_ID: &str = env!("DISCORD_APP_ID");
-const DISCORD_APP_SECRET: &str = env!("DISCORD_APP_SECRET");
-const PATREON_APP_ID: &str = env!("PATREON_APP_ID");
-const PATREON_APP_SECRET: &str = env!("PATREON_APP_SECRET");
-const WEBSITE_URL: &str = env!("WEBSITE_URL");
+pub static API_URL: Lazy<String> = Lazy::new(|| std::env::var("API_URL").expect("API_URL is not defined"));
+pub static DISCORD_APP_ID: Lazy<String> = Lazy::new(|| std::env::var("DISCORD_APP_ID").expect("DISCORD_APP_ID is not defined"));
+pub static DISCORD_APP_SECRET: Lazy<String> = Lazy::new(|| std::env::var("DISCORD_APP_SECRET").expect("DISCORD_APP_SECRET is not defined"));
+pub static PATREON_APP_ID: Lazy<String> = Lazy::new(|| std::env::var("PATREON_APP_ID").expect("PATREON_APP_ID is not defined"));
+pub static PATREON_APP_SECRET: Lazy<String> = Lazy::new(|| std::env::var("PATREON_APP_SECRET").expect("PATREON_APP_SECRET is not defined"));
+pub static WEBSITE_URL: Lazy<String> = Lazy::new(|| std::env::var("WEBSITE_URL").expect("WEBSITE_URL is not defined"));
 
 #[derive(Serialize)]
 struct JwtClaims {
@@ -138,10 +142,10 @@ async fn connection_callback(request: HttpRequest, path: web::Path<ConnectionKin
 
 			let params = HashMap::from([
 				("code", code),
-				("client_id", DISCORD_APP_ID.into()),
-				("client_secret", DISCORD_APP_SECRET.into()),
+				("client_id", DISCORD_APP_ID.clone()),
+				("client_secret", DISCORD_APP_SECRET.clone()),
 				("grant_type", "authorization_code".into()),
-				("redirect_uri", format!("{API_URL}/v1/connection_callback/{}", connection_kind.discriminant()))
+				("redirect_uri", format!("{}/v1/connection_callback/{}", *API_URL, connection_kind.discriminant()))
 			]);
 			
 			let token: BasicToken = post_json("https://discord.com/api/v10/oauth2/token")
@@ -175,10 +179,10 @@ async fn connection_callback(request: HttpRequest, path: web::Path<ConnectionKin
 
 			let params = HashMap::from([
 				("code", code),
-				("client_id", PATREON_APP_ID.into()),
-				("client_secret", PATREON_APP_SECRET.into()),
+				("client_id", PATREON_APP_ID.clone()),
+				("client_secret", PATREON_APP_SECRET.clone()),
 				("grant_type", "authorization_code".into()),
-				("redirect_uri", format!("{API_URL}/v1/connection_callback/{}", connection_kind.discriminant()))
+				("redirect_uri", format!("{}/v1/connection_callback/{}", *API_URL, connection_kind.discriminant()))
 			]);
 			
 			let token: BasicToken = post_json("https://patreon.com/api/oauth2/token")
@@ -207,7 +211,10 @@ async fn connection_callback(request: HttpRequest, path: web::Path<ConnectionKin
 		}
 	};
 
-	let pinned = Pin::static_ref(&PG_POOL).await;
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
 	let mut http_response = HttpResponse::Found();
 	let user_id = match user_id {
 		Some(x) => x,
@@ -222,7 +229,7 @@ async fn connection_callback(request: HttpRequest, path: web::Path<ConnectionKin
 				response.display_name,
 				response.name.as_ref().unwrap_or(&response.sub)
 			)
-				.fetch_one(&*pinned)
+				.fetch_one(&mut *transaction)
 				.await?
 				.id
 				.into();
@@ -257,7 +264,7 @@ async fn connection_callback(request: HttpRequest, path: web::Path<ConnectionKin
 		response.name,
 		response.website_url
 	)
-		.fetch_one(&*pinned)
+		.fetch_one(&mut *transaction)
 		.await?;
 
 	if let Some((token, scopes)) = response.oauth_authorisation {
@@ -274,43 +281,37 @@ async fn connection_callback(request: HttpRequest, path: web::Path<ConnectionKin
 			&scopes,
 			user_id.value
 		)
-			.execute(&*pinned)
+			.execute(&mut *transaction)
 			.await?;
 	}
 
 	let connection_id = Id::new(record.id);
-	let new_model = CACHE
-		.hakumi
-		.connections
-		.entry(connection_id)
-		.insert(ConnectionModel {
-			id: connection_id,
-			sub: response.sub,
-			kind: connection_kind.clone(),
-			user_id,
-
-			username: response.name,
-			display_name: response.display_name,
-
-			avatar_url: response.avatar_url,
-			website_url: response.website_url,
-
-			is_public: false,
-
-			oauth_authorisations: Vec::new()
-		});
-	CACHE
-		.hakumi
-		.user_connections
-		.entry(user_id)
-		.or_default()
-		.insert(connection_id);
-
-	tokio::spawn(
-		ModelEventKind::Created
-			.build(ModelKind::UserConnection(user_id, connection_id))
-			.send()
-	);
+	let new_model = ConnectionModel {
+		id: connection_id,
+		sub: response.sub,
+		kind: connection_kind.clone(),
+		user_id,
+
+		username: response.name,
+		display_name: response.display_name,
+
+		avatar_url: response.avatar_url,
+		website_url: response.website_url,
+
+		is_public: false,
+
+		oauth_authorisations: Vec::new()
+	};
+
+	audit::sessionless_entry(&request, user_id, AuditLogAction::UserConnectionCreated)
+		.target(ModelKind::UserConnection(user_id, connection_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Created
+		.build(ModelKind::UserConnection(user_id, connection_id))
+		.actionee(user_id)
+		.enqueue(&mut transaction)
+		.await?;
 
 	if let Some(server_id) = mellow_server_id {
 		sqlx::query!(
@@ -324,14 +325,18 @@ async fn connection_callback(request: HttpRequest, path: web::Path<ConnectionKin
 			serde_json::json!([ { "id": connection_id } ]),
 			user_id.value
 		)
-			.execute(&*pinned)
+			.execute(&mut *transaction)
 			.await?;
 
-		tokio::spawn(
-			ModelEventKind::Updated
-				.build(ModelKind::UserSettings(server_id, user_id))
-				.send()
-		);
+		ModelEventKind::Updated
+			.build(ModelKind::UserSettings(server_id, user_id))
+			.actionee(user_id)
+			.enqueue(&mut transaction)
+			.await?;
+
+		transaction
+			.commit()
+			.await?;
 
 		return crate::templates::connection_callback::mellow_done(connection_kind, server_id, user_id)
 			.await;
@@ -357,28 +362,33 @@ async fn connection_callback(request: HttpRequest, path: web::Path<ConnectionKin
 				serde_json::json!([ { "id": connection_id } ]),
 				user_id.value
 			)
-				.execute(&*pinned)
+				.execute(&mut *transaction)
 				.await?;
 
-			tokio::spawn(
-				ModelEventKind::Updated
-					.build(ModelKind::UserSettings(server_id, user_id))
-					.send()
-			);
-			format!("{WEBSITE_URL}/mellow/server/{}/user_settings?as_new_member", server_id)
+			ModelEventKind::Updated
+				.build(ModelKind::UserSettings(server_id, user_id))
+				.actionee(user_id)
+				.enqueue(&mut transaction)
+				.await?;
+			format!("{}/mellow/server/{}/user_settings?as_new_member", *WEBSITE_URL, server_id)
 		} else {
-			format!("{WEBSITE_URL}/mellow/user_settings_popup#{}", urlencoding::encode(&serde_json::to_string(&*new_model)?))
+			format!("{}/mellow/user_settings_popup#{}", *WEBSITE_URL, urlencoding::encode(&serde_json::to_string(&new_model)?))
 		}
 	} else if let Some(state) = &query.state && state.starts_with("mellow_new.") {
-		format!("{WEBSITE_URL}/mellow/server/{}/user_settings?as_new_member", &state[11..])
+		format!("{}/mellow/server/{}/user_settings?as_new_member", *WEBSITE_URL, &state[11..])
 	} else {
 		match query.redirect_uri.clone() {
 			Some(redirect_uri) => if redirect_uri.starts_with('/') {
-				format!("{WEBSITE_URL}{redirect_uri}")
+				format!("{}{redirect_uri}", *WEBSITE_URL)
 			} else { redirect_uri },
-			None => format!("{WEBSITE_URL}/settings/account/connections")
+			None => format!("{}/settings/account/connections", *WEBSITE_URL)
 		}
 	};
+
+	transaction
+		.commit()
+		.await?;
+
 	Ok(http_response
 		.append_header((LOCATION, redirect_uri))
 		.finish()
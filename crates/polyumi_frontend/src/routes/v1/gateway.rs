@@ -0,0 +1,181 @@
+use actix_web::{
+	get,
+	http::header::{ CACHE_CONTROL, UPGRADE },
+	web::{ self, Bytes },
+	HttpRequest, HttpResponse
+};
+use actix_ws::Message;
+use polyumi_models::{
+	mellow::model_event::stream::Topic,
+	polyumi::error::ErrorModelKind
+};
+use serde::Deserialize;
+use std::{
+	convert::Infallible,
+	time::Instant
+};
+use tokio::sync::mpsc;
+
+use crate::{
+	auth::get_session_from_request,
+	gateway::{ GatewayCommand, GatewayMessage, GatewaySession, HEARTBEAT_INTERVAL },
+	Result
+};
+
+pub fn config(config: &mut web::ServiceConfig) {
+	config.service(gateway);
+}
+
+#[derive(Deserialize)]
+struct GatewayQuery {
+	#[serde(default)]
+	topics: String,
+	since: Option<i64>
+}
+
+/// Upgrades to a WebSocket when asked to, and otherwise streams server-sent events.
+///
+/// `topics` is a comma-separated list of topics to start out subscribed to, and `since` resumes from a sequence,
+/// EventSource's `Last-Event-ID` header does the same thing.
+#[get("gateway")]
+async fn gateway(request: HttpRequest, query: web::Query<GatewayQuery>, body: web::Payload) -> Result<HttpResponse> {
+	let user_id = get_session_from_request(&request)
+		.await?
+		.required()?
+		.user_id;
+
+	let topics = query.topics
+		.split(',')
+		.filter(|x| !x.is_empty())
+		.map(|x| x.parse::<Topic>())
+		.collect::<core::result::Result<Vec<_>, _>>()
+		.map_err(|_| ErrorModelKind::InvalidQuery.model())?;
+	let since = match request.headers().get("last-event-id") {
+		Some(header) => Some(header
+			.to_str()
+			.ok()
+			.and_then(|x| x.parse().ok())
+			.ok_or_else(|| ErrorModelKind::InvalidQuery.model())?
+		),
+		None => query.since
+	};
+
+	let (session, messages) = GatewaySession::new(user_id, topics, since).await?;
+	if request
+		.headers()
+		.get(UPGRADE)
+		.is_some_and(|x| x.as_bytes().eq_ignore_ascii_case(b"websocket"))
+	{
+		let (response, websocket, stream) = actix_ws::handle(&request, body)
+			.map_err(|_| ErrorModelKind::InvalidParams.model())?;
+		actix_web::rt::spawn(run_websocket(session, messages, websocket, stream));
+
+		return Ok(response);
+	}
+
+	let (sender, receiver) = mpsc::channel(32);
+	tokio::spawn(run_event_stream(session, messages, sender));
+
+	Ok(HttpResponse::Ok()
+		.content_type("text/event-stream")
+		.insert_header((CACHE_CONTROL, "no-cache"))
+		.streaming(futures::stream::unfold(receiver, |mut receiver| async move {
+			receiver
+				.recv()
+				.await
+				.map(|x| (Ok::<_, Infallible>(x), receiver))
+		}))
+	)
+}
+
+async fn run_websocket(mut session: GatewaySession, messages: Vec<GatewayMessage>, mut websocket: actix_ws::Session, mut stream: actix_ws::MessageStream) {
+	for message in &messages {
+		if send_websocket(&mut websocket, message).await.is_err() {
+			return;
+		}
+	}
+
+	let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+	let mut last_received_at = Instant::now();
+	loop {
+		let message = tokio::select! {
+			_ = heartbeat.tick() => {
+				// clients that haven't said anything, not even a pong, in two heartbeats are gone
+				if last_received_at.elapsed() > HEARTBEAT_INTERVAL * 2 || websocket.ping(b"").await.is_err() {
+					break;
+				}
+				continue;
+			},
+			message = session.next_message() => match message {
+				Some(x) => x,
+				None => break
+			},
+			message = stream.recv() => {
+				last_received_at = Instant::now();
+				match message {
+					Some(Ok(Message::Text(text))) => match serde_json::from_str::<GatewayCommand>(&text) {
+						Ok(command) => session.handle(command).await,
+						Err(_) => GatewayMessage::Error {
+							error: ErrorModelKind::InvalidParams
+						}
+					},
+					Some(Ok(Message::Ping(bytes))) => {
+						if websocket.pong(&bytes).await.is_err() {
+							break;
+						}
+						continue;
+					},
+					Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+					Some(Ok(_)) => continue
+				}
+			}
+		};
+		if send_websocket(&mut websocket, &message).await.is_err() {
+			break;
+		}
+	}
+
+	websocket
+		.close(None)
+		.await
+		.ok();
+}
+
+async fn send_websocket(websocket: &mut actix_ws::Session, message: &GatewayMessage) -> core::result::Result<(), actix_ws::Closed> {
+	websocket
+		.text(serde_json::to_string(message).unwrap())
+		.await
+}
+
+async fn run_event_stream(mut session: GatewaySession, messages: Vec<GatewayMessage>, sender: mpsc::Sender<Bytes>) {
+	for message in &messages {
+		if sender.send(event_stream_frame(message)).await.is_err() {
+			return;
+		}
+	}
+
+	// the first tick is immediate, and ready was just sent
+	let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+	heartbeat.tick().await;
+	loop {
+		let message = tokio::select! {
+			_ = heartbeat.tick() => GatewayMessage::Heartbeat,
+			message = session.next_message() => match message {
+				Some(x) => x,
+				None => break
+			}
+		};
+		// fails once the client is gone and the response has been dropped
+		if sender.send(event_stream_frame(&message)).await.is_err() {
+			break;
+		}
+	}
+}
+
+fn event_stream_frame(message: &GatewayMessage) -> Bytes {
+	let data = serde_json::to_string(message).unwrap();
+	Bytes::from(match message {
+		GatewayMessage::Event { event, .. } => format!("id: {}\ndata: {data}\n\n", event.sequence),
+		_ => format!("data: {data}\n\n")
+	})
+}
\ No newline at end of file
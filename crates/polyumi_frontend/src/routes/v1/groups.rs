@@ -1,39 +1,442 @@
 use std::pin::Pin;
-use sqlx::QueryBuilder;
+use sqlx::{ PgConnection, QueryBuilder };
 use serde::{ Serialize, Deserialize };
-use chrono::{ Utc, DateTime };
-use actix_web::{ get, web, post, HttpRequest, HttpResponse };
-use polyumi_util::{ id::{ marker::{ GroupMarker, UserMarker }, Id }, PG_POOL };
+use chrono::{ DateTime, TimeDelta, Utc };
+use uuid::Uuid;
+use actix_web::{ delete, get, patch, post, put, web, HttpRequest, HttpResponse };
+use polyumi_util::{ id::{ marker::{ GroupMarker, GroupRoleMarker, UserMarker }, Id }, PG_POOL };
 use polyumi_models::{
 	hakumi::{
-		group::{ GroupModel, GroupMembershipModel },
+		group::{
+			membership::{ GroupMembershipCursor, GroupMembershipFilter },
+			GroupModel, GroupMembershipModel, GroupPermissions, GroupRoleModel, GroupVisibility
+		},
 		UserModel
 	},
-	polyumi::error::{ ResourceKind, ErrorModelKind }
+	mellow::model_event::{ ModelEventKind, ModelKind },
+	pagination::SortDirection,
+	polyumi::{
+		audit_log::{ AuditLogAction, AuditLogEntry, AuditLogEntryModel, AuditLogScope },
+		error::{ ResourceKind, ErrorModelKind },
+		ErrorModel
+	}
+};
+use twilight_model::id::{
+	marker::GuildMarker as DiscordGuildMarker,
+	Id as DiscordId
 };
+use validator::{ Validate, ValidationError };
 
 use crate::{
+	audit,
 	auth::get_session_from_request,
+	pagination::{ Page, PageQuery },
 	Result
 };
+use super::webhooks;
+
+const INVITE_TTL_DAYS: i64 = 7;
+const INVITE_INBOX_KIND: &str = "group_invite";
 
 pub fn config(config: &mut web::ServiceConfig) {
 	config.service(web::scope("group")
+		.service(create_group)
 		.service(group_get)
+		.service(update_group)
+		.service(delete_group)
 		.service(web::scope("{group_id}")
 			.service(get_group_membership)
 			.service(get_group_memberships)
 			.service(invite_group_members)
+			.service(accept_group_invite)
+			.service(decline_group_invite)
+			.service(cancel_group_invite)
+			.service(request_to_join_group)
+			.service(approve_join_request)
+			.service(reject_join_request)
+			.service(leave_group)
+			.service(remove_group_member)
+			.service(transfer_group_ownership)
+			.service(add_group_owner)
+			.service(remove_group_owner)
+			.service(get_group_roles)
+			.service(create_group_role)
+			.service(update_group_role)
+			.service(delete_group_role)
+			.service(assign_group_role)
+			.service(unassign_group_role)
+			.service(get_group_audit_log)
+			.service(webhooks::get_group_webhooks)
+			.service(webhooks::create_group_webhook)
 		)
 	);
 }
 
+/// Finds a group the session is allowed to see, private groups are hidden from everyone but their members and invitees.
+async fn get_visible_group(request: &HttpRequest, group_ref: &str) -> Result<GroupModel> {
+	let group = GroupModel::get(group_ref)
+		.await?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::Group, Some(group_ref)))?;
+	let session = get_session_from_request(request).await?;
+	if !group.is_visible_to(session.as_ref().map(|x| x.user_id)).await? {
+		return Err(ErrorModelKind::not_found(ResourceKind::Group, Some(group_ref)));
+	}
+
+	Ok(group)
+}
+
 #[get("{group_ref}")]
-async fn group_get(path: web::Path<String>) -> Result<HttpResponse> {
-	match GroupModel::get(&path).await? {
-		Some(model) => Ok(HttpResponse::Ok().json(model)),
-		None => Err(ErrorModelKind::not_found(ResourceKind::Group, Some(path)))
+async fn group_get(request: HttpRequest, path: web::Path<String>) -> Result<HttpResponse> {
+	Ok(HttpResponse::Ok().json(get_visible_group(&request, &path).await?))
+}
+
+fn validate_name(name: &str) -> core::result::Result<(), ValidationError> {
+	if name.chars().all(|x| x.is_ascii_alphanumeric() || x == '_' || x == '-' || x == '.') {
+		Ok(())
+	} else {
+		Err(ValidationError::new("group_name"))
+	}
+}
+
+fn validate_image_url(url: &str) -> core::result::Result<(), ValidationError> {
+	if url.starts_with("https://") {
+		Ok(())
+	} else {
+		Err(ValidationError::new("url_scheme"))
+	}
+}
+
+fn map_unique_violation(error: sqlx::Error, kind: impl FnOnce() -> ErrorModelKind) -> ErrorModel {
+	if error
+		.as_database_error()
+		.is_some_and(|x| x.is_unique_violation())
+	{
+		kind().model()
+	} else {
+		error.into()
+	}
+}
+
+fn map_name_taken(error: sqlx::Error, name: &str) -> ErrorModel {
+	map_unique_violation(error, || ErrorModelKind::GroupNameTaken { name: name.to_string() })
+}
+
+/// Errors unless the user has joined the group and has every one of `permissions`, owners have them all.
+pub async fn require_group_permission(group_id: Id<GroupMarker>, user_id: Id<UserMarker>, permissions: GroupPermissions) -> Result<()> {
+	if GroupMembershipModel::get_permissions(group_id, user_id)
+		.await?
+		.is_some_and(|x| x.contains(permissions))
+	{
+		return Ok(());
+	}
+
+	Err(ErrorModelKind::MissingPermission.model())
+}
+
+async fn verify_group_owner(group_id: Id<GroupMarker>, user_id: Id<UserMarker>) -> Result<()> {
+	GroupModel::get(&group_id.to_string())
+		.await?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::Group, Some(group_id)))?;
+	if GroupMembershipModel::get_user(group_id, user_id)
+		.await?
+		.is_some_and(|x| x.is_owner && !x.is_pending)
+	{
+		return Ok(());
+	}
+
+	Err(ErrorModelKind::MissingPermission.model())
+}
+
+/// Lets every mellow server the group owns know that who manages it has changed.
+async fn enqueue_server_updates(connection: &mut PgConnection, group_id: Id<GroupMarker>, actionee_id: Id<UserMarker>) -> Result<()> {
+	let server_ids: Vec<DiscordId<DiscordGuildMarker>> = sqlx::query!(
+		"
+		SELECT id
+		FROM mellow_servers
+		WHERE owner_team_id = $1
+		",
+		group_id.value
+	)
+		.fetch_all(&mut *connection)
+		.await?
+		.into_iter()
+		.map(|x| DiscordId::new(x.id as u64))
+		.collect();
+	for server_id in server_ids {
+		ModelEventKind::Updated
+			.build(ModelKind::Server(server_id))
+			.actionee(actionee_id)
+			.enqueue(&mut *connection)
+			.await?;
+	}
+
+	Ok(())
+}
+
+#[derive(Deserialize, Validate)]
+struct CreateGroup {
+	#[validate(length(min = 3, max = 32), custom(function = "validate_name"))]
+	name: String,
+	#[validate(length(max = 32))]
+	display_name: Option<String>,
+	#[validate(length(max = 512))]
+	bio: Option<String>,
+
+	#[serde(default)]
+	visibility: GroupVisibility,
+
+	#[validate(url, length(max = 2048), custom(function = "validate_image_url"))]
+	avatar_url: Option<String>,
+	#[validate(url, length(max = 2048), custom(function = "validate_image_url"))]
+	banner_url: Option<String>,
+	#[serde(default)]
+	#[validate(range(max = 0xFFFFFF))]
+	profile_theme_accent_colour: u32,
+	#[serde(default)]
+	#[validate(range(max = 0xFFFFFF))]
+	profile_theme_primary_colour: u32
+}
+
+#[post("")]
+async fn create_group(request: HttpRequest, payload: web::Json<CreateGroup>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	payload.validate()?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let group_id: Id<GroupMarker> = sqlx::query!(
+		"
+		INSERT INTO teams (name, display_name, bio, avatar_url, banner_url, profile_theme_accent_colour, profile_theme_primary_colour, creator_id, visibility)
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+		RETURNING id
+		",
+		payload.name,
+		payload.display_name,
+		payload.bio,
+		payload.avatar_url,
+		payload.banner_url,
+		payload.profile_theme_accent_colour as i32,
+		payload.profile_theme_primary_colour as i32,
+		session.user_id.value,
+		payload.visibility.as_str()
+	)
+		.fetch_one(&mut *transaction)
+		.await
+		.map_err(|error| map_name_taken(error, &payload.name))?
+		.id
+		.into();
+
+	// whoever creates a group owns it
+	sqlx::query!(
+		"
+		INSERT INTO team_members (team_id, user_id, is_owner)
+		VALUES ($1, $2, true)
+		",
+		group_id.value,
+		session.user_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupCreated)
+		.target(ModelKind::Group(group_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Created
+		.build(ModelKind::Group(group_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+	ModelEventKind::Created
+		.build(ModelKind::GroupMembership(group_id, session.user_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	let group = GroupModel::get(&group_id.to_string())
+		.await?
+		.ok_or_else(|| ErrorModelKind::InternalError.model())?;
+	Ok(HttpResponse::Ok().json(group))
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+struct UpdateGroup {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[validate(length(min = 3, max = 32), custom(function = "validate_name"))]
+	name: Option<String>,
+
+	#[serde(default, with = "serde_with::rust::double_option", skip_serializing_if = "Option::is_none")]
+	#[validate(length(max = 32))]
+	display_name: Option<Option<String>>,
+
+	#[serde(default, with = "serde_with::rust::double_option", skip_serializing_if = "Option::is_none")]
+	#[validate(length(max = 512))]
+	bio: Option<Option<String>>,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	visibility: Option<GroupVisibility>,
+
+	#[serde(default, with = "serde_with::rust::double_option", skip_serializing_if = "Option::is_none")]
+	#[validate(url, length(max = 2048), custom(function = "validate_image_url"))]
+	avatar_url: Option<Option<String>>,
+
+	#[serde(default, with = "serde_with::rust::double_option", skip_serializing_if = "Option::is_none")]
+	#[validate(url, length(max = 2048), custom(function = "validate_image_url"))]
+	banner_url: Option<Option<String>>,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[validate(range(max = 0xFFFFFF))]
+	profile_theme_accent_colour: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[validate(range(max = 0xFFFFFF))]
+	profile_theme_primary_colour: Option<u32>
+}
+
+#[patch("{group_id}")]
+async fn update_group(request: HttpRequest, path: web::Path<Id<GroupMarker>>, payload: web::Json<UpdateGroup>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	payload.validate()?;
+
+	let group_id = *path;
+	verify_group_owner(group_id, session.user_id)
+		.await?;
+	let group = GroupModel::get(&group_id.to_string())
+		.await?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::Group, Some(group_id)))?;
+
+	// fields that can be cleared come with a flag saying whether they were given at all
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		UPDATE teams
+		SET
+			name = COALESCE($2, name),
+			display_name = CASE WHEN $3 THEN $4 ELSE display_name END,
+			bio = CASE WHEN $5 THEN $6 ELSE bio END,
+			avatar_url = CASE WHEN $7 THEN $8 ELSE avatar_url END,
+			banner_url = CASE WHEN $9 THEN $10 ELSE banner_url END,
+			profile_theme_accent_colour = COALESCE($11, profile_theme_accent_colour),
+			profile_theme_primary_colour = COALESCE($12, profile_theme_primary_colour),
+			visibility = COALESCE($13, visibility)
+		WHERE id = $1
+		",
+		group_id.value,
+		payload.name,
+		payload.display_name.is_some(),
+		payload.display_name.clone().flatten(),
+		payload.bio.is_some(),
+		payload.bio.clone().flatten(),
+		payload.avatar_url.is_some(),
+		payload.avatar_url.clone().flatten(),
+		payload.banner_url.is_some(),
+		payload.banner_url.clone().flatten(),
+		payload.profile_theme_accent_colour.map(|x| x as i32),
+		payload.profile_theme_primary_colour.map(|x| x as i32),
+		payload.visibility.map(|x| x.as_str())
+	)
+		.execute(&mut *transaction)
+		.await
+		.map_err(|error| map_name_taken(error, payload.name.as_deref().unwrap_or_default()))?;
+
+	// only what was given ends up in the payload, so that's all that gets compared
+	audit::entry(&request, &session, AuditLogAction::GroupUpdated)
+		.target(ModelKind::Group(group_id))
+		.changes(&group, &*payload)?
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Updated
+		.build(ModelKind::Group(group_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	let group = GroupModel::get(&group_id.to_string())
+		.await?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::Group, Some(group_id)))?;
+	Ok(HttpResponse::Ok().json(group))
+}
+
+#[delete("{group_id}")]
+async fn delete_group(request: HttpRequest, path: web::Path<Id<GroupMarker>>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let group_id = *path;
+	verify_group_owner(group_id, session.user_id)
+		.await?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+
+	// servers the group owned are left without an owner, so they need an event of their own
+	let server_ids: Vec<DiscordId<DiscordGuildMarker>> = sqlx::query!(
+		"
+		UPDATE mellow_servers
+		SET owner_team_id = NULL
+		WHERE owner_team_id = $1
+		RETURNING id
+		",
+		group_id.value
+	)
+		.fetch_all(&mut *transaction)
+		.await?
+		.into_iter()
+		.map(|x| DiscordId::new(x.id as u64))
+		.collect();
+
+	// memberships, webhooks and cafes go along with it
+	sqlx::query!(
+		"
+		DELETE FROM teams
+		WHERE id = $1
+		",
+		group_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupDeleted)
+		.target(ModelKind::Group(group_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Deleted
+		.build(ModelKind::Group(group_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+	for server_id in server_ids {
+		ModelEventKind::Updated
+			.build(ModelKind::Server(server_id))
+			.actionee(session.user_id)
+			.enqueue(&mut transaction)
+			.await?;
 	}
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
 }
 
 #[derive(Serialize)]
@@ -43,6 +446,8 @@ struct GroupMembership {
 	is_invited: bool,
 	is_owner: bool,
 	is_pending: bool,
+	invite_expires_at: Option<DateTime<Utc>>,
+	role_ids: Vec<Id<GroupRoleMarker>>,
 
 	group_id: Id<GroupMarker>,
 	user: UserModel
@@ -67,46 +472,55 @@ async fn get_group_membership(request: HttpRequest, path: web::Path<Id<GroupMark
 		is_invited: membership.is_invited,
 		is_owner: membership.is_owner,
 		is_pending: membership.is_pending,
+		invite_expires_at: membership.invite_expires_at,
+		role_ids: membership.role_ids,
 
 		group_id: membership.group_id,
 		user
 	}))
 }
 
+#[derive(Deserialize)]
+struct GroupMembershipsQuery {
+	#[serde(default)]
+	filter: GroupMembershipFilter
+}
+
 #[get("memberships")]
-async fn get_group_memberships(path: web::Path<Id<GroupMarker>>) -> Result<HttpResponse> {
-	// TODO: needs to be a paginated response
-	let memberships: Vec<_> = GroupMembershipModel::get_group(*path)
-		.await?
-		.into_iter()
-		.filter(|x| !x.is_pending)
-		.collect();
-	let user_ids: Vec<Id<UserMarker>> = memberships.iter().map(|x| x.user_id).collect();
-	let users = UserModel::get_many(&user_ids).await?;
+async fn get_group_memberships(request: HttpRequest, path: web::Path<Id<GroupMarker>>, query: web::Query<GroupMembershipsQuery>, page: PageQuery<GroupMembershipCursor>) -> Result<HttpResponse> {
+	get_visible_group(&request, &path.to_string()).await?;
+
+	// pending invites and join requests are only visible to whoever manages the group
+	if matches!(query.filter, GroupMembershipFilter::Pending | GroupMembershipFilter::Requested) {
+		let session = get_session_from_request(&request)
+			.await?
+			.required()?;
+		require_group_permission(*path, session.user_id, GroupPermissions::MANAGE_MEMBERS)
+			.await?;
+	}
+
+	let memberships = GroupMembershipModel::get_group_page(*path, query.filter, page.direction_or(SortDirection::Asc), page.cursor, page.fetch_limit()).await?;
+	let total = GroupMembershipModel::count_group(*path, query.filter).await?;
 	Ok(HttpResponse::Ok().json(
-		memberships
-			.into_iter()
-			.flat_map(|x|
-				users
-					.iter()
-					.find(|y| y.id == x.user_id)
-					.map(|user| GroupMembership {
-						created_at: x.created_at,
-
-						is_invited: x.is_invited,
-						is_owner: x.is_owner,
-						is_pending: x.is_pending,
-		
-						group_id: x.group_id,
-						user: user.clone()
-					})
-			)
-			.collect::<Vec<_>>()
+		Page::new(memberships, &page, total, |(x, _)| x.cursor())
+			.map(|(x, user)| GroupMembership {
+				created_at: x.created_at,
+
+				is_invited: x.is_invited,
+				is_owner: x.is_owner,
+				is_pending: x.is_pending,
+				invite_expires_at: x.invite_expires_at,
+				role_ids: x.role_ids,
+
+				group_id: x.group_id,
+				user
+			})
 	))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct InviteGroupMembers {
+	#[validate(length(min = 1, max = 50))]
 	user_ids: Vec<Id<UserMarker>>
 }
 
@@ -116,40 +530,1035 @@ async fn invite_group_members(request: HttpRequest, path: web::Path<Id<GroupMark
 		.await?
 		.required()?;
 	//session.verify_request(&request, &payload)?;
+	payload.validate()?;
 
 	let _group = GroupModel::get(&path.to_string())
 		.await?
 		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::Group, Some(&path)))?;
-	let _group_membership = GroupMembershipModel::get_user(*path, session.user_id)
-		.await?
-		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::User, None::<String>))?;
+	require_group_permission(*path, session.user_id, GroupPermissions::MANAGE_MEMBERS)
+		.await?;
+
+	let mut user_ids = payload.user_ids.clone();
+	user_ids.sort_by_key(|x| x.value);
+	user_ids.dedup();
+
+	let users = UserModel::get_many(&user_ids).await?;
+	if let Some(user_id) = user_ids.iter().find(|x| !users.iter().any(|user| &user.id == *x)) {
+		return Err(ErrorModelKind::not_found(ResourceKind::User, Some(user_id)));
+	}
 
-	let group_memberships = GroupMembershipModel::get_group(*path).await?;
-	for user_id in &payload.user_ids {
-		if let Some(membership) = group_memberships.iter().find(|x| &x.user_id == user_id) {
+	let raw_user_ids: Vec<Uuid> = user_ids
+		.iter()
+		.map(|x| x.value)
+		.collect();
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+
+	// checked under lock so they can't join or leave in the meantime,
+	// expired invites don't count, they're replaced by the new one
+	let existing = sqlx::query!(
+		"
+		SELECT user_id, is_pending, invite_expires_at
+		FROM team_members
+		WHERE team_id = $1 AND user_id = ANY($2)
+		FOR UPDATE
+		",
+		path.value,
+		&raw_user_ids
+	)
+		.fetch_all(&mut *transaction)
+		.await?;
+	for user_id in &user_ids {
+		if let Some(membership) = existing.iter().find(|x| x.user_id == user_id.value && !x.invite_expires_at.is_some_and(|x| x <= Utc::now())) {
 			return Err(if membership.is_pending {
-				ErrorModelKind::UserAlreadyInGroup { user_id: *user_id }
-			} else {
 				ErrorModelKind::UserAlreadyPendingInGroup { user_id: *user_id }
+			} else {
+				ErrorModelKind::UserAlreadyInGroup { user_id: *user_id }
 			}.model());
 		}
 	}
 
-	let pinned = Pin::static_ref(&PG_POOL).await;
-	let mut query = QueryBuilder::new("INSERT INTO team_members (inviter_id, is_invited, is_pending, team_id, user_id)");
-	query.push_values(payload.user_ids.iter(), |mut builder, user_id| {
+	// only the expired invites looked at above
+	let expired_user_ids: Vec<Uuid> = existing
+		.iter()
+		.map(|x| x.user_id)
+		.collect();
+	sqlx::query!(
+		"
+		DELETE FROM team_members
+		WHERE team_id = $1 AND user_id = ANY($2)
+		",
+		path.value,
+		&expired_user_ids
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	let expires_at = Utc::now() + TimeDelta::days(INVITE_TTL_DAYS);
+	let mut query = QueryBuilder::new("INSERT INTO team_members (inviter_id, is_invited, is_pending, invite_expires_at, team_id, user_id)");
+	query.push_values(user_ids.iter(), |mut builder, user_id| {
 		builder
 			.push_bind(session.user_id.value)
 			.push_bind(true)
 			.push_bind(true)
+			.push_bind(expires_at)
 			.push_bind(path.value)
 			.push_bind(user_id.value);
 	});
+	query.push(" ON CONFLICT DO NOTHING RETURNING user_id");
 
-	query
-		.build()
-		.execute(pinned.get_ref())
+	// anyone who didn't have a membership to lock could've been invited or asked to join since
+	let inserted: Vec<(Uuid,)> = query
+		.build_query_as()
+		.fetch_all(&mut *transaction)
+		.await?;
+	if let Some(user_id) = user_ids.iter().find(|x| !inserted.iter().any(|(id,)| *id == x.value)) {
+		return Err(ErrorModelKind::UserAlreadyPendingInGroup { user_id: *user_id }.model());
+	}
+
+	sqlx::query!(
+		"
+		INSERT INTO user_inbox_items (user_id, kind, related_user_ids, related_group_id)
+		SELECT UNNEST($1::uuid[]), $2, ARRAY[$3::uuid], $4
+		",
+		&raw_user_ids,
+		INVITE_INBOX_KIND,
+		session.user_id.value,
+		path.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	for user_id in &user_ids {
+		audit::entry(&request, &session, AuditLogAction::GroupMembersInvited)
+			.target(ModelKind::GroupMembership(*path, *user_id))
+			.insert(&mut transaction)
+			.await?;
+		ModelEventKind::Created
+			.build(ModelKind::GroupMembership(*path, *user_id))
+			.actionee(session.user_id)
+			.enqueue(&mut transaction)
+			.await?;
+	}
+
+	transaction
+		.commit()
 		.await?;
 
 	Ok(HttpResponse::Ok().into())
+}
+
+async fn get_pending_membership(group_id: Id<GroupMarker>, user_id: Id<UserMarker>) -> Result<GroupMembershipModel> {
+	GroupMembershipModel::get_user(group_id, user_id)
+		.await?
+		.filter(|x| x.is_pending && x.is_invited)
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::GroupMembership, None::<String>))
+}
+
+async fn get_join_request(group_id: Id<GroupMarker>, user_id: Id<UserMarker>) -> Result<GroupMembershipModel> {
+	GroupMembershipModel::get_user(group_id, user_id)
+		.await?
+		.filter(GroupMembershipModel::is_join_request)
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::GroupMembership, None::<String>))
+}
+
+/// Deletes an invite or join request, along with the inbox item an invite came with.
+async fn remove_invite(group_id: Id<GroupMarker>, user_id: Id<UserMarker>, entry: AuditLogEntry) -> Result<()> {
+	let actionee_id = entry.actor_id;
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		DELETE FROM team_members
+		WHERE team_id = $1 AND user_id = $2 AND is_pending
+		",
+		group_id.value,
+		user_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+	sqlx::query!(
+		"
+		DELETE FROM user_inbox_items
+		WHERE user_id = $1 AND kind = $2 AND related_group_id = $3
+		",
+		user_id.value,
+		INVITE_INBOX_KIND,
+		group_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	entry
+		.target(ModelKind::GroupMembership(group_id, user_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Deleted
+		.build(ModelKind::GroupMembership(group_id, user_id))
+		.actionee(actionee_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(())
+}
+
+#[post("membership/accept")]
+async fn accept_group_invite(request: HttpRequest, path: web::Path<Id<GroupMarker>>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let group_id = *path;
+	let membership = get_pending_membership(group_id, session.user_id).await?;
+	if membership.is_expired() {
+		return Err(ErrorModelKind::GroupInviteExpired.model());
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		UPDATE team_members
+		SET is_pending = false, invite_expires_at = NULL, joined_at = now()
+		WHERE team_id = $1 AND user_id = $2
+		",
+		group_id.value,
+		session.user_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+	sqlx::query!(
+		"
+		DELETE FROM user_inbox_items
+		WHERE user_id = $1 AND kind = $2 AND related_group_id = $3
+		",
+		session.user_id.value,
+		INVITE_INBOX_KIND,
+		group_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupInviteAccepted)
+		.target(ModelKind::GroupMembership(group_id, session.user_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Updated
+		.build(ModelKind::GroupMembership(group_id, session.user_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[post("membership/decline")]
+async fn decline_group_invite(request: HttpRequest, path: web::Path<Id<GroupMarker>>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	get_pending_membership(*path, session.user_id).await?;
+	remove_invite(*path, session.user_id, audit::entry(&request, &session, AuditLogAction::GroupInviteDeclined)).await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("invites/{user_id}")]
+async fn cancel_group_invite(request: HttpRequest, path: web::Path<(Id<GroupMarker>, Id<UserMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	// whoever sent the invite can take it back, and so can anyone managing members
+	let (group_id, user_id) = *path;
+	let membership = get_pending_membership(group_id, user_id).await?;
+	if membership.inviter_id != Some(session.user_id) {
+		require_group_permission(group_id, session.user_id, GroupPermissions::MANAGE_MEMBERS)
+			.await?;
+	}
+
+	remove_invite(group_id, user_id, audit::entry(&request, &session, AuditLogAction::GroupInviteCancelled)).await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[post("membership/request")]
+async fn request_to_join_group(request: HttpRequest, path: web::Path<Id<GroupMarker>>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	// only public groups take requests, anything else is invite only
+	let group_id = *path;
+	let group = get_visible_group(&request, &group_id.to_string()).await?;
+	if group.visibility != GroupVisibility::Public {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+	if let Some(membership) = GroupMembershipModel::get_user(group_id, session.user_id).await? && !membership.is_expired() {
+		return Err(if membership.is_pending {
+			ErrorModelKind::UserAlreadyPendingInGroup { user_id: session.user_id }
+		} else {
+			ErrorModelKind::UserAlreadyInGroup { user_id: session.user_id }
+		}.model());
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		DELETE FROM team_members
+		WHERE team_id = $1 AND user_id = $2 AND is_pending
+		",
+		group_id.value,
+		session.user_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+	sqlx::query!(
+		"
+		INSERT INTO team_members (team_id, user_id, is_pending)
+		VALUES ($1, $2, true)
+		",
+		group_id.value,
+		session.user_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupJoinRequested)
+		.target(ModelKind::GroupMembership(group_id, session.user_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Created
+		.build(ModelKind::GroupMembership(group_id, session.user_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[post("requests/{user_id}/approve")]
+async fn approve_join_request(request: HttpRequest, path: web::Path<(Id<GroupMarker>, Id<UserMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (group_id, user_id) = *path;
+	require_group_permission(group_id, session.user_id, GroupPermissions::MANAGE_MEMBERS)
+		.await?;
+	get_join_request(group_id, user_id).await?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		UPDATE team_members
+		SET is_pending = false, joined_at = now()
+		WHERE team_id = $1 AND user_id = $2
+		",
+		group_id.value,
+		user_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupJoinRequestApproved)
+		.target(ModelKind::GroupMembership(group_id, user_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Updated
+		.build(ModelKind::GroupMembership(group_id, user_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("requests/{user_id}")]
+async fn reject_join_request(request: HttpRequest, path: web::Path<(Id<GroupMarker>, Id<UserMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (group_id, user_id) = *path;
+	require_group_permission(group_id, session.user_id, GroupPermissions::MANAGE_MEMBERS)
+		.await?;
+	get_join_request(group_id, user_id).await?;
+	remove_invite(group_id, user_id, audit::entry(&request, &session, AuditLogAction::GroupJoinRequestRejected)).await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("membership")]
+async fn leave_group(request: HttpRequest, path: web::Path<Id<GroupMarker>>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let group_id = *path;
+	let membership = GroupMembershipModel::get_user(group_id, session.user_id)
+		.await?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::GroupMembership, None::<String>))?;
+
+	// leaving before joining just withdraws the invite or request
+	if membership.is_pending {
+		remove_invite(group_id, session.user_id, audit::entry(&request, &session, AuditLogAction::GroupLeft)).await?;
+		return Ok(HttpResponse::Ok().finish());
+	}
+
+	let permissions = GroupMembershipModel::get_permissions(group_id, session.user_id)
+		.await?
+		.unwrap_or_default();
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+
+	// someone has to be left holding the group
+	if membership.is_owner && lock_group_owners(&mut transaction, group_id).await? <= 1 {
+		return Err(ErrorModelKind::LastGroupOwner.model());
+	}
+	sqlx::query!(
+		"
+		DELETE FROM team_members
+		WHERE team_id = $1 AND user_id = $2
+		",
+		group_id.value,
+		session.user_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupLeft)
+		.target(ModelKind::GroupMembership(group_id, session.user_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Deleted
+		.build(ModelKind::GroupMembership(group_id, session.user_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+	if permissions.contains(GroupPermissions::MANAGE_MELLOW_SERVERS) {
+		enqueue_server_updates(&mut transaction, group_id, session.user_id)
+			.await?;
+	}
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+/// Locks the group's owners until the transaction is over, returning how many there are.
+/// Owners stepping down at the same time end up taking turns, so the last one can't slip through.
+async fn lock_group_owners(connection: &mut PgConnection, group_id: Id<GroupMarker>) -> Result<usize> {
+	Ok(sqlx::query!(
+		"
+		SELECT user_id
+		FROM team_members
+		WHERE team_id = $1 AND is_owner AND NOT is_pending
+		FOR UPDATE
+		",
+		group_id.value
+	)
+		.fetch_all(connection)
+		.await?
+		.len()
+	)
+}
+
+async fn get_joined_membership(group_id: Id<GroupMarker>, user_id: Id<UserMarker>) -> Result<GroupMembershipModel> {
+	GroupMembershipModel::get_user(group_id, user_id)
+		.await?
+		.filter(|x| !x.is_pending)
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::GroupMembership, None::<String>))
+}
+
+#[delete("memberships/{user_id}")]
+async fn remove_group_member(request: HttpRequest, path: web::Path<(Id<GroupMarker>, Id<UserMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	// owners have to step down before they can be removed, and nobody can remove someone who can do more than they can
+	let (group_id, user_id) = *path;
+	let permissions = GroupMembershipModel::get_permissions(group_id, session.user_id)
+		.await?
+		.unwrap_or_default();
+	if !permissions.contains(GroupPermissions::MANAGE_MEMBERS) {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+	if get_joined_membership(group_id, user_id).await?.is_owner {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+	let target_permissions = GroupMembershipModel::get_permissions(group_id, user_id)
+		.await?
+		.unwrap_or_default();
+	if !permissions.contains(target_permissions) {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		DELETE FROM team_members
+		WHERE team_id = $1 AND user_id = $2
+		",
+		group_id.value,
+		user_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupMemberRemoved)
+		.target(ModelKind::GroupMembership(group_id, user_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Deleted
+		.build(ModelKind::GroupMembership(group_id, user_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+	if target_permissions.contains(GroupPermissions::MANAGE_MELLOW_SERVERS) {
+		enqueue_server_updates(&mut transaction, group_id, session.user_id)
+			.await?;
+	}
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+async fn set_group_owner(connection: &mut PgConnection, group_id: Id<GroupMarker>, user_id: Id<UserMarker>, is_owner: bool, actionee_id: Id<UserMarker>) -> Result<()> {
+	sqlx::query!(
+		"
+		UPDATE team_members
+		SET is_owner = $3
+		WHERE team_id = $1 AND user_id = $2
+		",
+		group_id.value,
+		user_id.value,
+		is_owner
+	)
+		.execute(&mut *connection)
+		.await?;
+
+	ModelEventKind::Updated
+		.build(ModelKind::GroupMembership(group_id, user_id))
+		.actionee(actionee_id)
+		.enqueue(&mut *connection)
+		.await?;
+
+	Ok(())
+}
+
+#[derive(Deserialize)]
+struct TransferGroupOwnership {
+	user_id: Id<UserMarker>
+}
+
+#[post("ownership/transfer")]
+async fn transfer_group_ownership(request: HttpRequest, path: web::Path<Id<GroupMarker>>, payload: web::Json<TransferGroupOwnership>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let group_id = *path;
+	if payload.user_id == session.user_id {
+		return Err(ErrorModelKind::InvalidParams.model());
+	}
+	verify_group_owner(group_id, session.user_id)
+		.await?;
+	get_joined_membership(group_id, payload.user_id).await?;
+
+	// both sides change together, the new owner takes over in the same commit the old one steps down in
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	set_group_owner(&mut transaction, group_id, payload.user_id, true, session.user_id)
+		.await?;
+	set_group_owner(&mut transaction, group_id, session.user_id, false, session.user_id)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupOwnershipTransferred)
+		.target(ModelKind::GroupMembership(group_id, payload.user_id))
+		.insert(&mut transaction)
+		.await?;
+	enqueue_server_updates(&mut transaction, group_id, session.user_id)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[put("owners/{user_id}")]
+async fn add_group_owner(request: HttpRequest, path: web::Path<(Id<GroupMarker>, Id<UserMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (group_id, user_id) = *path;
+	verify_group_owner(group_id, session.user_id)
+		.await?;
+	if get_joined_membership(group_id, user_id).await?.is_owner {
+		return Ok(HttpResponse::Ok().finish());
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	set_group_owner(&mut transaction, group_id, user_id, true, session.user_id)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupOwnerAdded)
+		.target(ModelKind::GroupMembership(group_id, user_id))
+		.insert(&mut transaction)
+		.await?;
+	enqueue_server_updates(&mut transaction, group_id, session.user_id)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("owners/{user_id}")]
+async fn remove_group_owner(request: HttpRequest, path: web::Path<(Id<GroupMarker>, Id<UserMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	// owners can step down themselves, or be demoted by any other owner
+	let (group_id, user_id) = *path;
+	verify_group_owner(group_id, session.user_id)
+		.await?;
+	if !get_joined_membership(group_id, user_id).await?.is_owner {
+		return Ok(HttpResponse::Ok().finish());
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	if lock_group_owners(&mut transaction, group_id).await? <= 1 {
+		return Err(ErrorModelKind::LastGroupOwner.model());
+	}
+	set_group_owner(&mut transaction, group_id, user_id, false, session.user_id)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupOwnerRemoved)
+		.target(ModelKind::GroupMembership(group_id, user_id))
+		.insert(&mut transaction)
+		.await?;
+	enqueue_server_updates(&mut transaction, group_id, session.user_id)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+fn validate_permissions(permissions: &GroupPermissions) -> core::result::Result<(), ValidationError> {
+	if permissions.is_valid() {
+		Ok(())
+	} else {
+		Err(ValidationError::new("group_permissions"))
+	}
+}
+
+fn map_role_name_taken(error: sqlx::Error, name: &str) -> ErrorModel {
+	map_unique_violation(error, || ErrorModelKind::GroupRoleNameTaken { name: name.to_string() })
+}
+
+async fn get_group_role(group_id: Id<GroupMarker>, role_id: Id<GroupRoleMarker>) -> Result<GroupRoleModel> {
+	GroupRoleModel::get(group_id, role_id)
+		.await?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::GroupRole, Some(role_id)))
+}
+
+#[get("roles")]
+async fn get_group_roles(request: HttpRequest, path: web::Path<Id<GroupMarker>>, page: PageQuery<Id<GroupRoleMarker>>) -> Result<HttpResponse> {
+	get_visible_group(&request, &path.to_string()).await?;
+
+	let roles = GroupRoleModel::get_group_page(*path, page.direction_or(SortDirection::Asc), page.cursor, page.fetch_limit()).await?;
+	let total = GroupRoleModel::count_group(*path).await?;
+	Ok(HttpResponse::Ok().json(Page::new(roles, &page, total, |x| x.id)))
+}
+
+#[derive(Deserialize, Validate)]
+struct CreateGroupRole {
+	#[validate(length(min = 1, max = 32))]
+	name: String,
+	#[serde(default)]
+	#[validate(custom(function = "validate_permissions"))]
+	permissions: GroupPermissions
+}
+
+#[post("roles")]
+async fn create_group_role(request: HttpRequest, path: web::Path<Id<GroupMarker>>, payload: web::Json<CreateGroupRole>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	payload.validate()?;
+
+	// roles are only defined by owners, so nobody can hand themselves more than they were given
+	let group_id = *path;
+	verify_group_owner(group_id, session.user_id)
+		.await?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let role_id: Id<GroupRoleMarker> = sqlx::query!(
+		"
+		INSERT INTO team_roles (team_id, name, permissions)
+		VALUES ($1, $2, $3)
+		RETURNING id
+		",
+		group_id.value,
+		payload.name,
+		payload.permissions.bits() as i64
+	)
+		.fetch_one(&mut *transaction)
+		.await
+		.map_err(|error| map_role_name_taken(error, &payload.name))?
+		.id
+		.into();
+
+	audit::entry(&request, &session, AuditLogAction::GroupRoleCreated)
+		.target(ModelKind::GroupRole(group_id, role_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Created
+		.build(ModelKind::GroupRole(group_id, role_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().json(get_group_role(group_id, role_id).await?))
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+struct UpdateGroupRole {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[validate(length(min = 1, max = 32))]
+	name: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[validate(custom(function = "validate_permissions"))]
+	permissions: Option<GroupPermissions>
+}
+
+#[patch("roles/{role_id}")]
+async fn update_group_role(request: HttpRequest, path: web::Path<(Id<GroupMarker>, Id<GroupRoleMarker>)>, payload: web::Json<UpdateGroupRole>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	payload.validate()?;
+
+	let (group_id, role_id) = *path;
+	verify_group_owner(group_id, session.user_id)
+		.await?;
+	let role = get_group_role(group_id, role_id).await?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		UPDATE team_roles
+		SET
+			name = COALESCE($2, name),
+			permissions = COALESCE($3, permissions)
+		WHERE id = $1
+		",
+		role_id.value,
+		payload.name,
+		payload.permissions.map(|x| x.bits() as i64)
+	)
+		.execute(&mut *transaction)
+		.await
+		.map_err(|error| map_role_name_taken(error, payload.name.as_deref().unwrap_or_default()))?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupRoleUpdated)
+		.target(ModelKind::GroupRole(group_id, role_id))
+		.changes(&role, &*payload)?
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Updated
+		.build(ModelKind::GroupRole(group_id, role_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+	if (role.permissions | payload.permissions.unwrap_or_default()).contains(GroupPermissions::MANAGE_MELLOW_SERVERS) {
+		enqueue_server_updates(&mut transaction, group_id, session.user_id)
+			.await?;
+	}
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().json(get_group_role(group_id, role_id).await?))
+}
+
+#[delete("roles/{role_id}")]
+async fn delete_group_role(request: HttpRequest, path: web::Path<(Id<GroupMarker>, Id<GroupRoleMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (group_id, role_id) = *path;
+	verify_group_owner(group_id, session.user_id)
+		.await?;
+	let role = get_group_role(group_id, role_id).await?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+
+	// everyone who had the role loses it, so their memberships change too
+	let user_ids: Vec<Id<UserMarker>> = sqlx::query!(
+		"
+		DELETE FROM team_member_roles
+		WHERE role_id = $1
+		RETURNING user_id
+		",
+		role_id.value
+	)
+		.fetch_all(&mut *transaction)
+		.await?
+		.into_iter()
+		.map(|x| x.user_id.into())
+		.collect();
+	sqlx::query!(
+		"
+		DELETE FROM team_roles
+		WHERE id = $1
+		",
+		role_id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::GroupRoleDeleted)
+		.target(ModelKind::GroupRole(group_id, role_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Deleted
+		.build(ModelKind::GroupRole(group_id, role_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+	for user_id in user_ids {
+		ModelEventKind::Updated
+			.build(ModelKind::GroupMembership(group_id, user_id))
+			.actionee(session.user_id)
+			.enqueue(&mut transaction)
+			.await?;
+	}
+	if role.permissions.contains(GroupPermissions::MANAGE_MELLOW_SERVERS) {
+		enqueue_server_updates(&mut transaction, group_id, session.user_id)
+			.await?;
+	}
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+/// Checks the user can hand out (or take away) a role, they need to manage members and have everything the role grants.
+async fn verify_role_assigner(group_id: Id<GroupMarker>, user_id: Id<UserMarker>, role: &GroupRoleModel) -> Result<()> {
+	if GroupMembershipModel::get_permissions(group_id, user_id)
+		.await?
+		.is_some_and(|x| x.contains(GroupPermissions::MANAGE_MEMBERS | role.permissions))
+	{
+		return Ok(());
+	}
+
+	Err(ErrorModelKind::MissingPermission.model())
+}
+
+#[put("memberships/{user_id}/roles/{role_id}")]
+async fn assign_group_role(request: HttpRequest, path: web::Path<(Id<GroupMarker>, Id<UserMarker>, Id<GroupRoleMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (group_id, user_id, role_id) = *path;
+	let role = get_group_role(group_id, role_id).await?;
+	verify_role_assigner(group_id, session.user_id, &role)
+		.await?;
+
+	// invites can't be given roles until they're accepted
+	GroupMembershipModel::get_user(group_id, user_id)
+		.await?
+		.filter(|x| !x.is_pending)
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::GroupMembership, None::<String>))?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let assigned = sqlx::query!(
+		"
+		INSERT INTO team_member_roles (team_id, user_id, role_id)
+		VALUES ($1, $2, $3)
+		ON CONFLICT DO NOTHING
+		",
+		group_id.value,
+		user_id.value,
+		role_id.value
+	)
+		.execute(&mut *transaction)
+		.await?
+		.rows_affected() > 0;
+
+	if assigned {
+		audit::entry(&request, &session, AuditLogAction::GroupRoleAssigned)
+			.target(ModelKind::GroupRole(group_id, role_id))
+			.target_user(user_id)
+			.insert(&mut transaction)
+			.await?;
+		ModelEventKind::Updated
+			.build(ModelKind::GroupMembership(group_id, user_id))
+			.actionee(session.user_id)
+			.enqueue(&mut transaction)
+			.await?;
+		if role.permissions.contains(GroupPermissions::MANAGE_MELLOW_SERVERS) {
+			enqueue_server_updates(&mut transaction, group_id, session.user_id)
+				.await?;
+		}
+	}
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("memberships/{user_id}/roles/{role_id}")]
+async fn unassign_group_role(request: HttpRequest, path: web::Path<(Id<GroupMarker>, Id<UserMarker>, Id<GroupRoleMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (group_id, user_id, role_id) = *path;
+	let role = get_group_role(group_id, role_id).await?;
+	verify_role_assigner(group_id, session.user_id, &role)
+		.await?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let unassigned = sqlx::query!(
+		"
+		DELETE FROM team_member_roles
+		WHERE team_id = $1 AND user_id = $2 AND role_id = $3
+		",
+		group_id.value,
+		user_id.value,
+		role_id.value
+	)
+		.execute(&mut *transaction)
+		.await?
+		.rows_affected() > 0;
+
+	if unassigned {
+		audit::entry(&request, &session, AuditLogAction::GroupRoleUnassigned)
+			.target(ModelKind::GroupRole(group_id, role_id))
+			.target_user(user_id)
+			.insert(&mut transaction)
+			.await?;
+		ModelEventKind::Updated
+			.build(ModelKind::GroupMembership(group_id, user_id))
+			.actionee(session.user_id)
+			.enqueue(&mut transaction)
+			.await?;
+		if role.permissions.contains(GroupPermissions::MANAGE_MELLOW_SERVERS) {
+			enqueue_server_updates(&mut transaction, group_id, session.user_id)
+				.await?;
+		}
+	}
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[get("audit_log")]
+async fn get_group_audit_log(request: HttpRequest, path: web::Path<Id<GroupMarker>>, page: PageQuery<u64>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let group_id = *path;
+	verify_group_owner(group_id, session.user_id)
+		.await?;
+
+	let scope = AuditLogScope::Group(group_id);
+	let entries: Vec<_> = AuditLogEntryModel::get_page(scope, page.direction_or(SortDirection::Desc), page.cursor, page.fetch_limit())
+		.await?
+		.into_iter()
+		.map(AuditLogEntryModel::redacted)
+		.collect();
+	let total = AuditLogEntryModel::count(scope).await?;
+	Ok(HttpResponse::Ok().json(Page::new(entries, &page, total, |x| x.id)))
 }
\ No newline at end of file
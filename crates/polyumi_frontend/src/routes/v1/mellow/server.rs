@@ -1,12 +1,16 @@
-use actix_web::{ web, patch, HttpRequest, HttpResponse };
+use actix_web::{ web, get, patch, HttpRequest, HttpResponse };
 use polyumi_cache::CACHE;
 use polyumi_models::{
 	hakumi::{
-		group::GroupMembershipModel,
+		group::GroupPermissions,
 		user::connection::ConnectionKind
 	},
 	mellow::model_event::{ ModelEventKind, ModelKind },
-	polyumi::error::ErrorModelKind
+	pagination::SortDirection,
+	polyumi::{
+		audit_log::{ AuditLogAction, AuditLogEntryModel, AuditLogScope },
+		error::{ ErrorModelKind, ResourceKind }
+	}
 };
 use polyumi_util::{
 	id::{
@@ -16,6 +20,7 @@ use polyumi_util::{
 	PG_POOL
 };
 use serde::{ Deserialize, Serialize };
+use serde_json::json;
 use std::pin::Pin;
 use twilight_model::id::{
 	marker::GuildMarker as DiscordGuildMarker,
@@ -24,7 +29,10 @@ use twilight_model::id::{
 use validator::Validate;
 
 use crate::{
+	audit,
 	auth::get_session_from_request,
+	pagination::{ Page, PageQuery },
+	routes::v1::groups::require_group_permission,
 	Result
 };
 
@@ -33,41 +41,39 @@ pub fn config(config: &mut web::ServiceConfig) {
 		.service(web::scope("{server_id}")
 			.service(update_syncing_settings)
 			.service(update_user_settings)
+			.service(get_audit_log)
 		)
 	);
 }
 
-pub async fn verify_membership(server_id: DiscordId<DiscordGuildMarker>, user_id: Id<UserMarker>) -> Result<()> {
+/// Errors unless the user owns the server, or is a member of the group that owns it with `permissions`.
+pub async fn verify_membership(server_id: DiscordId<DiscordGuildMarker>, user_id: Id<UserMarker>, permissions: GroupPermissions) -> Result<()> {
 	let server = CACHE
 		.mellow
 		.server(server_id)
 		.await
-		.map_err(|_| ErrorModelKind::Cache.model())?;
-	if
-		let Some(owner_group_id) = server.owner_group_id &&
-		GroupMembershipModel::get_user(owner_group_id, user_id)
-			.await?
-			.is_some()
-	{
-		return Ok(());
-	}
+		.map_err(|_| ErrorModelKind::Cache.model())?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::MellowServer, Some(server_id)))?;
 	if server.owner_user_id == Some(user_id) {
 		return Ok(());
 	}
+	if let Some(owner_group_id) = server.owner_group_id {
+		return require_group_permission(owner_group_id, user_id, permissions).await;
+	}
 
 	Err(ErrorModelKind::MissingPermission.model())
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Serialize, Validate)]
 struct UpdateSyncingSettings {
-	#[serde(default)]
+	#[serde(default, skip_serializing_if = "Option::is_none")]
 	allow_forced_syncing: Option<bool>,
 
-	#[serde(default, with = "serde_with::rust::double_option")]
+	#[serde(default, with = "serde_with::rust::double_option", skip_serializing_if = "Option::is_none")]
 	#[validate(length(max = 32))]
 	default_nickname: Option<Option<String>>,
 
-	#[serde(default, with = "serde_with::rust::double_option")]
+	#[serde(default, with = "serde_with::rust::double_option", skip_serializing_if = "Option::is_none")]
 	skip_onboarding_to: Option<Option<ConnectionKind>>
 }
 
@@ -79,13 +85,24 @@ async fn update_syncing_settings(request: HttpRequest, path: web::Path<u64>, pay
 
 	let server_id: DiscordId<DiscordGuildMarker> = DiscordId::new_checked(*path)
 		.ok_or_else(|| ErrorModelKind::InvalidParams.model())?;
-	verify_membership(server_id, session.user_id)
+	verify_membership(server_id, session.user_id, GroupPermissions::MANAGE_MELLOW_SERVERS)
 		.await?;
 
 	let pinned = Pin::static_ref(&PG_POOL).await;
 	let mut transaction = pinned
 		.begin()
 		.await?;
+	let settings = sqlx::query!(
+		"
+		SELECT allow_forced_syncing, default_nickname, skip_onboarding_to
+		FROM mellow_servers
+		WHERE id = $1
+		FOR UPDATE
+		",
+		server_id.get() as i64
+	)
+		.fetch_one(&mut *transaction)
+		.await?;
 
 		if let Some(allow_forced_syncing) = &payload.allow_forced_syncing {
 			sqlx::query!(
@@ -131,20 +148,29 @@ async fn update_syncing_settings(request: HttpRequest, path: web::Path<u64>, pay
 			.await?;
 	}
 
+	audit::entry(&request, &session, AuditLogAction::MellowServerSettingsUpdated)
+		.target(ModelKind::Server(server_id))
+		.changes(&json!({
+			"allow_forced_syncing": settings.allow_forced_syncing,
+			"default_nickname": settings.default_nickname,
+			"skip_onboarding_to": settings.skip_onboarding_to
+		}), &*payload)?
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Updated
+		.build(ModelKind::Server(server_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
 	transaction
 		.commit()
 		.await?;
 
-	tokio::spawn(
-		ModelEventKind::Updated
-			.build(ModelKind::Server(server_id))
-			.send()
-	);
-
 	Ok(HttpResponse::Ok().finish())
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Serialize, Validate)]
 struct UpdateUserSettings {
 	#[validate(length(max = 16))]
 	user_connections: Vec<UpdateUserSettingsUserConnection>
@@ -169,6 +195,22 @@ async fn update_user_settings(request: HttpRequest, path: web::Path<(u64, Id<Use
 	}
 
 	// TODO: verify existence of provided user connections
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let user_connections = sqlx::query!(
+		"
+		SELECT user_connections
+		FROM mellow_user_server_settings
+		WHERE server_id = $1 AND user_id = $2
+		",
+		server_id.get() as i64,
+		user_id.value
+	)
+		.fetch_optional(&mut *transaction)
+		.await?
+		.map(|x| x.user_connections);
 	sqlx::query!(
 		"
 		INSERT INTO mellow_user_server_settings (server_id, user_connections, user_id)
@@ -180,14 +222,44 @@ async fn update_user_settings(request: HttpRequest, path: web::Path<(u64, Id<Use
 		serde_json::to_value(&payload.user_connections)?,
 		user_id.value
 	)
-		.execute(&*Pin::static_ref(&PG_POOL).await)
+		.execute(&mut *transaction)
 		.await?;
 
-	tokio::spawn(
-		ModelEventKind::Updated
-			.build(ModelKind::UserSettings(server_id, user_id))
-			.send()
-	);
+	audit::entry(&request, &session, AuditLogAction::MellowUserSettingsUpdated)
+		.target(ModelKind::UserSettings(server_id, user_id))
+		.changes(&json!({ "user_connections": user_connections }), &*payload)?
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Updated
+		.build(ModelKind::UserSettings(server_id, user_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
 
 	Ok(HttpResponse::Ok().finish())
+}
+
+#[get("audit_log")]
+async fn get_audit_log(request: HttpRequest, path: web::Path<u64>, page: PageQuery<u64>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let server_id: DiscordId<DiscordGuildMarker> = DiscordId::new_checked(*path)
+		.ok_or_else(|| ErrorModelKind::InvalidParams.model())?;
+	verify_membership(server_id, session.user_id, GroupPermissions::MANAGE_MELLOW_SERVERS)
+		.await?;
+
+	let scope = AuditLogScope::MellowServer(server_id);
+	let entries: Vec<_> = AuditLogEntryModel::get_page(scope, page.direction_or(SortDirection::Desc), page.cursor, page.fetch_limit())
+		.await?
+		.into_iter()
+		.map(AuditLogEntryModel::redacted)
+		.collect();
+	let total = AuditLogEntryModel::count(scope).await?;
+	Ok(HttpResponse::Ok().json(Page::new(entries, &page, total, |x| x.id)))
 }
\ No newline at end of file
@@ -3,10 +3,12 @@ use actix_web::web;
 pub mod auth;
 pub mod cafes;
 pub mod connection_callbacks;
+pub mod gateway;
 pub mod groups;
 pub mod mellow;
 pub mod users;
 pub mod visual_scripting;
+pub mod webhooks;
 
 pub fn config(config: &mut web::ServiceConfig) {
 	config.service(
@@ -15,8 +17,10 @@ pub fn config(config: &mut web::ServiceConfig) {
 			.configure(auth::config)
 			.configure(cafes::config)
 			.configure(connection_callbacks::config)
+			.configure(gateway::config)
 			.configure(groups::config)
 			.configure(mellow::config)
 			.configure(users::config)
+			.configure(webhooks::config)
 	);
 }
\ No newline at end of file
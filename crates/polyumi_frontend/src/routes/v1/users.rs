@@ -1,13 +1,22 @@
-use actix_web::{ delete, get, web, HttpRequest, HttpResponse };
+use actix_web::{ delete, get, put, web, HttpRequest, HttpResponse };
+use polyumi_cache::CACHE;
+use serde_json::json;
 use polyumi_models::{
 	hakumi::{
+		group::GroupCursor,
 		user::{
+			block::UserBlockModel,
 			connection::ConnectionModel,
 			inbox::InboxItemModel
 		},
 		GroupModel, UserModel
 	},
-	polyumi::error::{ ResourceKind, ErrorModelKind }
+	mellow::model_event::{ ModelEventKind, ModelKind },
+	pagination::SortDirection,
+	polyumi::{
+		audit_log::{ AuditLogAction, AuditLogEntryModel, AuditLogScope },
+		error::{ ResourceKind, ErrorModelKind }
+	}
 };
 use polyumi_util::{
 	id::{
@@ -19,9 +28,12 @@ use polyumi_util::{
 use std::pin::Pin;
 
 use crate::{
+	audit,
 	auth::get_session_from_request,
+	pagination::{ Page, PageQuery },
 	Result
 };
+use super::webhooks;
 
 pub fn config(config: &mut web::ServiceConfig) {
 	config.service(web::scope("user")
@@ -30,6 +42,12 @@ pub fn config(config: &mut web::ServiceConfig) {
 			.service(user_groups)
 			.service(user_inbox)
 			.service(user_connections)
+			.service(user_audit_log)
+			.service(user_blocks)
+			.service(block_user)
+			.service(unblock_user)
+			.service(webhooks::get_user_webhooks)
+			.service(webhooks::create_user_webhook)
 			.service(web::scope("connection")
 				.service(delete_user_connection)
 			)
@@ -46,46 +64,68 @@ async fn user_get(path: web::Path<String>) -> Result<HttpResponse> {
 }
 
 #[get("groups")]
-async fn user_groups(path: web::Path<String>) -> Result<HttpResponse> {
+async fn user_groups(request: HttpRequest, path: web::Path<String>, page: PageQuery<GroupCursor>) -> Result<HttpResponse> {
 	let user = UserModel::get(&path)
 		.await?
 		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::User, Some(path)))?;
 
-	let group_ids = UserModel::get_groups(user.id).await?;
-	Ok(HttpResponse::Ok().json(GroupModel::get_many(&group_ids).await?))
+	// groups that aren't public only show up for the user and anyone else in them
+	let viewer_id = get_session_from_request(&request)
+		.await?
+		.as_ref()
+		.map(|x| x.user_id);
+	let groups = GroupModel::get_user_page(user.id, viewer_id, page.direction_or(SortDirection::Asc), page.cursor.clone(), page.fetch_limit()).await?;
+	let total = GroupModel::count_user(user.id, viewer_id).await?;
+	Ok(HttpResponse::Ok().json(Page::new(groups, &page, total, GroupModel::cursor)))
 }
 
 #[get("inbox")]
-async fn user_inbox(request: HttpRequest, payload: web::Bytes) -> Result<HttpResponse> {
+async fn user_inbox(request: HttpRequest, payload: web::Bytes, page: PageQuery<u64>) -> Result<HttpResponse> {
 	let session = get_session_from_request(&request)
 		.await?
 		.required()?;
 	session.verify_request(&request, &payload)?;
-	
-	Ok(InboxItemModel::get_user_many(session.user_id)
-		.await
-		.map(|x| HttpResponse::Ok().json(x))?
-	)
+
+	let items = InboxItemModel::get_user_page(session.user_id, page.direction_or(SortDirection::Desc), page.cursor, page.fetch_limit()).await?;
+	let total = InboxItemModel::count_user(session.user_id).await?;
+	Ok(HttpResponse::Ok().json(Page::new(items, &page, total, |x| x.id)))
+}
+
+#[get("audit_log")]
+async fn user_audit_log(request: HttpRequest, path: web::Path<Id<UserMarker>>, page: PageQuery<u64>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	// where something came from is only shown to whoever did it
+	let user_id = *path;
+	if user_id != session.user_id {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+
+	let scope = AuditLogScope::User(user_id);
+	let entries: Vec<_> = AuditLogEntryModel::get_page(scope, page.direction_or(SortDirection::Desc), page.cursor, page.fetch_limit())
+		.await?
+		.into_iter()
+		.map(|x| if x.actor_id == Some(user_id) { x } else { x.redacted() })
+		.collect();
+	let total = AuditLogEntryModel::count(scope).await?;
+	Ok(HttpResponse::Ok().json(Page::new(entries, &page, total, |x| x.id)))
 }
 
 #[get("connections")]
-async fn user_connections(request: HttpRequest, path: web::Path<Id<UserMarker>>) -> Result<HttpResponse> {
+async fn user_connections(request: HttpRequest, path: web::Path<Id<UserMarker>>, page: PageQuery<Id<ConnectionMarker>>) -> Result<HttpResponse> {
 	let session_user_id = get_session_from_request(&request)
 		.await?
 		.as_ref()
 		.map(|x| x.user_id);
-	
+
+	// only the user themselves can see connections they haven't made public
 	let user_id = *path;
-	let models = ConnectionModel::get_user_many(user_id)
-		.await?;
-	Ok(HttpResponse::Ok().json(if session_user_id == Some(user_id) {
-		models
-	} else {
-		models
-			.into_iter()
-			.filter(|x| x.is_public)
-			.collect()
-	}))
+	let public_only = session_user_id != Some(user_id);
+	let connections = ConnectionModel::get_user_page(user_id, public_only, page.direction_or(SortDirection::Asc), page.cursor, page.fetch_limit()).await?;
+	let total = ConnectionModel::count_user(user_id, public_only).await?;
+	Ok(HttpResponse::Ok().json(Page::new(connections, &page, total, |x| x.id)))
 }
 
 #[delete("{connection_id}")]
@@ -100,13 +140,21 @@ async fn delete_user_connection(request: HttpRequest, payload: web::Bytes, path:
 		return Err(ErrorModelKind::MissingPermission.model());
 	}
 
-	let connection = ConnectionModel::get(connection_id)
-		.await?
-		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::UserConnection, Some(connection_id)))?;
-	if connection.user_id != user_id {
+	let connection_user_id = CACHE
+		.hakumi
+		.connection(connection_id)
+		.await
+		.map_err(|_| ErrorModelKind::Cache.model())?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::UserConnection, Some(connection_id)))?
+		.user_id;
+	if connection_user_id != user_id {
 		return Err(ErrorModelKind::MissingPermission.model());
 	}
 
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
 	sqlx::query!(
 		"
 		DELETE FROM user_connections
@@ -114,8 +162,130 @@ async fn delete_user_connection(request: HttpRequest, payload: web::Bytes, path:
 		",
 		connection_id.value
 	)
-		.execute(&*Pin::static_ref(&PG_POOL).await)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::UserConnectionDeleted)
+		.target(ModelKind::UserConnection(user_id, connection_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Deleted
+		.build(ModelKind::UserConnection(user_id, connection_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
 		.await?;
-	
+
+	transaction
+		.commit()
+		.await?;
+
 	Ok(HttpResponse::Ok().into())
+}
+
+#[get("blocks")]
+async fn user_blocks(request: HttpRequest, path: web::Path<Id<UserMarker>>, page: PageQuery<Id<UserMarker>>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let user_id = *path;
+	if user_id != session.user_id {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+
+	let blocks = UserBlockModel::get_user_page(user_id, page.direction_or(SortDirection::Desc), page.cursor, page.fetch_limit()).await?;
+	let total = UserBlockModel::count_user(user_id).await?;
+	Ok(HttpResponse::Ok().json(Page::new(blocks, &page, total, |x| x.blocked_user_id)))
+}
+
+/// Hides someone's cafe orders from the user and stops them from placing any on the user's profile.
+#[put("blocks/{blocked_user_id}")]
+async fn block_user(request: HttpRequest, path: web::Path<(Id<UserMarker>, Id<UserMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (user_id, blocked_user_id) = *path;
+	if user_id != session.user_id {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+	if blocked_user_id == user_id {
+		return Err(ErrorModelKind::InvalidParams.model());
+	}
+	if UserModel::get(&blocked_user_id.to_string()).await?.is_none() {
+		return Err(ErrorModelKind::not_found(ResourceKind::User, Some(blocked_user_id)));
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let is_new = sqlx::query!(
+		"
+		INSERT INTO user_blocks (user_id, blocked_user_id)
+		VALUES ($1, $2)
+		ON CONFLICT DO NOTHING
+		",
+		user_id.value,
+		blocked_user_id.value
+	)
+		.execute(&mut *transaction)
+		.await?
+		.rows_affected() > 0;
+
+	// blocks are kept to the blocker's own log, so the blocked user isn't the entry's target
+	if is_new {
+		audit::entry(&request, &session, AuditLogAction::UserBlocked)
+			.changes(&json!({ "blocked_user_id": null }), &json!({ "blocked_user_id": blocked_user_id }))?
+			.insert(&mut transaction)
+			.await?;
+	}
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("blocks/{blocked_user_id}")]
+async fn unblock_user(request: HttpRequest, path: web::Path<(Id<UserMarker>, Id<UserMarker>)>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+
+	let (user_id, blocked_user_id) = *path;
+	if user_id != session.user_id {
+		return Err(ErrorModelKind::MissingPermission.model());
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let rows_affected = sqlx::query!(
+		"
+		DELETE FROM user_blocks
+		WHERE user_id = $1 AND blocked_user_id = $2
+		",
+		user_id.value,
+		blocked_user_id.value
+	)
+		.execute(&mut *transaction)
+		.await?
+		.rows_affected();
+	if rows_affected == 0 {
+		return Err(ErrorModelKind::not_found(ResourceKind::UserBlock, Some(blocked_user_id)));
+	}
+
+	audit::entry(&request, &session, AuditLogAction::UserUnblocked)
+		.changes(&json!({ "blocked_user_id": blocked_user_id }), &json!({ "blocked_user_id": null }))?
+		.insert(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
 }
\ No newline at end of file
@@ -1,8 +1,14 @@
 use actix_web::{ web, post, HttpRequest, HttpResponse };
 use polyumi_models::{
-	hakumi::visual_scripting::{ DocumentModel, ElementModel },
+	hakumi::{
+		group::GroupPermissions,
+		visual_scripting::{ DocumentModel, ElementModel }
+	},
 	mellow::model_event::{ ModelEventKind, ModelKind },
-	polyumi::error::{ ResourceKind, ErrorModelKind }
+	polyumi::{
+		audit_log::AuditLogAction,
+		error::{ ResourceKind, ErrorModelKind }
+	}
 };
 use polyumi_util::{
 	id::{
@@ -16,6 +22,7 @@ use std::pin::Pin;
 use validator::Validate;
 
 use crate::{
+	audit,
 	auth::get_session_from_request,
 	routes::v1::mellow::server::verify_membership,
 	Result
@@ -37,20 +44,23 @@ struct UpdateDocument {
 
 #[post("{document_id}")]
 async fn update_document(request: HttpRequest, path: web::Path<Id<DocumentMarker>>, payload: web::Json<UpdateDocument>) -> Result<HttpResponse> {
-	let user_id = get_session_from_request(&request)
+	let session = get_session_from_request(&request)
 		.await?
-		.required()?
-		.user_id;
+		.required()?;
 
 	let document_id = *path;
 	let document = DocumentModel::get(document_id)
 		.await?
 		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::VisualScriptingDocument, Some(document_id)))?;
 	match document.mellow_server_id {
-		Some(server_id) => verify_membership(server_id, user_id).await?,
+		Some(server_id) => verify_membership(server_id, session.user_id, GroupPermissions::EDIT_VISUAL_SCRIPTS).await?,
 		None => return Err(ErrorModelKind::MissingPermission.model())
 	};
 
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
 	sqlx::query!(
 		"
 		UPDATE visual_scripting_documents
@@ -60,14 +70,22 @@ async fn update_document(request: HttpRequest, path: web::Path<Id<DocumentMarker
 		document_id.value,
 		serde_json::to_value(&payload.definition)?
 	)
-		.execute(&*Pin::static_ref(&PG_POOL).await)
+		.execute(&mut *transaction)
 		.await?;
 
-	tokio::spawn(
-		ModelEventKind::Updated
-			.build(ModelKind::VisualScriptingDocument(document.mellow_server_id, document_id))
-			.send()
-	);
+	audit::entry(&request, &session, AuditLogAction::VisualScriptingDocumentUpdated)
+		.target(ModelKind::VisualScriptingDocument(document.mellow_server_id, document_id))
+		.insert(&mut transaction)
+		.await?;
+	ModelEventKind::Updated
+		.build(ModelKind::VisualScriptingDocument(document.mellow_server_id, document_id))
+		.actionee(session.user_id)
+		.enqueue(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
 
 	Ok(HttpResponse::Ok().into())
 }
\ No newline at end of file
@@ -0,0 +1,336 @@
+use actix_web::{ delete, get, patch, post, web, HttpRequest, HttpResponse };
+use polyumi_models::{
+	hakumi::{
+		group::GroupMembershipModel,
+		webhook::{ self, WebhookDeliveryModel, WebhookModel }
+	},
+	mellow::model_event::is_event_type,
+	pagination::SortDirection,
+	polyumi::{
+		audit_log::AuditLogAction,
+		error::{ ResourceKind, ErrorModelKind },
+		SessionModel
+	}
+};
+use polyumi_util::{
+	id::{
+		marker::{ GroupMarker, UserMarker, WebhookMarker },
+		Id
+	},
+	PG_POOL
+};
+use serde::{ Deserialize, Serialize };
+use std::{
+	pin::Pin,
+	sync::Arc
+};
+use validator::{ Validate, ValidationError };
+
+use crate::{
+	audit,
+	auth::get_session_from_request,
+	pagination::{ Page, PageQuery },
+	Result
+};
+
+pub fn config(config: &mut web::ServiceConfig) {
+	config.service(web::scope("webhook")
+		.service(web::scope("{webhook_id}")
+			.service(get_webhook)
+			.service(update_webhook)
+			.service(delete_webhook)
+			.service(get_webhook_deliveries)
+			.service(redeliver_webhook_delivery)
+		)
+	);
+}
+
+async fn verify_owner(owner_group_id: Option<Id<GroupMarker>>, owner_user_id: Option<Id<UserMarker>>, user_id: Id<UserMarker>) -> Result<()> {
+	if owner_user_id == Some(user_id) {
+		return Ok(());
+	}
+	if
+		let Some(owner_group_id) = owner_group_id &&
+		GroupMembershipModel::get_user(owner_group_id, user_id)
+			.await?
+			.is_some_and(|x| x.is_owner)
+	{
+		return Ok(());
+	}
+
+	Err(ErrorModelKind::MissingPermission.model())
+}
+
+async fn get_owned_webhook(request: &HttpRequest, webhook_id: Id<WebhookMarker>) -> Result<(Arc<SessionModel>, WebhookModel)> {
+	let session = get_session_from_request(request)
+		.await?
+		.required()?;
+
+	let webhook = WebhookModel::get(webhook_id)
+		.await?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::Webhook, Some(webhook_id)))?;
+	verify_owner(webhook.owner_group_id, webhook.owner_user_id, session.user_id)
+		.await?;
+
+	Ok((session, webhook))
+}
+
+// webhooks are sent from inside our network, so they can't be pointed back into it.
+// delivery checks again, this only catches it early.
+async fn verify_public_url(url: &str) -> Result<()> {
+	if webhook::resolve_url(url).await.is_none() {
+		return Err(ErrorModelKind::InvalidParams.model());
+	}
+
+	Ok(())
+}
+
+fn validate_url(url: &str) -> core::result::Result<(), ValidationError> {
+	if url.starts_with("https://") || url.starts_with("http://") {
+		Ok(())
+	} else {
+		Err(ValidationError::new("url_scheme"))
+	}
+}
+
+fn validate_events(events: &[String]) -> core::result::Result<(), ValidationError> {
+	if events.iter().all(|x| is_event_type(x)) {
+		Ok(())
+	} else {
+		Err(ValidationError::new("event_type"))
+	}
+}
+
+#[derive(Deserialize, Validate)]
+pub struct CreateWebhook {
+	#[validate(url, length(max = 2048), custom(function = "validate_url"))]
+	url: String,
+
+	#[serde(default)]
+	#[validate(length(max = 32), custom(function = "validate_events"))]
+	events: Vec<String>
+}
+
+#[derive(Serialize)]
+struct CreatedWebhook {
+	#[serde(flatten)]
+	webhook: WebhookModel,
+	// only ever shown once, right here
+	secret: String
+}
+
+async fn create_webhook(request: &HttpRequest, session: &SessionModel, owner_group_id: Option<Id<GroupMarker>>, owner_user_id: Option<Id<UserMarker>>, payload: web::Json<CreateWebhook>) -> Result<HttpResponse> {
+	payload.validate()?;
+	verify_public_url(&payload.url).await?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	let webhook_id: Id<WebhookMarker> = sqlx::query!(
+		"
+		INSERT INTO webhooks (owner_group_id, owner_user_id, creator_id, url, secret, events)
+		VALUES ($1, $2, $3, $4, $5, $6)
+		RETURNING id
+		",
+		owner_group_id.map(|x| x.value),
+		owner_user_id.map(|x| x.value),
+		session.user_id.value,
+		payload.url,
+		WebhookModel::generate_secret(),
+		&payload.events
+	)
+		.fetch_one(&mut *transaction)
+		.await?
+		.id
+		.into();
+
+	audit::entry(request, session, AuditLogAction::WebhookCreated)
+		.webhook(webhook_id, owner_group_id, owner_user_id)
+		.insert(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	let webhook = WebhookModel::get(webhook_id)
+		.await?
+		.ok_or_else(|| ErrorModelKind::InternalError.model())?;
+	Ok(HttpResponse::Ok().json(CreatedWebhook {
+		secret: webhook.secret.clone(),
+		webhook
+	}))
+}
+
+#[get("webhooks")]
+pub async fn get_group_webhooks(request: HttpRequest, path: web::Path<Id<GroupMarker>>, page: PageQuery<Id<WebhookMarker>>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	verify_owner(Some(*path), None, session.user_id)
+		.await?;
+
+	let webhooks = WebhookModel::get_group_page(*path, page.direction_or(SortDirection::Asc), page.cursor, page.fetch_limit()).await?;
+	let total = WebhookModel::count_group(*path).await?;
+	Ok(HttpResponse::Ok().json(Page::new(webhooks, &page, total, |x| x.id)))
+}
+
+#[post("webhooks")]
+pub async fn create_group_webhook(request: HttpRequest, path: web::Path<Id<GroupMarker>>, payload: web::Json<CreateWebhook>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	verify_owner(Some(*path), None, session.user_id)
+		.await?;
+
+	create_webhook(&request, &session, Some(*path), None, payload).await
+}
+
+#[get("webhooks")]
+pub async fn get_user_webhooks(request: HttpRequest, path: web::Path<Id<UserMarker>>, page: PageQuery<Id<WebhookMarker>>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	verify_owner(None, Some(*path), session.user_id)
+		.await?;
+
+	let webhooks = WebhookModel::get_user_page(*path, page.direction_or(SortDirection::Asc), page.cursor, page.fetch_limit()).await?;
+	let total = WebhookModel::count_user(*path).await?;
+	Ok(HttpResponse::Ok().json(Page::new(webhooks, &page, total, |x| x.id)))
+}
+
+#[post("webhooks")]
+pub async fn create_user_webhook(request: HttpRequest, path: web::Path<Id<UserMarker>>, payload: web::Json<CreateWebhook>) -> Result<HttpResponse> {
+	let session = get_session_from_request(&request)
+		.await?
+		.required()?;
+	verify_owner(None, Some(*path), session.user_id)
+		.await?;
+
+	create_webhook(&request, &session, None, Some(*path), payload).await
+}
+
+#[get("")]
+async fn get_webhook(request: HttpRequest, path: web::Path<Id<WebhookMarker>>) -> Result<HttpResponse> {
+	let (_, webhook) = get_owned_webhook(&request, *path).await?;
+	Ok(HttpResponse::Ok().json(webhook))
+}
+
+#[derive(Deserialize, Serialize, Validate)]
+struct UpdateWebhook {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[validate(url, length(max = 2048), custom(function = "validate_url"))]
+	url: Option<String>,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[validate(length(max = 32), custom(function = "validate_events"))]
+	events: Option<Vec<String>>,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	is_enabled: Option<bool>
+}
+
+#[patch("")]
+async fn update_webhook(request: HttpRequest, path: web::Path<Id<WebhookMarker>>, payload: web::Json<UpdateWebhook>) -> Result<HttpResponse> {
+	payload.validate()?;
+	let (session, webhook) = get_owned_webhook(&request, *path).await?;
+	if let Some(url) = &payload.url {
+		verify_public_url(url).await?;
+	}
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		UPDATE webhooks
+		SET url = COALESCE($2, url), events = COALESCE($3, events), is_enabled = COALESCE($4, is_enabled)
+		WHERE id = $1
+		",
+		webhook.id.value,
+		payload.url,
+		payload.events.as_deref(),
+		payload.is_enabled
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::WebhookUpdated)
+		.webhook(webhook.id, webhook.owner_group_id, webhook.owner_user_id)
+		.changes(&webhook, &*payload)?
+		.insert(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("")]
+async fn delete_webhook(request: HttpRequest, path: web::Path<Id<WebhookMarker>>) -> Result<HttpResponse> {
+	let (session, webhook) = get_owned_webhook(&request, *path).await?;
+
+	// pending deliveries and the delivery log go along with it
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		DELETE FROM webhooks
+		WHERE id = $1
+		",
+		webhook.id.value
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	audit::entry(&request, &session, AuditLogAction::WebhookDeleted)
+		.webhook(webhook.id, webhook.owner_group_id, webhook.owner_user_id)
+		.insert(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
+
+#[get("deliveries")]
+async fn get_webhook_deliveries(request: HttpRequest, path: web::Path<Id<WebhookMarker>>, page: PageQuery<u64>) -> Result<HttpResponse> {
+	let (_, webhook) = get_owned_webhook(&request, *path).await?;
+
+	let deliveries = WebhookDeliveryModel::get_webhook_page(webhook.id, page.direction_or(SortDirection::Desc), page.cursor, page.fetch_limit()).await?;
+	let total = WebhookDeliveryModel::count_webhook(webhook.id).await?;
+	Ok(HttpResponse::Ok().json(Page::new(deliveries, &page, total, |x| x.id)))
+}
+
+#[post("deliveries/{delivery_id}/redeliver")]
+async fn redeliver_webhook_delivery(request: HttpRequest, path: web::Path<(Id<WebhookMarker>, u64)>) -> Result<HttpResponse> {
+	let (webhook_id, delivery_id) = *path;
+	let (session, webhook) = get_owned_webhook(&request, webhook_id).await?;
+
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	if !WebhookDeliveryModel::redeliver(webhook.id, delivery_id, &mut transaction).await? {
+		return Err(ErrorModelKind::not_found(ResourceKind::WebhookDelivery, Some(delivery_id)));
+	}
+
+	audit::entry(&request, &session, AuditLogAction::WebhookRedelivered)
+		.webhook(webhook.id, webhook.owner_group_id, webhook.owner_user_id)
+		.insert(&mut transaction)
+		.await?;
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(HttpResponse::Ok().finish())
+}
\ No newline at end of file
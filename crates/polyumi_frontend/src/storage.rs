@@ -0,0 +1,53 @@
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use std::path::PathBuf;
+
+use crate::Result;
+
+/// Where uploads end up, only the local filesystem for now.
+pub static STORAGE: Lazy<Box<dyn Storage>> = Lazy::new(|| Box::new(LocalStorage {
+	path: std::env::var("STORAGE_PATH")
+		.expect("STORAGE_PATH is not defined")
+		.into(),
+	public_url: std::env::var("STORAGE_URL")
+		.expect("STORAGE_URL is not defined")
+}));
+
+/// Somewhere files can be kept and fetched from by clients.
+pub trait Storage: Send + Sync {
+	/// Stores `body` under `key`, replacing whatever was there, and returns the URL it can be fetched from.
+	fn put<'a>(&'a self, key: &'a str, content_type: &'a str, body: Bytes) -> BoxFuture<'a, Result<String>>;
+
+	/// Removes whatever is stored under `key`, if anything.
+	fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Keeps files in a directory that's served as-is from `public_url`, by a reverse proxy or similar.
+pub struct LocalStorage {
+	path: PathBuf,
+	public_url: String
+}
+
+impl Storage for LocalStorage {
+	fn put<'a>(&'a self, key: &'a str, _content_type: &'a str, body: Bytes) -> BoxFuture<'a, Result<String>> {
+		Box::pin(async move {
+			let path = self.path.join(key);
+			if let Some(parent) = path.parent() {
+				tokio::fs::create_dir_all(parent).await?;
+			}
+			tokio::fs::write(path, body).await?;
+
+			Ok(format!("{}/{key}", self.public_url.trim_end_matches('/')))
+		})
+	}
+
+	fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>> {
+		Box::pin(async move {
+			match tokio::fs::remove_file(self.path.join(key)).await {
+				Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+				_ => Ok(())
+			}
+		})
+	}
+}
\ No newline at end of file
@@ -2,7 +2,7 @@ use actix_web::HttpResponse;
 use polyumi_cache::CACHE;
 use polyumi_models::{
 	hakumi::user::{ connection::ConnectionKind, UserModel },
-	polyumi::error::ErrorModelKind
+	polyumi::error::{ ErrorModelKind, ResourceKind }
 };
 use polyumi_util::id::{ marker::UserMarker, Id };
 use twilight_model::id::{ marker::GuildMarker, Id as DiscordId };
@@ -34,7 +34,8 @@ pub async fn mellow_done(connection_kind: ConnectionKind, server_id: DiscordId<G
 		.mellow
 		.server(server_id)
 		.await
-		.map_err(|_| ErrorModelKind::Cache.model())?;
+		.map_err(|_| ErrorModelKind::Cache.model())?
+		.ok_or_else(|| ErrorModelKind::not_found(ResourceKind::MellowServer, Some(server_id)))?;
 	body = body
 		.replace("{{ server_avatar }}", server.avatar_url.as_deref().unwrap_or(""))
 		.replace("{{ server_name }}", &server.name);
@@ -0,0 +1,161 @@
+use actix_web::{
+	http::StatusCode,
+	test::{ call_and_read_body_json, call_service, TestRequest }
+};
+use polyumi_util::PG_POOL;
+use serde_json::{ json, Value };
+use std::pin::Pin;
+
+mod common;
+use common::fixtures;
+
+#[test]
+fn group_audit_log() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+		let invitee = fixtures::user().await;
+
+		let name = fixtures::random_name("renamed");
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/group/{}", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "name": name, "bio": null }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/memberships", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "user_ids": [invitee.id] }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		// newest first, and nobody but the actor gets to see where it came from
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/audit_log", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["total"], 2);
+		assert_eq!(response["items"][0]["action"], "group_members_invited");
+		assert_eq!(response["items"][0]["target_user_id"], invitee.id.to_string());
+		assert_eq!(response["items"][1]["action"], "group_updated");
+		assert_eq!(response["items"][1]["actor_id"], owner.id.to_string());
+		assert_eq!(response["items"][1]["session_id"], Value::Null);
+		assert_eq!(response["items"][1]["diff"], json!({ "name": { "old": group.name, "new": name } }));
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/audit_log", group.id))
+			.cookie(common::session_cookie(member.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+	});
+}
+
+#[test]
+fn mellow_server_and_user_audit_log() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let server_id = fixtures::mellow_server(None, Some(owner.id)).await;
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/mellow/server/{server_id}/syncing/settings"))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "allow_forced_syncing": true }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/mellow/server/{server_id}/audit_log"))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["total"], 1);
+		assert_eq!(response["items"][0]["action"], "mellow_server_settings_updated");
+		assert_eq!(response["items"][0]["target_kind"], "server");
+		assert_eq!(response["items"][0]["diff"], json!({ "allow_forced_syncing": { "old": false, "new": true } }));
+
+		// the user's own log keeps the session it was done with
+		let request = TestRequest::get()
+			.uri(&format!("/v1/user/{}/audit_log", owner.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["total"], 1);
+		assert!(response["items"][0]["session_id"].is_string());
+
+		let stranger = fixtures::user().await;
+		let request = TestRequest::get()
+			.uri(&format!("/v1/user/{}/audit_log", owner.id))
+			.cookie(common::session_cookie(stranger.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+	});
+}
+
+#[test]
+fn audit_log_ignores_untrusted_forwarding_headers() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let server_id = fixtures::mellow_server(None, Some(owner.id)).await;
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/mellow/server/{server_id}/syncing/settings"))
+			.cookie(common::session_cookie(owner.id))
+			.peer_addr("203.0.113.7:4321".parse().unwrap())
+			.insert_header(("x-forwarded-for", "198.51.100.1"))
+			.set_json(json!({ "allow_forced_syncing": true }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/user/{}/audit_log", owner.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["items"][0]["ip_address"], "203.0.113.7");
+	});
+}
+
+#[test]
+fn audit_log_is_append_only() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+
+		let request = TestRequest::delete()
+			.uri(&format!("/v1/group/{}", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let pool = Pin::static_ref(&PG_POOL).await;
+		assert!(sqlx::query("DELETE FROM audit_log_entries WHERE group_id = $1")
+			.bind(group.id.value)
+			.execute(pool.get_ref())
+			.await
+			.is_err()
+		);
+		assert!(sqlx::query("UPDATE audit_log_entries SET action = 'nothing' WHERE group_id = $1")
+			.bind(group.id.value)
+			.execute(pool.get_ref())
+			.await
+			.is_err()
+		);
+
+		// the entry outlives the group it was about
+		let (action,): (String,) = sqlx::query_as("SELECT action FROM audit_log_entries WHERE group_id = $1")
+			.bind(group.id.value)
+			.fetch_one(pool.get_ref())
+			.await
+			.unwrap();
+		assert_eq!(action, "group_deleted");
+	});
+}
\ No newline at end of file
@@ -0,0 +1,321 @@
+use actix_web::{
+	http::StatusCode,
+	test::{ call_service, TestRequest }
+};
+use polyumi_cache::{
+	batch::{ BatchLoader, MAX_BATCH_SIZE },
+	loading::LoadingCache,
+	shared::Redis,
+	BoundedCache, Cache, CacheConfig, CACHE
+};
+use polyumi_models::{
+	mellow::model_event::{ ModelEventKind, ModelKind, MODEL_EVENT_BUS },
+	polyumi::{ audit_log, SessionModel }
+};
+use polyumi_util::PG_POOL;
+use serde_json::json;
+use std::{
+	pin::Pin,
+	sync::{
+		atomic::{ AtomicUsize, Ordering },
+		Arc, Mutex
+	},
+	time::Duration
+};
+use twilight_model::id::{
+	marker::GuildMarker,
+	Id as DiscordId
+};
+
+mod common;
+use common::fixtures;
+
+async fn rename_server(server_id: DiscordId<GuildMarker>, name: &str) {
+	sqlx::query("UPDATE mellow_servers SET name = $2 WHERE id = $1")
+		.bind(server_id.get() as i64)
+		.bind(name)
+		.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap();
+}
+
+async fn cached_server_name(server_id: DiscordId<GuildMarker>) -> String {
+	CACHE
+		.mellow
+		.server(server_id)
+		.await
+		.unwrap()
+		.expect("server doesn't exist")
+		.name
+		.clone()
+}
+
+#[test]
+fn syncing_settings_invalidate_cached_server() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let server_id = fixtures::mellow_server(None, Some(owner.id)).await;
+
+		// fills the cache
+		let old_name = cached_server_name(server_id).await;
+		rename_server(server_id, "renamed").await;
+		assert_eq!(cached_server_name(server_id).await, old_name);
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/mellow/server/{server_id}/syncing/settings"))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "allow_forced_syncing": true }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		assert_eq!(cached_server_name(server_id).await, "renamed");
+	});
+}
+
+#[test]
+fn deleted_connections_are_evicted() {
+	common::run(async {
+		let app = common::app().await;
+		let user = fixtures::user().await;
+		let connection_id = fixtures::user_connection(user.id).await;
+
+		assert_eq!(*CACHE.hakumi.user_connection_ids(user.id).await.unwrap(), vec![connection_id]);
+		assert!(CACHE.hakumi.connections.get(&connection_id).await.unwrap().is_some());
+
+		let request = TestRequest::delete()
+			.uri(&format!("/v1/user/{}/connection/{connection_id}", user.id))
+			.cookie(common::session_cookie(user.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		assert!(CACHE.hakumi.connection(connection_id).await.unwrap().is_none());
+		assert!(CACHE.hakumi.user_connection_ids(user.id).await.unwrap().is_empty());
+	});
+}
+
+#[test]
+fn reads_racing_a_write_are_evicted_on_commit() {
+	common::run(async {
+		let owner = fixtures::user().await;
+		let server_id = fixtures::mellow_server(None, Some(owner.id)).await;
+		let mut receiver = MODEL_EVENT_BUS
+			.subscribe()
+			.await
+			.unwrap();
+
+		let mut transaction = Pin::static_ref(&PG_POOL)
+			.await
+			.begin()
+			.await
+			.unwrap();
+		sqlx::query("UPDATE mellow_servers SET name = 'renamed' WHERE id = $1")
+			.bind(server_id.get() as i64)
+			.execute(&mut *transaction)
+			.await
+			.unwrap();
+		ModelEventKind::Updated
+			.build(ModelKind::Server(server_id))
+			.system()
+			.enqueue(&mut transaction)
+			.await
+			.unwrap();
+
+		// another request reads the old row back in before the write commits
+		let old_name = cached_server_name(server_id).await;
+		assert_ne!(old_name, "renamed");
+
+		transaction
+			.commit()
+			.await
+			.unwrap();
+		loop {
+			let event = tokio::time::timeout(Duration::from_secs(10), receiver.recv())
+				.await
+				.expect("timed out waiting for the event")
+				.unwrap();
+			if event.envelope.model == ModelKind::Server(server_id) {
+				break;
+			}
+		}
+
+		assert_eq!(cached_server_name(server_id).await, "renamed");
+	});
+}
+
+#[test]
+fn missing_servers_are_cached_until_invalidated() {
+	common::run(async {
+		let owner = fixtures::user().await;
+		let server_id = fixtures::mellow_server(None, Some(owner.id)).await;
+		sqlx::query("DELETE FROM mellow_servers WHERE id = $1")
+			.bind(server_id.get() as i64)
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		CACHE.invalidate(&ModelKind::Server(server_id)).await;
+		assert!(CACHE.mellow.server(server_id).await.unwrap().is_none());
+
+		// created behind the cache's back, so it keeps remembering there's nothing there
+		sqlx::query("INSERT INTO mellow_servers (id, name, owner_user_id) VALUES ($1, 'created', $2)")
+			.bind(server_id.get() as i64)
+			.bind(owner.id.value)
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		assert!(CACHE.mellow.server(server_id).await.unwrap().is_none());
+
+		CACHE.invalidate(&ModelKind::Server(server_id)).await;
+		assert_eq!(cached_server_name(server_id).await, "created");
+	});
+}
+
+#[test]
+fn bounded_caches_evict_and_expire() {
+	let cache = BoundedCache::new("test", CacheConfig::new(8, Duration::from_millis(200)));
+	for key in 0..64 {
+		cache.insert(key, key.to_string());
+	}
+	cache.run_pending_tasks();
+
+	let metrics = cache.metrics();
+	assert!(metrics.entries <= 8);
+	assert!(metrics.evictions >= 56);
+
+	cache.insert(100, "expires".to_string());
+	assert_eq!(cache.get(&100).as_deref().map(String::as_str), Some("expires"));
+	std::thread::sleep(Duration::from_millis(300));
+	assert!(cache.get(&100).is_none());
+
+	let metrics = cache.metrics();
+	assert_eq!((metrics.hits, metrics.misses), (1, 1));
+}
+
+#[test]
+fn redis_backed_caches_are_shared_between_instances() {
+	common::run(async {
+		let address = common::resp::start().await;
+		let mut instances = Vec::new();
+		for _ in 0..2 {
+			let cache = Cache::new(Some(Redis::open(&format!("redis://{address}")).unwrap()));
+			cache
+				.listen()
+				.await
+				.unwrap();
+			instances.push(cache);
+		}
+		let (first, second) = (&instances[0], &instances[1]);
+
+		let user = fixtures::user().await;
+		first.polyumi.sessions
+			.insert(audit_log::session_id("token"), SessionModel::new(user.id, None).unwrap())
+			.await
+			.unwrap();
+		let session = second.polyumi.sessions
+			.get(&audit_log::session_id("token"))
+			.await
+			.unwrap()
+			.expect("session wasn't shared");
+		assert_eq!(session.user_id, user.id);
+
+		let server_id = fixtures::mellow_server(None, Some(user.id)).await;
+		let old_name = first.mellow.server(server_id).await.unwrap().unwrap().name.clone();
+		assert_eq!(second.mellow.server(server_id).await.unwrap().unwrap().name, old_name);
+
+		rename_server(server_id, "renamed").await;
+		first.invalidate(&ModelKind::Server(server_id)).await;
+
+		// the second instance hears about it over pub/sub
+		tokio::time::timeout(Duration::from_secs(10), async {
+			while second.mellow.server(server_id).await.unwrap().unwrap().name != "renamed" {
+				tokio::time::sleep(Duration::from_millis(20)).await;
+			}
+		})
+			.await
+			.expect("second instance kept serving a stale server");
+	});
+}
+
+#[tokio::test]
+async fn concurrent_misses_share_one_load() {
+	let cache = LoadingCache::new("test", Box::new(BoundedCache::new("test", CacheConfig::new(8, Duration::from_secs(60)))), CacheConfig::new(8, Duration::from_secs(60)));
+	let loads = AtomicUsize::new(0);
+	let results = futures::future::join_all((0..50).map(|_| cache.get_or_load(&1, || async {
+		loads.fetch_add(1, Ordering::SeqCst);
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		Ok(Some("loaded"))
+	}))).await;
+
+	assert_eq!(loads.load(Ordering::SeqCst), 1);
+	assert!(results.into_iter().all(|x| x.unwrap().as_deref() == Some(&"loaded")));
+}
+
+#[tokio::test]
+async fn loads_racing_a_removal_are_not_kept() {
+	let cache = LoadingCache::new("test", Box::new(BoundedCache::new("test", CacheConfig::new(8, Duration::from_secs(60)))), CacheConfig::new(8, Duration::from_secs(60)));
+
+	// the write commits and is invalidated after the row was read, but before it's cached
+	let result = cache.get_or_load(&1, || async {
+		cache.remove(&1).await.unwrap();
+		Ok(Some("stale"))
+	}).await;
+	assert_eq!(result.unwrap().as_deref(), Some(&"stale"));
+	assert!(cache.get(&1).await.unwrap().is_none());
+
+	let result = cache.get_or_load(&1, || async { Ok(Some("fresh")) }).await;
+	assert_eq!(result.unwrap().as_deref(), Some(&"fresh"));
+	assert_eq!(cache.get(&1).await.unwrap().as_deref(), Some(&"fresh"));
+}
+
+#[tokio::test]
+async fn batch_loader_combines_concurrent_keys() {
+	let batches = Arc::new(Mutex::new(Vec::new()));
+	let loader = BatchLoader::new({
+		let batches = batches.clone();
+		move |mut keys: Vec<u32>| {
+			keys.sort();
+			batches.lock().unwrap().push(keys.clone());
+			async move {
+				Ok(keys
+					.into_iter()
+					.filter(|x| x % 2 == 0)
+					.map(|x| (x, x * 10))
+					.collect()
+				)
+			}
+		}
+	});
+
+	let results = futures::future::join_all((0..10).map(|x| loader.load(x))).await;
+	assert_eq!(*batches.lock().unwrap(), vec![(0..10).collect::<Vec<_>>()]);
+	for (key, result) in results.into_iter().enumerate() {
+		let expected = (key % 2 == 0).then_some(key as u32 * 10);
+		assert_eq!(result.unwrap(), expected);
+	}
+}
+
+#[tokio::test]
+async fn batch_loader_splits_full_batches() {
+	let batch_sizes = Arc::new(Mutex::new(Vec::new()));
+	let loader = BatchLoader::new({
+		let batch_sizes = batch_sizes.clone();
+		move |keys: Vec<usize>| {
+			batch_sizes.lock().unwrap().push(keys.len());
+			async move {
+				Ok(keys
+					.into_iter()
+					.map(|x| (x, x))
+					.collect()
+				)
+			}
+		}
+	});
+
+	let key_count = MAX_BATCH_SIZE * 2 + 1;
+	let results = futures::future::join_all((0..key_count).map(|x| loader.load(x))).await;
+	assert!(results.into_iter().all(|x| x.unwrap().is_some()));
+
+	let batch_sizes = batch_sizes.lock().unwrap();
+	assert!(batch_sizes.iter().all(|x| *x <= MAX_BATCH_SIZE), "{batch_sizes:?}");
+	assert_eq!(batch_sizes.iter().sum::<usize>(), key_count);
+}
\ No newline at end of file
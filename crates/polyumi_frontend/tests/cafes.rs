@@ -0,0 +1,759 @@
+use actix_web::{
+	http::StatusCode,
+	test::{ call_and_read_body_json, call_service, TestRequest }
+};
+use polyumi_frontend::opengraph;
+use polyumi_models::{
+	hakumi::{ cafe::order::CafeOrderEmbed, group::GroupPermissions },
+	mellow::model_event::{ outbox, ModelEventKind, ModelKind }
+};
+use polyumi_util::PG_POOL;
+use reqwest::Url;
+use serde_json::{ json, Value };
+use std::pin::Pin;
+
+mod common;
+use common::fixtures;
+
+#[test]
+fn place_edit_and_delete_orders() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let cafe_id = fixtures::cafe(None, Some(owner.id)).await;
+		let author = fixtures::user().await;
+		let stranger = fixtures::user().await;
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(author.id))
+			.set_json(json!({ "content": "   " }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(author.id))
+			.set_json(json!({ "content": "hello!" }))
+			.to_request();
+		let order: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(order["author_id"], author.id.to_string());
+		assert_eq!(order["kind"], "message");
+		assert_eq!(order["payload"]["content"], "hello!");
+		assert_eq!(order["edited_at"], Value::Null);
+		let order_id = order["id"].as_u64().unwrap();
+
+		// only the author can edit, and only the author or the cafe's owner can delete
+		let edit = |user_id| TestRequest::patch()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{order_id}"))
+			.cookie(common::session_cookie(user_id))
+			.set_json(json!({ "content": "hello again!" }))
+			.to_request();
+		assert_eq!(call_service(&app, edit(owner.id)).await.status(), StatusCode::FORBIDDEN);
+		let order: Value = call_and_read_body_json(&app, edit(author.id)).await;
+		assert_eq!(order["payload"]["content"], "hello again!");
+		assert!(order["edited_at"].is_string());
+
+		let delete = |user_id| TestRequest::delete()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{order_id}"))
+			.cookie(common::session_cookie(user_id))
+			.to_request();
+		assert_eq!(call_service(&app, delete(stranger.id)).await.status(), StatusCode::FORBIDDEN);
+		assert_eq!(call_service(&app, delete(owner.id)).await.status(), StatusCode::OK);
+		assert_eq!(call_service(&app, delete(owner.id)).await.status(), StatusCode::NOT_FOUND);
+
+		while outbox::deliver_outbox().await.unwrap() > 0 {}
+		let events: Vec<_> = common::mellow()
+			.events()
+			.into_iter()
+			.filter(|x| x.model == ModelKind::CafeOrder(cafe_id, order_id))
+			.map(|x| (x.kind, x.actionee_id))
+			.collect();
+		assert_eq!(events, [
+			(ModelEventKind::Created, Some(author.id)),
+			(ModelEventKind::Updated, Some(author.id)),
+			(ModelEventKind::Deleted, Some(owner.id))
+		]);
+	});
+}
+
+#[test]
+fn group_cafes_are_moderated_by_members_who_manage_them() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let cafe_id = fixtures::cafe(Some(group.id), None).await;
+		let moderator = fixtures::user().await;
+		fixtures::group_member(group.id, moderator.id, false).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "content": "welcome!" }))
+			.to_request();
+		let order: Value = call_and_read_body_json(&app, request).await;
+
+		let delete = |user_id| TestRequest::delete()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{}", order["id"]))
+			.cookie(common::session_cookie(user_id))
+			.to_request();
+		assert_eq!(call_service(&app, delete(member.id)).await.status(), StatusCode::FORBIDDEN);
+		assert_eq!(call_service(&app, delete(moderator.id)).await.status(), StatusCode::FORBIDDEN);
+
+		fixtures::group_role(group.id, moderator.id, GroupPermissions::MANAGE_CAFE).await;
+		assert_eq!(call_service(&app, delete(moderator.id)).await.status(), StatusCode::OK);
+	});
+}
+
+#[test]
+fn list_orders_newest_first() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let cafe_id = fixtures::cafe(None, Some(owner.id)).await;
+		let other_cafe_id = fixtures::cafe(None, Some(fixtures::user().await.id)).await;
+		let author = fixtures::user().await;
+
+		for (cafe_id, content) in [(cafe_id, "first"), (other_cafe_id, "elsewhere"), (cafe_id, "second"), (cafe_id, "third")] {
+			let request = TestRequest::post()
+				.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+				.cookie(common::session_cookie(author.id))
+				.set_json(json!({ "content": content }))
+				.to_request();
+			assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		}
+
+		// orders from a newer version (or a bad migration) are flagged rather than breaking the cafe
+		sqlx::query("INSERT INTO cafe_orders (cafe_id, author_id, kind, payload) VALUES ($1, $2, 'message', '{\"kind\":\"sticker\"}')")
+			.bind(cafe_id as i64)
+			.bind(author.id.value)
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders?limit=2"))
+			.to_request();
+		let page: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(page["total"], 4);
+		assert_eq!(page["items"][0]["kind"], "unknown");
+		assert_eq!(page["items"][1]["payload"]["content"], "third");
+		assert_eq!(page["items"][1]["author"]["username"], author.username);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders?limit=2&cursor={}", page["next_cursor"].as_str().unwrap()))
+			.to_request();
+		let page: Value = call_and_read_body_json(&app, request).await;
+		let contents: Vec<_> = page["items"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|x| x["payload"]["content"].clone())
+			.collect();
+		assert_eq!(contents, ["second", "first"]);
+		assert_eq!(page["next_cursor"], Value::Null);
+
+		let request = TestRequest::get()
+			.uri("/v1/cafe/0/orders")
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+	});
+}
+
+#[test]
+fn replies_and_reactions() {
+	common::run(async {
+		let app = common::app().await;
+		let cafe_id = fixtures::cafe(None, Some(fixtures::user().await.id)).await;
+		let author = fixtures::user().await;
+		let replier = fixtures::user().await;
+
+		let place = |user_id, body: Value| TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(user_id))
+			.set_json(body)
+			.to_request();
+		let order: Value = call_and_read_body_json(&app, place(author.id, json!({ "content": "what's everyone having?" }))).await;
+		let order_id = order["id"].as_u64().unwrap();
+
+		let reply: Value = call_and_read_body_json(&app, place(replier.id, json!({ "content": "tea", "parent_id": order_id }))).await;
+		assert_eq!(reply["payload"]["kind"], "reply");
+		assert_eq!(reply["payload"]["parent_id"], order_id);
+
+		// threads don't nest
+		let request = place(author.id, json!({ "content": "coffee", "parent_id": reply["id"] }));
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+		let react = |user_id, emoji: &str| TestRequest::put()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{order_id}/reactions/{}", urlencoding::encode(emoji)))
+			.cookie(common::session_cookie(user_id))
+			.to_request();
+		assert_eq!(call_service(&app, react(author.id, "nice")).await.status(), StatusCode::BAD_REQUEST);
+		call_service(&app, react(author.id, "☕")).await;
+		call_service(&app, react(author.id, "☕")).await;
+		let order: Value = call_and_read_body_json(&app, react(replier.id, "☕")).await;
+		assert_eq!(order["reactions"], json!([{ "emoji": "☕", "count": 2, "reacted": true }]));
+
+		// replies and reactions only show up alongside what they belong to
+		let request = TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(replier.id))
+			.to_request();
+		let page: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(page["total"], 1);
+		assert_eq!(page["items"][0]["reply_count"], 1);
+		assert_eq!(page["items"][0]["reactions"][0]["count"], 2);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{order_id}/replies"))
+			.to_request();
+		let page: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(page["total"], 1);
+		assert_eq!(page["items"][0]["payload"]["content"], "tea");
+
+		let unreact = |user_id| TestRequest::delete()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{order_id}/reactions/{}", urlencoding::encode("☕")))
+			.cookie(common::session_cookie(user_id))
+			.to_request();
+		let order: Value = call_and_read_body_json(&app, unreact(replier.id)).await;
+		assert_eq!(order["reactions"], json!([{ "emoji": "☕", "count": 1, "reacted": false }]));
+		assert_eq!(call_service(&app, unreact(replier.id)).await.status(), StatusCode::NOT_FOUND);
+
+		let request = TestRequest::delete()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{order_id}"))
+			.cookie(common::session_cookie(author.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let (remaining,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM cafe_orders WHERE cafe_id = $1")
+			.bind(cafe_id as i64)
+			.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		assert_eq!(remaining, 0);
+	});
+}
+
+#[test]
+fn attachments_are_kept_in_storage() {
+	common::run(async {
+		let app = common::app().await;
+		let cafe_id = fixtures::cafe(None, Some(fixtures::user().await.id)).await;
+		let author = fixtures::user().await;
+
+		let upload = |body: &'static [u8]| TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/attachments"))
+			.cookie(common::session_cookie(author.id))
+			.insert_header(("content-type", "image/png"))
+			.set_payload(body)
+			.to_request();
+		assert_eq!(call_service(&app, upload(b"<html></html>")).await.status(), StatusCode::BAD_REQUEST);
+
+		let order: Value = call_and_read_body_json(&app, upload(b"\x89PNG\r\n\x1a\nnot really a png")).await;
+		assert_eq!(order["kind"], "attachment");
+		assert_eq!(order["payload"]["content_type"], "image/png");
+		assert_eq!(order["payload"]["size"], 24);
+		let key = order["payload"]["key"].as_str().unwrap();
+		assert_eq!(order["payload"]["url"], format!("{}/{key}", common::STORAGE_URL));
+
+		let path = std::path::Path::new(&std::env::var("STORAGE_PATH").unwrap()).join(key);
+		assert_eq!(std::fs::read(&path).unwrap(), b"\x89PNG\r\n\x1a\nnot really a png");
+
+		let request = TestRequest::delete()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{}", order["id"]))
+			.cookie(common::session_cookie(author.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		assert!(!path.exists());
+	});
+}
+
+#[test]
+fn embeds_use_cached_metadata() {
+	common::run(async {
+		let app = common::app().await;
+		let cafe_id = fixtures::cafe(None, Some(fixtures::user().await.id)).await;
+		let author = fixtures::user().await;
+
+		sqlx::query("INSERT INTO cafe_embeds (url, title, site_name) VALUES ('https://hakumi.test/menu', 'Menu', 'HAKUMI')")
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+
+		let embed = |url| TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/embeds"))
+			.cookie(common::session_cookie(author.id))
+			.set_json(json!({ "url": url }))
+			.to_request();
+		let order: Value = call_and_read_body_json(&app, embed("https://hakumi.test/menu")).await;
+		assert_eq!(order["kind"], "embed");
+		assert_eq!(order["payload"], json!({
+			"url": "https://hakumi.test/menu",
+			"title": "Menu",
+			"description": null,
+			"image_url": null,
+			"site_name": "HAKUMI"
+		}));
+
+		// nothing on the local network gets fetched
+		for url in ["http://127.0.0.1:8080/", "http://[::1]/", "http://10.0.0.1/", "file:///etc/passwd", "not a url"] {
+			assert_eq!(call_service(&app, embed(url)).await.status(), StatusCode::BAD_REQUEST, "{url}");
+		}
+	});
+}
+
+#[test]
+fn parse_opengraph_metadata() {
+	let html = r#"
+		<html><head>
+			<title>Fallback &amp; friends</title>
+			<meta name="description" content="plain description">
+			<META property='og:site_name' content='HAKUMI'>
+			<meta content="/images/cake.png" property="og:image" />
+		</head></html>
+	"#;
+	let url = Url::parse("https://hakumi.test/menu").unwrap();
+	let mut embed = CafeOrderEmbed {
+		url: url.to_string(),
+		title: None,
+		description: None,
+		image_url: None,
+		site_name: None
+	};
+	opengraph::parse(&mut embed, &url, html);
+	assert_eq!(embed.title.as_deref(), Some("Fallback & friends"));
+	assert_eq!(embed.description.as_deref(), Some("plain description"));
+	assert_eq!(embed.image_url.as_deref(), Some("https://hakumi.test/images/cake.png"));
+	assert_eq!(embed.site_name.as_deref(), Some("HAKUMI"));
+}
+
+#[test]
+fn create_group_and_topic_cafes() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+
+		let create = |user_id, body: Value| TestRequest::post()
+			.uri("/v1/cafe")
+			.cookie(common::session_cookie(user_id))
+			.set_json(body)
+			.to_request();
+		assert_eq!(call_service(&app, create(member.id, json!({ "kind": "group", "group_id": group.id }))).await.status(), StatusCode::FORBIDDEN);
+
+		let cafe: Value = call_and_read_body_json(&app, create(owner.id, json!({ "kind": "group", "group_id": group.id }))).await;
+		assert_eq!(cafe["kind"], "group");
+		assert_eq!(cafe["owner_group_id"], group.id.to_string());
+		assert_eq!(cafe["owner_user_id"], Value::Null);
+		assert_eq!(cafe["settings"], json!({
+			"post_permission": "everyone",
+			"slow_mode_seconds": 0,
+			"locked": false
+		}));
+		assert_eq!(cafe["word_filter"], json!([]));
+		assert_eq!(cafe["word_filter_action"], "flag");
+
+		// one board per group
+		let response = call_service(&app, create(owner.id, json!({ "kind": "group", "group_id": group.id }))).await;
+		assert_eq!(response.status(), StatusCode::CONFLICT);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/cafe/group/{}", group.id))
+			.to_request();
+		let group_cafe: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(group_cafe["id"], cafe["id"]);
+		assert!(group_cafe.get("word_filter").is_none());
+
+		for body in [json!({ "kind": "topic" }), json!({ "kind": "topic", "name": " " }), json!({ "kind": "profile" }), json!({ "kind": "group" })] {
+			assert_eq!(call_service(&app, create(member.id, body.clone())).await.status(), StatusCode::BAD_REQUEST, "{body}");
+		}
+		let cafe: Value = call_and_read_body_json(&app, create(member.id, json!({ "kind": "topic", "name": "tea" }))).await;
+		assert_eq!(cafe["kind"], "topic");
+		assert_eq!(cafe["name"], "tea");
+		assert_eq!(cafe["owner_user_id"], member.id.to_string());
+
+		// kinds from newer versions come back as they were stored
+		let (cafe_id,): (i64,) = sqlx::query_as("INSERT INTO cafes (kind, owner_user_id) VALUES ('forum', $1) RETURNING id")
+			.bind(owner.id.value)
+			.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		let request = TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}"))
+			.to_request();
+		let cafe: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(cafe["kind"], "forum");
+	});
+}
+
+#[test]
+fn concurrent_group_cafes_only_make_one() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+
+		let create = || TestRequest::post()
+			.uri("/v1/cafe")
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "kind": "group", "group_id": group.id }))
+			.to_request();
+		let (first, second) = futures::join!(call_service(&app, create()), call_service(&app, create()));
+		let mut statuses = [first.status(), second.status()];
+		statuses.sort();
+		assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+	});
+}
+
+#[test]
+fn cafe_settings_decide_who_can_place_orders() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let cafe_id = fixtures::cafe(Some(group.id), None).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+		let stranger = fixtures::user().await;
+
+		let update = |user_id, body: Value| TestRequest::patch()
+			.uri(&format!("/v1/cafe/{cafe_id}"))
+			.cookie(common::session_cookie(user_id))
+			.set_json(body)
+			.to_request();
+		assert_eq!(call_service(&app, update(member.id, json!({ "locked": true }))).await.status(), StatusCode::FORBIDDEN);
+		assert_eq!(call_service(&app, update(owner.id, json!({ "name": "renamed" }))).await.status(), StatusCode::BAD_REQUEST);
+
+		let place = |user_id| TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(user_id))
+			.set_json(json!({ "content": "hi" }))
+			.to_request();
+		let cafe: Value = call_and_read_body_json(&app, update(owner.id, json!({ "post_permission": "members" }))).await;
+		assert_eq!(cafe["settings"]["post_permission"], "members");
+		assert_eq!(call_service(&app, place(stranger.id)).await.status(), StatusCode::FORBIDDEN);
+		let order: Value = call_and_read_body_json(&app, place(member.id)).await;
+
+		// reactions aren't held back by slow mode
+		call_service(&app, update(owner.id, json!({ "slow_mode_seconds": 60 }))).await;
+		let response = call_service(&app, place(member.id)).await;
+		assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+		let error: Value = actix_web::test::read_body_json(response).await;
+		assert_eq!(error["error"]["kind"], "cafe_slow_mode");
+		assert!(error["error"]["retry_after"].as_u64().unwrap() > 55);
+
+		let request = TestRequest::put()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{}/reactions/{}", order["id"], urlencoding::encode("🍵")))
+			.cookie(common::session_cookie(member.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		// only moderators can place or edit orders in a locked cafe
+		call_service(&app, update(owner.id, json!({ "locked": true, "slow_mode_seconds": 0 }))).await;
+		assert_eq!(call_service(&app, place(member.id)).await.status(), StatusCode::FORBIDDEN);
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{}", order["id"]))
+			.cookie(common::session_cookie(member.id))
+			.set_json(json!({ "content": "hello" }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+		assert_eq!(call_service(&app, place(owner.id)).await.status(), StatusCode::OK);
+
+		let (diff,): (Value,) = sqlx::query_as("SELECT diff FROM audit_log_entries WHERE action = 'cafe_updated' AND target = $1 ORDER BY id DESC LIMIT 1")
+			.bind(json!({ "Cafe": cafe_id }))
+			.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		assert_eq!(diff, json!({
+			"locked": { "old": false, "new": true },
+			"slow_mode_seconds": { "old": 60, "new": 0 }
+		}));
+	});
+}
+
+#[test]
+fn private_group_cafes_are_hidden_from_outsiders() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let cafe_id = fixtures::cafe(Some(group.id), None).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+		let stranger = fixtures::user().await;
+		sqlx::query("UPDATE teams SET visibility = 'private' WHERE id = $1")
+			.bind(group.id.value)
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+
+		for uri in [format!("/v1/cafe/{cafe_id}"), format!("/v1/cafe/group/{}", group.id), format!("/v1/cafe/{cafe_id}/orders")] {
+			for (user_id, status) in [(Some(stranger.id), StatusCode::NOT_FOUND), (None, StatusCode::NOT_FOUND), (Some(member.id), StatusCode::OK)] {
+				let mut request = TestRequest::get().uri(&uri);
+				if let Some(user_id) = user_id {
+					request = request.cookie(common::session_cookie(user_id));
+				}
+				assert_eq!(call_service(&app, request.to_request()).await.status(), status, "{uri}");
+			}
+		}
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(stranger.id))
+			.set_json(json!({ "content": "hi" }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+	});
+}
+
+#[test]
+fn reports_go_to_the_moderation_queue() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let cafe_id = fixtures::cafe(None, Some(owner.id)).await;
+		let author = fixtures::user().await;
+		let reporter = fixtures::user().await;
+
+		let place = |content: &str| TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(author.id))
+			.set_json(json!({ "content": content }))
+			.to_request();
+		let rude: Value = call_and_read_body_json(&app, place("rude")).await;
+		let fine: Value = call_and_read_body_json(&app, place("fine")).await;
+
+		let report = |user_id, order: &Value, reason: &str| TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{}/reports", order["id"]))
+			.cookie(common::session_cookie(user_id))
+			.set_json(json!({ "reason": reason }))
+			.to_request();
+		assert_eq!(call_service(&app, report(author.id, &rude, "me?")).await.status(), StatusCode::BAD_REQUEST);
+		assert_eq!(call_service(&app, report(reporter.id, &rude, " ")).await.status(), StatusCode::BAD_REQUEST);
+
+		// reporting again while it's open only changes the reason
+		let first: Value = call_and_read_body_json(&app, report(reporter.id, &rude, "mean")).await;
+		let second: Value = call_and_read_body_json(&app, report(reporter.id, &rude, "very mean")).await;
+		assert_eq!(first["id"], second["id"]);
+		assert_eq!(second["reason"], "very mean");
+		assert_eq!(second["order"]["payload"]["content"], "rude");
+		let other: Value = call_and_read_body_json(&app, report(owner.id, &rude, "agreed")).await;
+		let dismissed: Value = call_and_read_body_json(&app, report(reporter.id, &fine, "hmm")).await;
+
+		let queue = |user_id, resolved: bool| TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}/reports?resolved={resolved}"))
+			.cookie(common::session_cookie(user_id))
+			.to_request();
+		assert_eq!(call_service(&app, queue(reporter.id, false)).await.status(), StatusCode::FORBIDDEN);
+		let page: Value = call_and_read_body_json(&app, queue(owner.id, false)).await;
+		assert_eq!(page["total"], 3);
+		assert_eq!(page["items"][0]["id"], dismissed["id"]);
+
+		let resolve = |report: &Value, action: &str| TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/reports/{}/resolve", report["id"]))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "action": action }))
+			.to_request();
+		let report: Value = call_and_read_body_json(&app, resolve(&dismissed, "dismiss")).await;
+		assert_eq!(report["resolution"], "dismissed");
+		assert_eq!(report["resolver_id"], owner.id.to_string());
+		assert_eq!(call_service(&app, resolve(&dismissed, "dismiss")).await.status(), StatusCode::BAD_REQUEST);
+
+		// every open report about a deleted order is resolved along with it
+		let report: Value = call_and_read_body_json(&app, resolve(&first, "delete_order")).await;
+		assert_eq!(report["resolution"], "order_deleted");
+		assert_eq!(report["order"], Value::Null);
+		let request = TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.to_request();
+		let page: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(page["total"], 1);
+
+		let page: Value = call_and_read_body_json(&app, queue(owner.id, false)).await;
+		assert_eq!(page["total"], 0);
+		let page: Value = call_and_read_body_json(&app, queue(owner.id, true)).await;
+		assert_eq!(page["total"], 3);
+		assert_eq!(page["items"][1]["id"], other["id"]);
+		assert_eq!(page["items"][1]["resolution"], "order_deleted");
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}/audit_log"))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let page: Value = call_and_read_body_json(&app, request).await;
+		let actions: Vec<_> = page["items"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|x| x["action"].as_str().unwrap())
+			.collect();
+		assert_eq!(&actions[..4], ["cafe_order_report_resolved", "cafe_order_deleted", "cafe_order_report_resolved", "cafe_order_reported"]);
+		assert_eq!(page["items"][0]["diff"], json!({ "resolution": { "old": null, "new": "order_deleted" } }));
+		assert_eq!(page["items"][0]["ip_address"], Value::Null);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}/audit_log"))
+			.cookie(common::session_cookie(author.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+	});
+}
+
+#[test]
+fn word_filter_flags_or_rejects_orders() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let cafe_id = fixtures::cafe(None, Some(owner.id)).await;
+		let author = fixtures::user().await;
+
+		let update = |body: Value| TestRequest::patch()
+			.uri(&format!("/v1/cafe/{cafe_id}"))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(body)
+			.to_request();
+		assert_eq!(call_service(&app, update(json!({ "word_filter": ["  "] }))).await.status(), StatusCode::BAD_REQUEST);
+		let cafe: Value = call_and_read_body_json(&app, update(json!({ "word_filter": ["Cheese"] }))).await;
+		assert_eq!(cafe["word_filter"], json!(["Cheese"]));
+		assert_eq!(cafe["word_filter_action"], "flag");
+
+		// nobody but moderators gets to know what's filtered
+		let get = |user_id| TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}"))
+			.cookie(common::session_cookie(user_id))
+			.to_request();
+		let cafe: Value = call_and_read_body_json(&app, get(author.id)).await;
+		assert!(cafe.get("word_filter").is_none());
+		assert!(cafe.get("word_filter_action").is_none());
+		let cafe: Value = call_and_read_body_json(&app, get(owner.id)).await;
+		assert_eq!(cafe["word_filter"], json!(["Cheese"]));
+
+		let place = |user_id, content: &str| TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(user_id))
+			.set_json(json!({ "content": content }))
+			.to_request();
+		let queue = || TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}/reports"))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+
+		// only whole words count, and moderators aren't held to the filter
+		let order: Value = call_and_read_body_json(&app, place(author.id, "cheesecake please")).await;
+		call_and_read_body_json::<_, _, Value>(&app, place(owner.id, "cheese")).await;
+		let page: Value = call_and_read_body_json(&app, queue()).await;
+		assert_eq!(page["total"], 0);
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{}", order["id"]))
+			.cookie(common::session_cookie(author.id))
+			.set_json(json!({ "content": "more CHEESE!" }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		let page: Value = call_and_read_body_json(&app, queue()).await;
+		assert_eq!(page["total"], 1);
+		assert_eq!(page["items"][0]["reason"], "word filter: Cheese");
+		assert_eq!(page["items"][0]["reporter_id"], Value::Null);
+		assert_eq!(page["items"][0]["order_id"], order["id"]);
+
+		// editing it again doesn't pile up reports
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders/{}", order["id"]))
+			.cookie(common::session_cookie(author.id))
+			.set_json(json!({ "content": "even more cheese" }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		let page: Value = call_and_read_body_json(&app, queue()).await;
+		assert_eq!(page["total"], 1);
+
+		call_service(&app, update(json!({ "word_filter_action": "reject" }))).await;
+		let response = call_service(&app, place(author.id, "cheese again")).await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+		let error: Value = actix_web::test::read_body_json(response).await;
+		assert_eq!(error["error"]["kind"], "cafe_word_filtered");
+		assert_eq!(error["error"]["word"], "Cheese");
+
+		// links are held to it by what they say about themselves too
+		sqlx::query("INSERT INTO cafe_embeds (url, title, description) VALUES ('https://hakumi.test/menu', 'Menu', 'Now with extra cheese')")
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		let request = TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/embeds"))
+			.cookie(common::session_cookie(author.id))
+			.set_json(json!({ "url": "https://hakumi.test/menu" }))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+		let error: Value = actix_web::test::read_body_json(response).await;
+		assert_eq!(error["error"]["kind"], "cafe_word_filtered");
+	});
+}
+
+#[test]
+fn blocked_users_are_hidden_and_kept_off_profiles() {
+	common::run(async {
+		let app = common::app().await;
+		let user = fixtures::user().await;
+		let cafe_id = fixtures::cafe(None, Some(user.id)).await;
+		let pest = fixtures::user().await;
+		let friend = fixtures::user().await;
+
+		let place = |user_id, content: &str| TestRequest::post()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(user_id))
+			.set_json(json!({ "content": content }))
+			.to_request();
+		call_and_read_body_json::<_, _, Value>(&app, place(pest.id, "before")).await;
+		call_and_read_body_json::<_, _, Value>(&app, place(friend.id, "hello")).await;
+
+		let block = |blocker_id, blocked_id| TestRequest::put()
+			.uri(&format!("/v1/user/{blocker_id}/blocks/{blocked_id}"))
+			.cookie(common::session_cookie(user.id))
+			.to_request();
+		assert_eq!(call_service(&app, block(pest.id, friend.id)).await.status(), StatusCode::FORBIDDEN);
+		assert_eq!(call_service(&app, block(user.id, user.id)).await.status(), StatusCode::BAD_REQUEST);
+		assert_eq!(call_service(&app, block(user.id, pest.id)).await.status(), StatusCode::OK);
+		assert_eq!(call_service(&app, block(user.id, pest.id)).await.status(), StatusCode::OK);
+
+		assert_eq!(call_service(&app, place(pest.id, "after")).await.status(), StatusCode::FORBIDDEN);
+
+		let orders = |user_id| TestRequest::get()
+			.uri(&format!("/v1/cafe/{cafe_id}/orders"))
+			.cookie(common::session_cookie(user_id))
+			.to_request();
+		let page: Value = call_and_read_body_json(&app, orders(user.id)).await;
+		assert_eq!(page["total"], 1);
+		assert_eq!(page["items"][0]["payload"]["content"], "hello");
+		let page: Value = call_and_read_body_json(&app, orders(friend.id)).await;
+		assert_eq!(page["total"], 2);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/user/{}/blocks", user.id))
+			.cookie(common::session_cookie(user.id))
+			.to_request();
+		let page: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(page["total"], 1);
+		assert_eq!(page["items"][0]["blocked_user"]["username"], pest.username);
+
+		let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM audit_log_entries WHERE action = 'user_blocked' AND actor_id = $1")
+			.bind(user.id.value)
+			.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		assert_eq!(count, 1);
+
+		let unblock = || TestRequest::delete()
+			.uri(&format!("/v1/user/{}/blocks/{}", user.id, pest.id))
+			.cookie(common::session_cookie(user.id))
+			.to_request();
+		assert_eq!(call_service(&app, unblock()).await.status(), StatusCode::OK);
+		assert_eq!(call_service(&app, unblock()).await.status(), StatusCode::NOT_FOUND);
+		assert_eq!(call_service(&app, place(pest.id, "after")).await.status(), StatusCode::OK);
+	});
+}
\ No newline at end of file
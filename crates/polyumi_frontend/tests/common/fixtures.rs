@@ -0,0 +1,189 @@
+use polyumi_models::hakumi::group::GroupPermissions;
+use polyumi_util::{
+	id::{
+		marker::{ ConnectionMarker, GroupMarker, GroupRoleMarker, UserMarker },
+		Id
+	},
+	PG_POOL
+};
+use std::pin::Pin;
+use twilight_model::id::{
+	marker::GuildMarker as DiscordGuildMarker,
+	Id as DiscordId
+};
+use uuid::Uuid;
+
+// fixtures insert rows with random names so tests sharing a database never collide.
+pub fn random_name(prefix: &str) -> String {
+	format!("{prefix}_{}", &Uuid::new_v4().simple().to_string()[..12])
+}
+
+pub struct UserFixture {
+	pub id: Id<UserMarker>,
+	pub username: String
+}
+
+pub async fn user() -> UserFixture {
+	let username = random_name("user");
+	let (id,): (Uuid,) = sqlx::query_as(
+		"
+		INSERT INTO users (username)
+		VALUES ($1)
+		RETURNING id
+		"
+	)
+		.bind(&username)
+		.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap();
+
+	UserFixture {
+		id: id.into(),
+		username
+	}
+}
+
+pub struct GroupFixture {
+	pub id: Id<GroupMarker>,
+	pub name: String,
+	pub owner_id: Id<UserMarker>
+}
+
+pub async fn group(owner_id: Id<UserMarker>) -> GroupFixture {
+	let name = random_name("group");
+	let (id,): (Uuid,) = sqlx::query_as(
+		"
+		INSERT INTO teams (name, creator_id)
+		VALUES ($1, $2)
+		RETURNING id
+		"
+	)
+		.bind(&name)
+		.bind(owner_id.value)
+		.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap();
+
+	let group_id = id.into();
+	sqlx::query(
+		"
+		INSERT INTO team_members (team_id, user_id, is_owner)
+		VALUES ($1, $2, true)
+		"
+	)
+		.bind(id)
+		.bind(owner_id.value)
+		.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap();
+
+	GroupFixture {
+		id: group_id,
+		name,
+		owner_id
+	}
+}
+
+pub async fn group_member(group_id: Id<GroupMarker>, user_id: Id<UserMarker>, is_pending: bool) {
+	sqlx::query(
+		"
+		INSERT INTO team_members (team_id, user_id, is_invited, is_pending)
+		VALUES ($1, $2, $3, $3)
+		"
+	)
+		.bind(group_id.value)
+		.bind(user_id.value)
+		.bind(is_pending)
+		.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap();
+}
+
+/// Gives a member a new role with `permissions`.
+pub async fn group_role(group_id: Id<GroupMarker>, user_id: Id<UserMarker>, permissions: GroupPermissions) -> Id<GroupRoleMarker> {
+	let pinned = Pin::static_ref(&PG_POOL).await;
+	let (role_id,): (Uuid,) = sqlx::query_as(
+		"
+		INSERT INTO team_roles (team_id, name, permissions)
+		VALUES ($1, $2, $3)
+		RETURNING id
+		"
+	)
+		.bind(group_id.value)
+		.bind(random_name("role"))
+		.bind(permissions.bits() as i64)
+		.fetch_one(pinned.get_ref())
+		.await
+		.unwrap();
+	sqlx::query(
+		"
+		INSERT INTO team_member_roles (team_id, user_id, role_id)
+		VALUES ($1, $2, $3)
+		"
+	)
+		.bind(group_id.value)
+		.bind(user_id.value)
+		.bind(role_id)
+		.execute(pinned.get_ref())
+		.await
+		.unwrap();
+
+	Id::new(role_id)
+}
+
+pub async fn mellow_server(owner_group_id: Option<Id<GroupMarker>>, owner_user_id: Option<Id<UserMarker>>) -> DiscordId<DiscordGuildMarker> {
+	// discord snowflakes are positive i64s, mellow_servers.id is an int8
+	let server_id = DiscordId::new((Uuid::new_v4().as_u64_pair().0 >> 1) | 1);
+	sqlx::query(
+		"
+		INSERT INTO mellow_servers (id, name, owner_team_id, owner_user_id)
+		VALUES ($1, $2, $3, $4)
+		"
+	)
+		.bind(server_id.get() as i64)
+		.bind(random_name("server"))
+		.bind(owner_group_id.map(|x| x.value))
+		.bind(owner_user_id.map(|x| x.value))
+		.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap();
+
+	server_id
+}
+
+pub async fn user_connection(user_id: Id<UserMarker>) -> Id<ConnectionMarker> {
+	let (connection_id,): (Uuid,) = sqlx::query_as(
+		"
+		INSERT INTO user_connections (sub, type, user_id, username)
+		VALUES ($1, 0, $2, $3)
+		RETURNING id
+		"
+	)
+		.bind(Uuid::new_v4().to_string())
+		.bind(user_id.value)
+		.bind(random_name("connection"))
+		.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap();
+
+	connection_id.into()
+}
+
+/// A user's profile cafe, or a group's cafe when it's given a group.
+pub async fn cafe(owner_group_id: Option<Id<GroupMarker>>, owner_user_id: Option<Id<UserMarker>>) -> u64 {
+	let (cafe_id,): (i64,) = sqlx::query_as(
+		"
+		INSERT INTO cafes (kind, owner_group_id, owner_user_id)
+		VALUES ($1, $2, $3)
+		RETURNING id
+		"
+	)
+		.bind(if owner_group_id.is_some() { "group" } else { "profile" })
+		.bind(owner_group_id.map(|x| x.value))
+		.bind(owner_user_id.map(|x| x.value))
+		.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap();
+
+	cafe_id as u64
+}
\ No newline at end of file
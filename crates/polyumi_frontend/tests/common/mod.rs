@@ -0,0 +1,176 @@
+#![allow(dead_code)]
+use actix_web::{
+	cookie::Cookie,
+	dev::{ Service, ServiceResponse },
+	test,
+	App, Error
+};
+use actix_http::Request;
+use jsonwebtoken::Header;
+use once_cell::sync::Lazy;
+use polyumi_frontend::auth::ENCODING_KEY;
+use polyumi_mellow_stand_in::StandIn;
+use polyumi_util::{
+	id::{ marker::UserMarker, Id },
+	MIGRATOR, PG_POOL
+};
+use reqwest::Url;
+use sqlx::{ Connection, Executor, PgConnection };
+use std::{
+	pin::Pin,
+	sync::Mutex
+};
+use tokio::{
+	runtime::Runtime,
+	sync::OnceCell
+};
+use uuid::Uuid;
+
+pub mod fixtures;
+pub mod resp;
+
+// PG_POOL is a process-wide static, so every test in a binary has to run on the same runtime,
+// otherwise the pool's connections die alongside whichever test happened to create them.
+static RUNTIME: Lazy<Runtime> = Lazy::new(|| tokio::runtime::Builder::new_multi_thread()
+	.enable_all()
+	.build()
+	.unwrap()
+);
+
+static MELLOW: OnceCell<StandIn> = OnceCell::const_new();
+
+// tests in a binary share one database, so they take turns, each starting from an empty one.
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Where uploads appear to be served from, they're really kept in a temporary directory named after the test database.
+pub const STORAGE_URL: &str = "https://storage.polyumi.test";
+
+// a missing DATABASE_URL fails the test instead of skipping it, so it can't pass without having run.
+pub fn run<F: Future<Output = ()>>(test: F) {
+	assert!(std::env::var_os("DATABASE_URL").is_some(), "DATABASE_URL is not defined, these tests need a postgres server");
+
+	// a test that panicked while holding the lock has already failed, that's no reason to fail the rest
+	let _guard = TEST_LOCK
+		.lock()
+		.unwrap_or_else(|x| x.into_inner());
+	RUNTIME.block_on(async move {
+		MELLOW
+			.get_or_init(create_environment)
+			.await
+			.clear();
+		reset_database().await;
+		test.await
+	});
+}
+
+// empties every table but the migrations, sequences are left alone since ids only have to be unique.
+async fn reset_database() {
+	let pool = Pin::static_ref(&PG_POOL).await;
+	let tables: Vec<(String,)> = sqlx::query_as(
+		"
+		SELECT format('%I', tablename)
+		FROM pg_tables
+		WHERE schemaname = 'public' AND tablename <> '_sqlx_migrations'
+		"
+	)
+		.fetch_all(pool.get_ref())
+		.await
+		.unwrap();
+	let tables: Vec<_> = tables
+		.into_iter()
+		.map(|(x,)| x)
+		.collect();
+	pool
+		.get_ref()
+		.execute(format!("TRUNCATE {} CASCADE", tables.join(", ")).as_str())
+		.await
+		.unwrap();
+}
+
+/// The mellow stand-in that every model event in this test binary is delivered to.
+pub fn mellow() -> &'static StandIn {
+	MELLOW
+		.get()
+		.expect("mellow() called outside of run()")
+}
+
+// each test binary gets its own throwaway database, named polyumi_test_*, emptied before every test.
+// leftovers from previous runs are dropped once nothing is connected to them anymore.
+// model events are delivered to a local mellow stand-in instead of the real thing.
+async fn create_environment() -> StandIn {
+	let admin_url = std::env::var("DATABASE_URL")
+		.expect("DATABASE_URL is not defined");
+	let mut connection = PgConnection::connect(&admin_url)
+		.await
+		.unwrap();
+
+	let stale_databases: Vec<(String,)> = sqlx::query_as(
+		"
+		SELECT datname FROM pg_database d
+		WHERE datname LIKE 'polyumi_test_%' AND NOT EXISTS (
+			SELECT 1 FROM pg_stat_activity a WHERE a.datname = d.datname
+		)
+		"
+	)
+		.fetch_all(&mut connection)
+		.await
+		.unwrap();
+	for (database_name,) in stale_databases {
+		connection
+			.execute(format!(r#"DROP DATABASE IF EXISTS "{database_name}""#).as_str())
+			.await
+			.ok();
+	}
+
+	let database_name = format!("polyumi_test_{}", Uuid::new_v4().simple());
+	connection
+		.execute(format!(r#"CREATE DATABASE "{database_name}""#).as_str())
+		.await
+		.unwrap();
+
+	let mut database_url = Url::parse(&admin_url).unwrap();
+	database_url.set_path(&database_name);
+
+	// SAFETY: this runs once, before anything has touched PG_POOL or the secrets below,
+	// and every other test thread is parked on the MELLOW cell until it returns.
+	unsafe {
+		std::env::set_var("DATABASE_URL", database_url.as_str());
+		for key in ["ABSOLUTESOLVER", "AUTH_JWT_KEY"] {
+			if std::env::var_os(key).is_none() {
+				std::env::set_var(key, "polyumi_test");
+			}
+		}
+		std::env::set_var("STORAGE_PATH", std::env::temp_dir().join(&database_name));
+		std::env::set_var("STORAGE_URL", STORAGE_URL);
+		// receivers in these tests all run locally
+		std::env::set_var("WEBHOOK_ALLOWED_ADDRESSES", "127.0.0.1");
+	}
+
+	let mellow = StandIn::new(std::env::var("ABSOLUTESOLVER").unwrap());
+	let (server, address) = mellow
+		.server("127.0.0.1:0")
+		.unwrap();
+	tokio::spawn(server);
+
+	// SAFETY: same as above
+	unsafe {
+		std::env::set_var("MODEL_EVENT_ENDPOINTS", format!("http://{address}/internal/model_event"));
+	}
+
+	MIGRATOR
+		.run(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap();
+
+	mellow
+}
+
+pub async fn app() -> impl Service<Request, Response = ServiceResponse, Error = Error> {
+	test::init_service(App::new().configure(polyumi_frontend::config)).await
+}
+
+pub fn session_cookie(user_id: Id<UserMarker>) -> Cookie<'static> {
+	let jwt = jsonwebtoken::encode(&Header::default(), &serde_json::json!({ "sub": user_id }), &ENCODING_KEY)
+		.unwrap();
+	Cookie::new("auth-token", jwt)
+}
\ No newline at end of file
@@ -0,0 +1,136 @@
+// just enough of a redis server to back the shared cache, keys never expire.
+use std::{
+	collections::HashMap,
+	net::SocketAddr,
+	sync::{ Arc, Mutex }
+};
+use tokio::{
+	io::{ AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader },
+	net::{ TcpListener, TcpStream },
+	sync::broadcast
+};
+
+#[derive(Clone)]
+struct State {
+	values: Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>,
+	messages: broadcast::Sender<(Vec<u8>, Vec<u8>)>
+}
+
+pub async fn start() -> SocketAddr {
+	let listener = TcpListener::bind("127.0.0.1:0")
+		.await
+		.unwrap();
+	let address = listener.local_addr().unwrap();
+	let state = State {
+		values: Arc::default(),
+		messages: broadcast::channel(1024).0
+	};
+	tokio::spawn(async move {
+		while let Ok((stream, _)) = listener.accept().await {
+			tokio::spawn(handle(stream, state.clone()));
+		}
+	});
+
+	address
+}
+
+async fn read_command<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> Option<Vec<Vec<u8>>> {
+	let mut line = String::new();
+	if reader.read_line(&mut line).await.ok()? == 0 {
+		return None;
+	}
+	let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+
+	let mut arguments = Vec::with_capacity(count);
+	for _ in 0..count {
+		line.clear();
+		reader.read_line(&mut line).await.ok()?;
+		let length: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+		let mut argument = vec![0; length + 2];
+		reader.read_exact(&mut argument).await.ok()?;
+		argument.truncate(length);
+		arguments.push(argument);
+	}
+
+	Some(arguments)
+}
+
+fn bulk(value: &[u8]) -> Vec<u8> {
+	let mut reply = format!("${}\r\n", value.len()).into_bytes();
+	reply.extend_from_slice(value);
+	reply.extend_from_slice(b"\r\n");
+	reply
+}
+
+fn array(items: &[Vec<u8>]) -> Vec<u8> {
+	let mut reply = format!("*{}\r\n", items.len()).into_bytes();
+	for item in items {
+		reply.extend_from_slice(item);
+	}
+	reply
+}
+
+async fn handle(stream: TcpStream, state: State) {
+	let (reader, mut writer) = stream.into_split();
+	let mut reader = BufReader::new(reader);
+	let mut channels: Vec<Vec<u8>> = Vec::new();
+	let mut messages = state.messages.subscribe();
+	loop {
+		let command = tokio::select! {
+			command = read_command(&mut reader) => match command {
+				Some(x) => x,
+				None => return
+			},
+			Ok((channel, payload)) = messages.recv(), if !channels.is_empty() => {
+				if channels.contains(&channel) {
+					let reply = array(&[bulk(b"message"), bulk(&channel), bulk(&payload)]);
+					if writer.write_all(&reply).await.is_err() {
+						return;
+					}
+				}
+				continue;
+			}
+		};
+
+		let name = String::from_utf8_lossy(&command[0]).to_ascii_uppercase();
+		let reply = match name.as_str() {
+			"PING" => b"+PONG\r\n".to_vec(),
+			"GET" => match state.values.lock().unwrap().get(&command[1]) {
+				Some(value) => bulk(value),
+				None => b"$-1\r\n".to_vec()
+			},
+			"SET" => {
+				state.values.lock().unwrap().insert(command[1].clone(), command[2].clone());
+				b"+OK\r\n".to_vec()
+			},
+			"PSETEX" => {
+				state.values.lock().unwrap().insert(command[1].clone(), command[3].clone());
+				b"+OK\r\n".to_vec()
+			},
+			"DEL" => {
+				let removed = command[1..]
+					.iter()
+					.filter(|x| state.values.lock().unwrap().remove(*x).is_some())
+					.count();
+				format!(":{removed}\r\n").into_bytes()
+			},
+			"PUBLISH" => {
+				let receivers = state.messages.send((command[1].clone(), command[2].clone())).unwrap_or(0);
+				format!(":{receivers}\r\n").into_bytes()
+			},
+			"SUBSCRIBE" => {
+				let mut reply = Vec::new();
+				for channel in &command[1..] {
+					channels.push(channel.clone());
+					reply.extend(array(&[bulk(b"subscribe"), bulk(channel), format!(":{}\r\n", channels.len()).into_bytes()]));
+				}
+				reply
+			},
+			// CLIENT SETINFO and anything else the client sends on connect
+			_ => b"+OK\r\n".to_vec()
+		};
+		if writer.write_all(&reply).await.is_err() {
+			return;
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,298 @@
+use actix_web::{
+	body::MessageBody,
+	dev::ServiceResponse,
+	http::StatusCode,
+	test::{ call_service, TestRequest },
+	App, HttpServer
+};
+use futures::{ SinkExt, StreamExt };
+use polyumi_models::mellow::model_event::{ stream, ModelEventKind, ModelKind };
+use polyumi_util::PG_POOL;
+use serde_json::{ json, Value };
+use std::{
+	pin::{ pin, Pin },
+	time::Duration
+};
+use tokio_tungstenite::tungstenite::{
+	self,
+	client::IntoClientRequest,
+	Message
+};
+
+mod common;
+use common::fixtures::{ self, GroupFixture, UserFixture };
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reads server-sent events off a streaming response, one `data:` payload at a time.
+async fn read_events(response: ServiceResponse, count: usize) -> Vec<(Option<i64>, Value)> {
+	let mut body = pin!(response.into_body());
+	let mut buffer = String::new();
+	let mut events = Vec::new();
+	while events.len() < count {
+		let chunk = tokio::time::timeout(TIMEOUT, futures::future::poll_fn(|cx| body.as_mut().poll_next(cx)))
+			.await
+			.expect("timed out waiting for an event")
+			.expect("event stream ended")
+			.unwrap();
+		buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+
+		while let Some(index) = buffer.find("\n\n") {
+			let frame: String = buffer.drain(..index + 2).collect();
+			let mut id = None;
+			for line in frame.lines() {
+				if let Some(value) = line.strip_prefix("id: ") {
+					id = value.parse().ok();
+				} else if let Some(value) = line.strip_prefix("data: ") {
+					events.push((id, serde_json::from_str(value).unwrap()));
+				}
+			}
+		}
+	}
+
+	events
+}
+
+async fn invite(group: &GroupFixture, owner: &UserFixture, invitee: &UserFixture) {
+	let app = common::app().await;
+	let request = TestRequest::post()
+		.uri(&format!("/v1/group/{}/memberships", group.id))
+		.cookie(common::session_cookie(owner.id))
+		.set_json(json!({ "user_ids": [invitee.id] }))
+		.to_request();
+	assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+}
+
+async fn next_message<S: StreamExt<Item = Result<Message, tungstenite::Error>> + Unpin>(websocket: &mut S) -> Value {
+	loop {
+		let message = tokio::time::timeout(TIMEOUT, websocket.next())
+			.await
+			.expect("timed out waiting for a message")
+			.expect("websocket closed")
+			.unwrap();
+		if let Message::Text(text) = message {
+			return serde_json::from_str(&text).unwrap();
+		}
+	}
+}
+
+fn is_membership_event(message: &Value, group: &GroupFixture, user: &UserFixture) -> bool {
+	message["op"] == "event" && message["event"]["model"] == json!({ "GroupMembership": [group.id, user.id] })
+}
+
+#[test]
+fn event_stream_receives_subscribed_events() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/gateway?topics=group:{}", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(response.headers().get("content-type").unwrap(), "text/event-stream");
+
+		let invitee = fixtures::user().await;
+		invite(&group, &owner, &invitee).await;
+
+		let events = read_events(response, 2).await;
+		assert_eq!(events[0].1["op"], "ready");
+		assert_eq!(events[0].1["topics"], json!([format!("group:{}", group.id)]));
+		assert!(is_membership_event(&events[1].1, &group, &invitee));
+		assert_eq!(events[1].0, events[1].1["event"]["sequence"].as_i64());
+	});
+}
+
+#[test]
+fn event_stream_resumes_from_sequence() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let invitee = fixtures::user().await;
+		invite(&group, &owner, &invitee).await;
+		stream::publish().await.unwrap();
+
+		let (sequence,): (i64,) = sqlx::query_as("SELECT sequence FROM model_events WHERE $1 = ANY(topics)")
+			.bind(format!("group:{}", group.id))
+			.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/gateway?topics=group:{}", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.insert_header(("last-event-id", (sequence - 1).to_string()))
+			.to_request();
+		let events = read_events(call_service(&app, request).await, 2).await;
+		assert_eq!(events[0].1["op"], "ready");
+		assert!(is_membership_event(&events[1].1, &group, &invitee));
+		assert_eq!(events[1].0, Some(sequence));
+	});
+}
+
+#[test]
+fn event_stream_resume_includes_late_commits() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let pool = Pin::static_ref(&PG_POOL).await;
+
+		// enqueued before the invite but committed after it, a client that saw the invite still has to get it
+		let mut late = pool.begin().await.unwrap();
+		ModelEventKind::Updated
+			.build(ModelKind::Group(group.id))
+			.system()
+			.enqueue(&mut late)
+			.await
+			.unwrap();
+		invite(&group, &owner, &fixtures::user().await).await;
+		stream::publish().await.unwrap();
+
+		let (sequence,): (i64,) = sqlx::query_as("SELECT MAX(sequence) FROM model_events WHERE $1 = ANY(topics)")
+			.bind(format!("group:{}", group.id))
+			.fetch_one(pool.get_ref())
+			.await
+			.unwrap();
+		late.commit().await.unwrap();
+		stream::publish().await.unwrap();
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/gateway?topics=group:{}", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.insert_header(("last-event-id", sequence.to_string()))
+			.to_request();
+		let events = read_events(call_service(&app, request).await, 2).await;
+		assert_eq!(events[1].1["event"]["model"], json!({ "Group": group.id }));
+		assert!(events[1].0.unwrap() > sequence);
+	});
+}
+
+#[test]
+fn gateway_topic_permissions() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let stranger = fixtures::user().await;
+
+		// a private group's cafe is as hidden as the group
+		let private_group = fixtures::group(owner.id).await;
+		let cafe_id = fixtures::cafe(Some(private_group.id), None).await;
+		sqlx::query("UPDATE teams SET visibility = 'private' WHERE id = $1")
+			.bind(private_group.id.value)
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+
+		for (uri, status) in [
+			(format!("/v1/gateway?topics=group:{}", group.id), StatusCode::FORBIDDEN),
+			(format!("/v1/gateway?topics=cafe:{cafe_id}"), StatusCode::FORBIDDEN),
+			(format!("/v1/gateway?topics=user:{}", owner.id), StatusCode::FORBIDDEN),
+			("/v1/gateway?topics=nothing:1".into(), StatusCode::BAD_REQUEST)
+		] {
+			let request = TestRequest::get()
+				.uri(&uri)
+				.cookie(common::session_cookie(stranger.id))
+				.to_request();
+			assert_eq!(call_service(&app, request).await.status(), status);
+		}
+
+		let request = TestRequest::get()
+			.uri("/v1/gateway")
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+	});
+}
+
+#[test]
+fn websocket_subscribe_and_receive() {
+	common::run(async {
+		let server = HttpServer::new(|| App::new().configure(polyumi_frontend::config))
+			.workers(1)
+			.bind("127.0.0.1:0")
+			.unwrap();
+		let address = server.addrs()[0];
+		tokio::spawn(server.run());
+
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+
+		let mut request = format!("ws://{address}/v1/gateway")
+			.into_client_request()
+			.unwrap();
+		request.headers_mut().insert("cookie", common::session_cookie(owner.id).to_string().parse().unwrap());
+		let (mut websocket, _) = tokio_tungstenite::connect_async(request)
+			.await
+			.unwrap();
+
+		assert_eq!(next_message(&mut websocket).await["op"], "ready");
+
+		websocket
+			.send(Message::Text(json!({ "op": "subscribe", "topics": [format!("group:{}", group.id)] }).to_string()))
+			.await
+			.unwrap();
+		assert_eq!(next_message(&mut websocket).await["op"], "subscribed");
+
+		let invitee = fixtures::user().await;
+		invite(&group, &owner, &invitee).await;
+		assert!(is_membership_event(&next_message(&mut websocket).await, &group, &invitee));
+	});
+}
+
+#[test]
+fn removed_members_stop_receiving_group_events() {
+	common::run(async {
+		let server = HttpServer::new(|| App::new().configure(polyumi_frontend::config))
+			.workers(1)
+			.bind("127.0.0.1:0")
+			.unwrap();
+		let address = server.addrs()[0];
+		tokio::spawn(server.run());
+
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+		let other_group = fixtures::group(owner.id).await;
+
+		let mut request = format!("ws://{address}/v1/gateway")
+			.into_client_request()
+			.unwrap();
+		request.headers_mut().insert("cookie", common::session_cookie(member.id).to_string().parse().unwrap());
+		let (mut websocket, _) = tokio_tungstenite::connect_async(request)
+			.await
+			.unwrap();
+
+		assert_eq!(next_message(&mut websocket).await["op"], "ready");
+
+		websocket
+			.send(Message::Text(json!({ "op": "subscribe", "topics": [format!("group:{}", group.id), format!("user:{}", member.id)] }).to_string()))
+			.await
+			.unwrap();
+		assert_eq!(next_message(&mut websocket).await["op"], "subscribed");
+
+		let app = common::app().await;
+		let request = TestRequest::delete()
+			.uri(&format!("/v1/group/{}/memberships/{}", group.id, member.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		// they still hear about being removed, and then the group is taken away from them
+		assert!(is_membership_event(&next_message(&mut websocket).await, &group, &member));
+		let message = next_message(&mut websocket).await;
+		assert_eq!(message["op"], "unsubscribed");
+		assert_eq!(message["topics"], json!([format!("group:{}", group.id)]));
+
+		// events are passed on in order, so if the first invite was still coming through it'd show up before the second
+		let invitee = fixtures::user().await;
+		invite(&group, &owner, &invitee).await;
+		invite(&other_group, &owner, &member).await;
+		assert!(is_membership_event(&next_message(&mut websocket).await, &other_group, &member));
+	});
+}
\ No newline at end of file
@@ -0,0 +1,930 @@
+use actix_web::{
+	http::StatusCode,
+	test::{ call_and_read_body_json, call_service, read_body_json, TestRequest }
+};
+use polyumi_models::{
+	hakumi::group::GroupPermissions,
+	mellow::model_event::{ outbox, ModelEventKind, ModelKind }
+};
+use polyumi_util::{ id::{ marker::GroupMarker, Id }, PG_POOL };
+use serde_json::{ json, Value };
+use std::pin::Pin;
+use uuid::Uuid;
+
+mod common;
+use common::fixtures;
+
+#[test]
+fn get_group_by_name() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}", group.name))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["id"], group.id.to_string());
+		assert_eq!(response["name"], group.name);
+	});
+}
+
+#[test]
+fn get_group_memberships_skips_pending() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+		let invitee = fixtures::user().await;
+		fixtures::group_member(group.id, invitee.id, true).await;
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/memberships", group.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["total"], 2);
+		assert_eq!(response["next_cursor"], Value::Null);
+
+		let mut user_ids: Vec<_> = response["items"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|x| x["user"]["id"].as_str().unwrap().to_string())
+			.collect();
+		user_ids.sort();
+
+		let mut expected = vec![owner.id.to_string(), member.id.to_string()];
+		expected.sort();
+		assert_eq!(user_ids, expected);
+	});
+}
+
+#[test]
+fn get_group_memberships_pages_with_cursor() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		for _ in 0..4 {
+			let member = fixtures::user().await;
+			fixtures::group_member(group.id, member.id, false).await;
+		}
+
+		let mut user_ids = Vec::new();
+		let mut cursor: Option<String> = None;
+		loop {
+			let mut uri = format!("/v1/group/{}/memberships?limit=2&direction=desc", group.id);
+			if let Some(cursor) = &cursor {
+				uri.push_str(&format!("&cursor={cursor}"));
+			}
+
+			let request = TestRequest::get()
+				.uri(&uri)
+				.to_request();
+			let response: Value = call_and_read_body_json(&app, request).await;
+			assert_eq!(response["total"], 5);
+			for item in response["items"].as_array().unwrap() {
+				user_ids.push(item["user"]["id"].as_str().unwrap().to_string());
+			}
+
+			match response["next_cursor"].as_str() {
+				Some(x) => cursor = Some(x.to_string()),
+				None => break
+			}
+		}
+
+		// newest first, so the owner who created the group comes last
+		assert_eq!(user_ids.len(), 5);
+		assert_eq!(user_ids.last(), Some(&owner.id.to_string()));
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/memberships?filter=owners", group.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["total"], 1);
+		assert_eq!(response["items"][0]["user"]["id"], owner.id.to_string());
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/memberships?cursor=nonsense", group.id))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+	});
+}
+
+#[test]
+fn get_pending_group_memberships_requires_owner() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+		let invitee = fixtures::user().await;
+		fixtures::group_member(group.id, invitee.id, true).await;
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/memberships?filter=pending", group.id))
+			.cookie(common::session_cookie(member.id))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/memberships?filter=pending", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["total"], 1);
+		assert_eq!(response["items"][0]["user"]["id"], invitee.id.to_string());
+		assert_eq!(response["items"][0]["is_pending"], true);
+	});
+}
+
+#[test]
+fn get_group_membership_requires_session() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/membership", group.id))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/membership", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["is_owner"], true);
+		assert_eq!(response["user"]["id"], owner.id.to_string());
+	});
+}
+
+#[test]
+fn create_group_and_reject_taken_names() {
+	common::run(async {
+		let app = common::app().await;
+		let creator = fixtures::user().await;
+		let name = fixtures::random_name("group");
+
+		let request = TestRequest::post()
+			.uri("/v1/group")
+			.cookie(common::session_cookie(creator.id))
+			.set_json(json!({ "name": name, "display_name": "Cool Group", "profile_theme_accent_colour": 0xFF8800 }))
+			.to_request();
+		let group: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(group["name"], name);
+		assert_eq!(group["display_name"], "Cool Group");
+		assert_eq!(group["profile_theme_accent_colour"], 0xFF8800);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/membership", group["id"].as_str().unwrap()))
+			.cookie(common::session_cookie(creator.id))
+			.to_request();
+		let membership: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(membership["is_owner"], true);
+
+		let request = TestRequest::post()
+			.uri("/v1/group")
+			.cookie(common::session_cookie(creator.id))
+			.set_json(json!({ "name": name.to_uppercase() }))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::CONFLICT);
+
+		for payload in [json!({ "name": "no spaces allowed" }), json!({ "name": "ok" }), json!({ "name": fixtures::random_name("group"), "profile_theme_primary_colour": 0x1000000 })] {
+			let request = TestRequest::post()
+				.uri("/v1/group")
+				.cookie(common::session_cookie(creator.id))
+				.set_json(payload)
+				.to_request();
+			let response = call_service(&app, request).await;
+			assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+		}
+	});
+}
+
+#[test]
+fn update_group_requires_owner() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/group/{}", group.id))
+			.cookie(common::session_cookie(member.id))
+			.set_json(json!({ "bio": "hijacked" }))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/group/{}", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "bio": "hello", "display_name": "Hello" }))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["bio"], "hello");
+		assert_eq!(response["display_name"], "Hello");
+		assert_eq!(response["name"], group.name);
+
+		// null clears a field, leaving it out leaves it alone
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/group/{}", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "display_name": null }))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["bio"], "hello");
+		assert_eq!(response["display_name"], Value::Null);
+
+		while outbox::deliver_outbox().await.unwrap() > 0 {}
+		let updates = common::mellow()
+			.events()
+			.into_iter()
+			.filter(|x| x.model == ModelKind::Group(group.id) && x.kind == ModelEventKind::Updated)
+			.count();
+		assert_eq!(updates, 2);
+	});
+}
+
+#[test]
+fn delete_group_requires_owner() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+		let server_id = fixtures::mellow_server(Some(group.id), None).await;
+
+		let request = TestRequest::delete()
+			.uri(&format!("/v1/group/{}", group.id))
+			.cookie(common::session_cookie(member.id))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+		let request = TestRequest::delete()
+			.uri(&format!("/v1/group/{}", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::OK);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}", group.id))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+		while outbox::deliver_outbox().await.unwrap() > 0 {}
+		let events = common::mellow().events();
+		assert!(events.iter().any(|x| x.model == ModelKind::Group(group.id) && x.kind == ModelEventKind::Deleted));
+		assert!(events.iter().any(|x| x.model == ModelKind::Server(server_id) && x.kind == ModelEventKind::Updated));
+	});
+}
+
+#[test]
+fn invite_rejects_existing_members_and_invites() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+		let invitee = fixtures::user().await;
+		fixtures::group_member(group.id, invitee.id, true).await;
+
+		for (user_id, kind) in [(member.id, "user_already_in_group"), (invitee.id, "user_already_pending_in_group")] {
+			let request = TestRequest::post()
+				.uri(&format!("/v1/group/{}/memberships", group.id))
+				.cookie(common::session_cookie(owner.id))
+				.set_json(json!({ "user_ids": [user_id] }))
+				.to_request();
+			let response = call_service(&app, request).await;
+			assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+			let body: Value = read_body_json(response).await;
+			assert_eq!(body["error"]["kind"], kind);
+		}
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/memberships", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "user_ids": [fixtures::user().await.id, Uuid::new_v4()] }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+
+		// neither pending invitees nor members without a role that manages members can invite anyone
+		for user_id in [invitee.id, member.id] {
+			let request = TestRequest::post()
+				.uri(&format!("/v1/group/{}/memberships", group.id))
+				.cookie(common::session_cookie(user_id))
+				.set_json(json!({ "user_ids": [fixtures::user().await.id] }))
+				.to_request();
+			let response = call_service(&app, request).await;
+			assert_eq!(response.status(), StatusCode::FORBIDDEN);
+		}
+	});
+}
+
+#[test]
+fn accept_and_decline_invites() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let accepter = fixtures::user().await;
+		let decliner = fixtures::user().await;
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/memberships", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "user_ids": [accepter.id, decliner.id] }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/user/{}/inbox", accepter.id))
+			.cookie(common::session_cookie(accepter.id))
+			.to_request();
+		let inbox: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(inbox["total"], 1);
+		assert_eq!(inbox["items"][0]["kind"], "group_invite");
+		assert_eq!(inbox["items"][0]["related_group_id"], group.id.to_string());
+		assert_eq!(inbox["items"][0]["related_users"][0]["id"], owner.id.to_string());
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/membership/accept", group.id))
+			.cookie(common::session_cookie(accepter.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/membership", group.id))
+			.cookie(common::session_cookie(accepter.id))
+			.to_request();
+		let membership: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(membership["is_pending"], false);
+		assert_eq!(membership["invite_expires_at"], Value::Null);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/user/{}/inbox", accepter.id))
+			.cookie(common::session_cookie(accepter.id))
+			.to_request();
+		let inbox: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(inbox["total"], 0);
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/membership/decline", group.id))
+			.cookie(common::session_cookie(decliner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/membership", group.id))
+			.cookie(common::session_cookie(decliner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+
+		// there's nothing left to accept or decline
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/membership/accept", group.id))
+			.cookie(common::session_cookie(accepter.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+	});
+}
+
+#[test]
+fn cancel_invites() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let inviter = fixtures::user().await;
+		fixtures::group_member(group.id, inviter.id, false).await;
+		fixtures::group_role(group.id, inviter.id, GroupPermissions::MANAGE_MEMBERS).await;
+		let bystander = fixtures::user().await;
+		fixtures::group_member(group.id, bystander.id, false).await;
+
+		let invitees = [fixtures::user().await, fixtures::user().await];
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/memberships", group.id))
+			.cookie(common::session_cookie(inviter.id))
+			.set_json(json!({ "user_ids": [invitees[0].id, invitees[1].id] }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let request = TestRequest::delete()
+			.uri(&format!("/v1/group/{}/invites/{}", group.id, invitees[0].id))
+			.cookie(common::session_cookie(bystander.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+
+		for (canceller, invitee) in [(&inviter, &invitees[0]), (&owner, &invitees[1])] {
+			let request = TestRequest::delete()
+				.uri(&format!("/v1/group/{}/invites/{}", group.id, invitee.id))
+				.cookie(common::session_cookie(canceller.id))
+				.to_request();
+			assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+			let request = TestRequest::get()
+				.uri(&format!("/v1/user/{}/inbox", invitee.id))
+				.cookie(common::session_cookie(invitee.id))
+				.to_request();
+			let inbox: Value = call_and_read_body_json(&app, request).await;
+			assert_eq!(inbox["total"], 0);
+		}
+
+		while outbox::deliver_outbox().await.unwrap() > 0 {}
+		let events = common::mellow().events();
+		assert!(events.iter().any(|x| x.model == ModelKind::GroupMembership(group.id, invitees[1].id) && x.kind == ModelEventKind::Deleted));
+	});
+}
+
+#[test]
+fn expired_invites_can_be_replaced() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let invitee = fixtures::user().await;
+
+		let invite = || TestRequest::post()
+			.uri(&format!("/v1/group/{}/memberships", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "user_ids": [invitee.id] }))
+			.to_request();
+		assert_eq!(call_service(&app, invite()).await.status(), StatusCode::OK);
+
+		sqlx::query("UPDATE team_members SET invite_expires_at = now() - interval '1 day' WHERE team_id = $1 AND user_id = $2")
+			.bind(group.id.value)
+			.bind(invitee.id.value)
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/membership/accept", group.id))
+			.cookie(common::session_cookie(invitee.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::GONE);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/memberships?filter=pending", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["total"], 0);
+
+		assert_eq!(call_service(&app, invite()).await.status(), StatusCode::OK);
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/membership/accept", group.id))
+			.cookie(common::session_cookie(invitee.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+	});
+}
+
+#[test]
+fn roles_grant_permissions() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+
+		let create_role = |user_id, name: &str| TestRequest::post()
+			.uri(&format!("/v1/group/{}/roles", group.id))
+			.cookie(common::session_cookie(user_id))
+			.set_json(json!({ "name": name, "permissions": GroupPermissions::MANAGE_MEMBERS.bits() }))
+			.to_request();
+		assert_eq!(call_service(&app, create_role(member.id, "Moderator")).await.status(), StatusCode::FORBIDDEN);
+
+		let role: Value = call_and_read_body_json(&app, create_role(owner.id, "Moderator")).await;
+		assert_eq!(role["permissions"], GroupPermissions::MANAGE_MEMBERS.bits());
+
+		let response = call_service(&app, create_role(owner.id, "moderator")).await;
+		assert_eq!(response.status(), StatusCode::CONFLICT);
+		let body: Value = read_body_json(response).await;
+		assert_eq!(body["error"]["kind"], "group_role_name_taken");
+
+		let invite = || async {
+			let request = TestRequest::post()
+				.uri(&format!("/v1/group/{}/memberships", group.id))
+				.cookie(common::session_cookie(member.id))
+				.set_json(json!({ "user_ids": [fixtures::user().await.id] }))
+				.to_request();
+			call_service(&app, request).await.status()
+		};
+		assert_eq!(invite().await, StatusCode::FORBIDDEN);
+
+		let request = TestRequest::put()
+			.uri(&format!("/v1/group/{}/memberships/{}/roles/{}", group.id, member.id, role["id"].as_str().unwrap()))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/membership", group.id))
+			.cookie(common::session_cookie(member.id))
+			.to_request();
+		let membership: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(membership["role_ids"], json!([role["id"]]));
+
+		assert_eq!(invite().await, StatusCode::OK);
+
+		let request = TestRequest::delete()
+			.uri(&format!("/v1/group/{}/roles/{}", group.id, role["id"].as_str().unwrap()))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		assert_eq!(invite().await, StatusCode::FORBIDDEN);
+
+		while outbox::deliver_outbox().await.unwrap() > 0 {}
+		let events = common::mellow().events();
+		assert!(events.iter().any(|x| x.model == ModelKind::GroupMembership(group.id, member.id) && x.kind == ModelEventKind::Updated));
+	});
+}
+
+#[test]
+fn roles_cant_be_assigned_beyond_own_permissions() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let manager = fixtures::user().await;
+		fixtures::group_member(group.id, manager.id, false).await;
+		fixtures::group_role(group.id, manager.id, GroupPermissions::MANAGE_MEMBERS).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+
+		let cafe_role = fixtures::group_role(group.id, owner.id, GroupPermissions::MANAGE_CAFE).await;
+		let members_role = fixtures::group_role(group.id, owner.id, GroupPermissions::MANAGE_MEMBERS).await;
+		for (role_id, status) in [(cafe_role, StatusCode::FORBIDDEN), (members_role, StatusCode::OK)] {
+			let request = TestRequest::put()
+				.uri(&format!("/v1/group/{}/memberships/{}/roles/{role_id}", group.id, member.id))
+				.cookie(common::session_cookie(manager.id))
+				.to_request();
+			assert_eq!(call_service(&app, request).await.status(), status);
+		}
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/roles", group.id))
+			.to_request();
+		let roles: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(roles["total"], 3);
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/roles", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "name": "everything", "permissions": u64::MAX }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+	});
+}
+
+#[test]
+fn private_groups_are_hidden_from_outsiders() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let stranger = fixtures::user().await;
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/group/{}", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "visibility": "private" }))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["visibility"], "private");
+
+		for (user_id, status) in [(stranger.id, StatusCode::NOT_FOUND), (owner.id, StatusCode::OK)] {
+			let request = TestRequest::get()
+				.uri(&format!("/v1/group/{}/memberships", group.id))
+				.cookie(common::session_cookie(user_id))
+				.to_request();
+			assert_eq!(call_service(&app, request).await.status(), status);
+		}
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}", group.name))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+
+		// it only shows up on the owner's profile for the owner
+		for (user_id, total) in [(stranger.id, 0), (owner.id, 1)] {
+			let request = TestRequest::get()
+				.uri(&format!("/v1/user/{}/groups", owner.id))
+				.cookie(common::session_cookie(user_id))
+				.to_request();
+			let response: Value = call_and_read_body_json(&app, request).await;
+			assert_eq!(response["total"], total);
+		}
+	});
+}
+
+#[test]
+fn join_requests_are_approved_or_rejected() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let requesters = [fixtures::user().await, fixtures::user().await];
+
+		for requester in &requesters {
+			let request = TestRequest::post()
+				.uri(&format!("/v1/group/{}/membership/request", group.id))
+				.cookie(common::session_cookie(requester.id))
+				.to_request();
+			assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		}
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/membership/request", group.id))
+			.cookie(common::session_cookie(requesters[0].id))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+		let body: Value = read_body_json(response).await;
+		assert_eq!(body["error"]["kind"], "user_already_pending_in_group");
+
+		// requests aren't invites, so they can't be accepted by whoever made them
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/membership/accept", group.id))
+			.cookie(common::session_cookie(requesters[0].id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/memberships?filter=requested", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["total"], 2);
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/requests/{}/approve", group.id, requesters[0].id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		let request = TestRequest::delete()
+			.uri(&format!("/v1/group/{}/requests/{}", group.id, requesters[1].id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		for (requester, status) in [(&requesters[0], StatusCode::OK), (&requesters[1], StatusCode::NOT_FOUND)] {
+			let request = TestRequest::get()
+				.uri(&format!("/v1/group/{}/membership", group.id))
+				.cookie(common::session_cookie(requester.id))
+				.to_request();
+			assert_eq!(call_service(&app, request).await.status(), status);
+		}
+
+		// unlisted groups are invite only
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/group/{}", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "visibility": "unlisted" }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/membership/request", group.id))
+			.cookie(common::session_cookie(requesters[1].id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+	});
+}
+
+#[test]
+fn leave_group() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+
+		let leave = |user_id| TestRequest::delete()
+			.uri(&format!("/v1/group/{}/membership", group.id))
+			.cookie(common::session_cookie(user_id))
+			.to_request();
+		assert_eq!(call_service(&app, leave(member.id)).await.status(), StatusCode::OK);
+		assert_eq!(call_service(&app, leave(member.id)).await.status(), StatusCode::NOT_FOUND);
+
+		let response = call_service(&app, leave(owner.id)).await;
+		assert_eq!(response.status(), StatusCode::CONFLICT);
+		let body: Value = read_body_json(response).await;
+		assert_eq!(body["error"]["kind"], "last_group_owner");
+
+		while outbox::deliver_outbox().await.unwrap() > 0 {}
+		let events = common::mellow().events();
+		assert!(events.iter().any(|x| x.model == ModelKind::GroupMembership(group.id, member.id) && x.kind == ModelEventKind::Deleted));
+	});
+}
+
+async fn audit_actions(group_id: Id<GroupMarker>) -> Vec<String> {
+	sqlx::query_scalar("SELECT action FROM audit_log_entries WHERE group_id = $1 ORDER BY id")
+		.bind(group_id.value)
+		.fetch_all(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap()
+}
+
+#[test]
+fn remove_group_members() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let server_id = fixtures::mellow_server(Some(group.id), None).await;
+		let manager = fixtures::user().await;
+		fixtures::group_member(group.id, manager.id, false).await;
+		fixtures::group_role(group.id, manager.id, GroupPermissions::MANAGE_MEMBERS).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+		let server_manager = fixtures::user().await;
+		fixtures::group_member(group.id, server_manager.id, false).await;
+		fixtures::group_role(group.id, server_manager.id, GroupPermissions::MANAGE_MELLOW_SERVERS).await;
+
+		let remove = |actor_id, user_id| TestRequest::delete()
+			.uri(&format!("/v1/group/{}/memberships/{user_id}", group.id))
+			.cookie(common::session_cookie(actor_id))
+			.to_request();
+
+		// managers can't remove owners, or anyone who can do things they can't
+		assert_eq!(call_service(&app, remove(member.id, manager.id)).await.status(), StatusCode::FORBIDDEN);
+		assert_eq!(call_service(&app, remove(manager.id, owner.id)).await.status(), StatusCode::FORBIDDEN);
+		assert_eq!(call_service(&app, remove(manager.id, server_manager.id)).await.status(), StatusCode::FORBIDDEN);
+
+		assert_eq!(call_service(&app, remove(manager.id, member.id)).await.status(), StatusCode::OK);
+		assert_eq!(call_service(&app, remove(owner.id, server_manager.id)).await.status(), StatusCode::OK);
+		assert_eq!(call_service(&app, remove(owner.id, member.id)).await.status(), StatusCode::NOT_FOUND);
+
+		assert_eq!(audit_actions(group.id).await, ["group_member_removed", "group_member_removed"]);
+
+		while outbox::deliver_outbox().await.unwrap() > 0 {}
+		let events = common::mellow().events();
+		assert!(events.iter().any(|x| x.model == ModelKind::GroupMembership(group.id, member.id) && x.kind == ModelEventKind::Deleted));
+		assert!(events.iter().any(|x| x.model == ModelKind::Server(server_id) && x.kind == ModelEventKind::Updated));
+	});
+}
+
+#[test]
+fn transfer_group_ownership() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let server_id = fixtures::mellow_server(Some(group.id), None).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+
+		let transfer = |actor_id, user_id| TestRequest::post()
+			.uri(&format!("/v1/group/{}/ownership/transfer", group.id))
+			.cookie(common::session_cookie(actor_id))
+			.set_json(json!({ "user_id": user_id }))
+			.to_request();
+		assert_eq!(call_service(&app, transfer(member.id, member.id)).await.status(), StatusCode::BAD_REQUEST);
+		assert_eq!(call_service(&app, transfer(member.id, owner.id)).await.status(), StatusCode::FORBIDDEN);
+		assert_eq!(call_service(&app, transfer(owner.id, member.id)).await.status(), StatusCode::OK);
+
+		for (user_id, is_owner) in [(owner.id, false), (member.id, true)] {
+			let request = TestRequest::get()
+				.uri(&format!("/v1/group/{}/membership", group.id))
+				.cookie(common::session_cookie(user_id))
+				.to_request();
+			let membership: Value = call_and_read_body_json(&app, request).await;
+			assert_eq!(membership["is_owner"], is_owner);
+		}
+		assert_eq!(call_service(&app, transfer(owner.id, member.id)).await.status(), StatusCode::FORBIDDEN);
+
+		assert_eq!(audit_actions(group.id).await, ["group_ownership_transferred"]);
+
+		while outbox::deliver_outbox().await.unwrap() > 0 {}
+		let events = common::mellow().events();
+		assert!(events.iter().any(|x| x.model == ModelKind::Server(server_id) && x.kind == ModelEventKind::Updated));
+	});
+}
+
+#[test]
+fn co_owners_and_orphaned_groups() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let co_owner = fixtures::user().await;
+		fixtures::group_member(group.id, co_owner.id, false).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+
+		let request = TestRequest::put()
+			.uri(&format!("/v1/group/{}/owners/{}", group.id, co_owner.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		// the co-owner steps down, after which the owner is the last one left
+		let step_down = |user_id| TestRequest::delete()
+			.uri(&format!("/v1/group/{}/owners/{user_id}", group.id))
+			.cookie(common::session_cookie(user_id))
+			.to_request();
+		assert_eq!(call_service(&app, step_down(co_owner.id)).await.status(), StatusCode::OK);
+		assert_eq!(call_service(&app, step_down(owner.id)).await.status(), StatusCode::CONFLICT);
+
+		assert_eq!(audit_actions(group.id).await, ["group_owner_added", "group_owner_removed"]);
+
+		// when the owner's account goes, the longest standing member takes over
+		sqlx::query("DELETE FROM users WHERE id = $1")
+			.bind(owner.id.value)
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		let request = TestRequest::get()
+			.uri(&format!("/v1/group/{}/membership", group.id))
+			.cookie(common::session_cookie(co_owner.id))
+			.to_request();
+		let membership: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(membership["is_owner"], true);
+	});
+}
+
+#[test]
+fn owners_stepping_down_together_leave_one_behind() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let co_owner = fixtures::user().await;
+		fixtures::group_member(group.id, co_owner.id, false).await;
+		let request = TestRequest::put()
+			.uri(&format!("/v1/group/{}/owners/{}", group.id, co_owner.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		// one leaves while the other steps down, only one of them can get away with it
+		let leave = TestRequest::delete()
+			.uri(&format!("/v1/group/{}/membership", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let step_down = TestRequest::delete()
+			.uri(&format!("/v1/group/{}/owners/{}", group.id, co_owner.id))
+			.cookie(common::session_cookie(co_owner.id))
+			.to_request();
+		let (left, stepped_down) = futures::join!(call_service(&app, leave), call_service(&app, step_down));
+		let mut statuses = [left.status(), stepped_down.status()];
+		statuses.sort();
+		assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+
+		let owners: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM team_members WHERE team_id = $1 AND is_owner")
+			.bind(group.id.value)
+			.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		assert_eq!(owners, 1);
+	});
+}
+
+#[test]
+fn concurrent_invites_only_invite_once() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let invitee = fixtures::user().await;
+
+		let invite = || TestRequest::post()
+			.uri(&format!("/v1/group/{}/memberships", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "user_ids": [invitee.id] }))
+			.to_request();
+		let (first, second) = futures::join!(call_service(&app, invite()), call_service(&app, invite()));
+		let mut statuses = [first.status(), second.status()];
+		statuses.sort();
+		assert_eq!(statuses, [StatusCode::OK, StatusCode::BAD_REQUEST]);
+
+		let inbox_items: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_inbox_items WHERE user_id = $1")
+			.bind(invitee.id.value)
+			.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		assert_eq!(inbox_items, 1);
+	});
+}
\ No newline at end of file
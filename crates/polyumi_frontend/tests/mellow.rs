@@ -0,0 +1,96 @@
+use actix_web::{
+	http::StatusCode,
+	test::{ call_service, read_body_json, TestRequest }
+};
+use polyumi_models::hakumi::group::GroupPermissions;
+use polyumi_util::PG_POOL;
+use serde_json::{ json, Value };
+use std::pin::Pin;
+
+mod common;
+use common::fixtures;
+
+#[test]
+fn update_syncing_settings_as_owner() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let server_id = fixtures::mellow_server(None, Some(owner.id)).await;
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/mellow/server/{server_id}/syncing/settings"))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "default_nickname": "{roblox_username}" }))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::OK);
+
+		let (default_nickname,): (Option<String>,) = sqlx::query_as("SELECT default_nickname FROM mellow_servers WHERE id = $1")
+			.bind(server_id.get() as i64)
+			.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+		assert_eq!(default_nickname.as_deref(), Some("{roblox_username}"));
+	});
+}
+
+#[test]
+fn update_syncing_settings_as_group_member() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+		let server_id = fixtures::mellow_server(Some(group.id), None).await;
+
+		// being in the group isn't enough without a role that manages its servers
+		let update = || TestRequest::patch()
+			.uri(&format!("/v1/mellow/server/{server_id}/syncing/settings"))
+			.cookie(common::session_cookie(member.id))
+			.set_json(json!({ "allow_forced_syncing": true }))
+			.to_request();
+		assert_eq!(call_service(&app, update()).await.status(), StatusCode::FORBIDDEN);
+
+		fixtures::group_role(group.id, member.id, GroupPermissions::MANAGE_MELLOW_SERVERS).await;
+		assert_eq!(call_service(&app, update()).await.status(), StatusCode::OK);
+	});
+}
+
+#[test]
+fn update_syncing_settings_without_permission() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let stranger = fixtures::user().await;
+		let server_id = fixtures::mellow_server(None, Some(owner.id)).await;
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/mellow/server/{server_id}/syncing/settings"))
+			.cookie(common::session_cookie(stranger.id))
+			.set_json(json!({ "allow_forced_syncing": true }))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::FORBIDDEN);
+	});
+}
+
+#[test]
+fn update_syncing_settings_for_unknown_server() {
+	common::run(async {
+		let app = common::app().await;
+		let user = fixtures::user().await;
+
+		let request = TestRequest::patch()
+			.uri("/v1/mellow/server/1/syncing/settings")
+			.cookie(common::session_cookie(user.id))
+			.set_json(json!({ "allow_forced_syncing": true }))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+		let body: Value = read_body_json(response).await;
+		assert_eq!(body["error"]["resource_kind"], "mellow_server");
+		assert_eq!(body["error"]["resource_reference"], "1");
+	});
+}
\ No newline at end of file
@@ -0,0 +1,148 @@
+use actix_web::{
+	http::StatusCode,
+	test::{ call_service, TestRequest }
+};
+use chrono::Utc;
+use polyumi_models::{
+	mellow::model_event::{
+		envelope::{ self, SIGNATURE_TOLERANCE_SECS },
+		outbox::{ self, MAX_ATTEMPTS },
+		stream,
+		ModelEventKind, ModelKind, MODEL_EVENT_VERSION
+	},
+	Error
+};
+use polyumi_util::PG_POOL;
+use serde_json::json;
+use std::pin::Pin;
+use twilight_model::id::{
+	marker::GuildMarker,
+	Id as DiscordId
+};
+
+mod common;
+use common::fixtures;
+
+#[test]
+fn model_events_are_delivered_after_commit() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let server_id = fixtures::mellow_server(None, Some(owner.id)).await;
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/mellow/server/{server_id}/syncing/settings"))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "allow_forced_syncing": true }))
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::OK);
+
+		while outbox::deliver_outbox().await.unwrap() > 0 {}
+
+		let event = common::mellow()
+			.events()
+			.into_iter()
+			.find(|x| x.model == ModelKind::Server(server_id))
+			.expect("event was not delivered");
+		assert_eq!(event.version, MODEL_EVENT_VERSION);
+		assert_eq!(event.kind, ModelEventKind::Updated);
+		assert_eq!(event.actionee_id, Some(owner.id));
+	});
+}
+
+#[test]
+fn failed_model_events_are_dead_lettered() {
+	common::run(async {
+		let pool = Pin::static_ref(&PG_POOL).await;
+		let (id,): (i64,) = sqlx::query_as("INSERT INTO model_event_outbox (endpoint, body, attempts) VALUES ('http://127.0.0.1:9/internal/model_event', '{}', $1) RETURNING id")
+			.bind(MAX_ATTEMPTS - 1)
+			.fetch_one(pool.get_ref())
+			.await
+			.unwrap();
+
+		outbox::deliver_outbox().await.unwrap();
+
+		let (attempts, last_error): (i32, Option<String>) = sqlx::query_as("SELECT attempts, last_error FROM model_event_dead_letters WHERE id = $1")
+			.bind(id)
+			.fetch_one(pool.get_ref())
+			.await
+			.unwrap();
+		assert_eq!(attempts, MAX_ATTEMPTS);
+		assert!(last_error.is_some());
+	});
+}
+
+#[test]
+fn unsigned_model_events_are_rejected() {
+	common::run(async {
+		let address = std::env::var("MODEL_EVENT_ENDPOINTS").unwrap();
+		let result = outbox::send(&address, b"{}".to_vec(), "not a signature").await;
+		assert!(result.is_err());
+	});
+}
+
+#[test]
+fn signatures_cover_the_timestamp() {
+	let body = serde_json::to_vec(&json!({
+		"version": MODEL_EVENT_VERSION,
+		"id": "00000000-0000-0000-0000-000000000000",
+		"sequence": 1,
+		"occurred_at": Utc::now(),
+		"actionee_id": null,
+		"kind": "Deleted",
+		"model": { "Server": "1" }
+	})).unwrap();
+	let now = Utc::now().timestamp();
+
+	let signature = envelope::sign(b"secret", now, &body).unwrap();
+	assert!(envelope::verify(b"secret", &signature, &body).is_ok());
+	assert!(matches!(envelope::verify(b"not the secret", &signature, &body), Err(Error::InvalidSignature)));
+
+	let moved_timestamp = signature.replace(&format!("t={now}"), &format!("t={}", now + 1));
+	assert!(matches!(envelope::verify(b"secret", &moved_timestamp, &body), Err(Error::InvalidSignature)));
+
+	let stale_signature = envelope::sign(b"secret", now - SIGNATURE_TOLERANCE_SECS - 1, &body).unwrap();
+	assert!(matches!(envelope::verify(b"secret", &stale_signature, &body), Err(Error::StaleSignature)));
+}
+
+async fn published_sequence(server_id: DiscordId<GuildMarker>) -> i64 {
+	let (sequence, body_sequence): (Option<i64>, Option<i64>) = sqlx::query_as("SELECT sequence, (body->>'sequence')::int8 FROM model_events WHERE body->'model' = $1")
+		.bind(json!({ "Server": server_id }))
+		.fetch_one(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await
+		.unwrap();
+	assert_eq!(sequence, body_sequence);
+	sequence.expect("event wasn't published")
+}
+
+#[test]
+fn sequences_follow_commit_order() {
+	common::run(async {
+		let pool = Pin::static_ref(&PG_POOL).await;
+		let first_server_id = fixtures::mellow_server(None, None).await;
+		let second_server_id = fixtures::mellow_server(None, None).await;
+
+		// the first event is enqueued first but committed last, so it has to come after the second
+		let mut first = pool.begin().await.unwrap();
+		ModelEventKind::Updated
+			.build(ModelKind::Server(first_server_id))
+			.system()
+			.enqueue(&mut first)
+			.await
+			.unwrap();
+		let mut second = pool.begin().await.unwrap();
+		ModelEventKind::Updated
+			.build(ModelKind::Server(second_server_id))
+			.system()
+			.enqueue(&mut second)
+			.await
+			.unwrap();
+		second.commit().await.unwrap();
+		stream::publish().await.unwrap();
+		first.commit().await.unwrap();
+		stream::publish().await.unwrap();
+
+		assert!(published_sequence(first_server_id).await > published_sequence(second_server_id).await);
+	});
+}
\ No newline at end of file
@@ -0,0 +1,131 @@
+use actix_web::{
+	http::StatusCode,
+	test::{ call_and_read_body_json, call_service, read_body_json, TestRequest }
+};
+use polyumi_util::PG_POOL;
+use serde_json::Value;
+use std::pin::Pin;
+
+mod common;
+use common::fixtures;
+
+#[test]
+fn get_user_by_id_and_username() {
+	common::run(async {
+		let app = common::app().await;
+		let user = fixtures::user().await;
+
+		for user_ref in [user.id.to_string(), user.username.to_uppercase()] {
+			let request = TestRequest::get()
+				.uri(&format!("/v1/user/{user_ref}"))
+				.to_request();
+			let response: Value = call_and_read_body_json(&app, request).await;
+			assert_eq!(response["id"], user.id.to_string());
+			assert_eq!(response["username"], user.username);
+		}
+	});
+}
+
+#[test]
+fn get_unknown_user() {
+	common::run(async {
+		let app = common::app().await;
+		let request = TestRequest::get()
+			.uri("/v1/user/nobody_at_all")
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+		let body: Value = read_body_json(response).await;
+		assert_eq!(body["error"]["kind"], "not_found");
+		assert_eq!(body["error"]["resource_kind"], "user");
+	});
+}
+
+#[test]
+fn get_user_groups() {
+	common::run(async {
+		let app = common::app().await;
+		let user = fixtures::user().await;
+		let group = fixtures::group(user.id).await;
+
+		let pending_group = fixtures::group(fixtures::user().await.id).await;
+		fixtures::group_member(pending_group.id, user.id, true).await;
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/user/{}/groups", user.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["total"], 1);
+		assert_eq!(response["items"][0]["id"], group.id.to_string());
+	});
+}
+
+#[test]
+fn get_user_groups_pages_by_name() {
+	common::run(async {
+		let app = common::app().await;
+		let user = fixtures::user().await;
+		let mut group_ids = Vec::new();
+		for _ in 0..3 {
+			let group = fixtures::group(user.id).await;
+			group_ids.push((group.name.to_lowercase(), group.id.to_string()));
+		}
+		group_ids.sort();
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/user/{}/groups?limit=2", user.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["total"], 3);
+		assert_eq!(response["items"].as_array().unwrap().len(), 2);
+		assert_eq!(response["items"][0]["id"], group_ids[0].1);
+
+		// the cursor remembers where it was, so renaming the group it ended on doesn't move it
+		sqlx::query("UPDATE teams SET name = $1 WHERE id = $2::uuid")
+			.bind(fixtures::random_name("a"))
+			.bind(&group_ids[1].1)
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/user/{}/groups?limit=2&cursor={}", user.id, response["next_cursor"].as_str().unwrap()))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["items"].as_array().unwrap().len(), 1);
+		assert_eq!(response["items"][0]["id"], group_ids[2].1);
+		assert_eq!(response["next_cursor"], Value::Null);
+	});
+}
+
+#[test]
+fn list_routes_reject_invalid_queries() {
+	common::run(async {
+		let app = common::app().await;
+		let user = fixtures::user().await;
+
+		for query in ["limit=0", "limit=1000", "limit=many", "cursor=%%%", "direction=sideways"] {
+			let request = TestRequest::get()
+				.uri(&format!("/v1/user/{}/groups?{query}", user.id))
+				.to_request();
+			let response = call_service(&app, request).await;
+			assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{query}");
+
+			let body: Value = read_body_json(response).await;
+			assert_eq!(body["error"]["kind"], "invalid_query");
+		}
+	});
+}
+
+#[test]
+fn unknown_route() {
+	common::run(async {
+		let app = common::app().await;
+		let request = TestRequest::get()
+			.uri("/v1/this_does_not_exist/at_all")
+			.to_request();
+		let response = call_service(&app, request).await;
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	});
+}
\ No newline at end of file
@@ -0,0 +1,207 @@
+use actix_web::{
+	http::StatusCode,
+	test::{ call_and_read_body_json, call_service, TestRequest }
+};
+use polyumi_mellow_stand_in::StandIn;
+use polyumi_models::mellow::model_event::{
+	outbox,
+	ModelKind
+};
+use polyumi_util::PG_POOL;
+use serde_json::{ json, Value };
+use std::pin::Pin;
+
+mod common;
+use common::fixtures;
+
+async fn deliver_all() {
+	while outbox::deliver_outbox().await.unwrap() > 0 {}
+}
+
+#[test]
+fn group_webhook_receives_membership_events() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/webhooks", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "url": "http://127.0.0.1:9/", "events": ["group_membership.created"] }))
+			.to_request();
+		let webhook: Value = call_and_read_body_json(&app, request).await;
+		let webhook_id = webhook["id"].as_str().unwrap();
+
+		// the secret is only known after creating the webhook, so point it at the receiver afterwards
+		let receiver = StandIn::new(webhook["secret"].as_str().unwrap());
+		let (server, address) = receiver
+			.server("127.0.0.1:0")
+			.unwrap();
+		tokio::spawn(server);
+
+		let request = TestRequest::patch()
+			.uri(&format!("/v1/webhook/{webhook_id}"))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "url": format!("http://{address}/internal/model_event") }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+		let invitee = fixtures::user().await;
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/memberships", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "user_ids": [invitee.id] }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		deliver_all().await;
+
+		let events = receiver.events();
+		assert_eq!(events.len(), 1);
+		assert_eq!(events[0].model, ModelKind::GroupMembership(group.id, invitee.id));
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/webhook/{webhook_id}/deliveries"))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		let deliveries = &response["items"];
+		assert_eq!(deliveries.as_array().unwrap().len(), 1);
+		assert_eq!(deliveries[0]["status_code"], 200);
+		assert_eq!(deliveries[0]["event"]["id"], events[0].id.to_string());
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/webhook/{webhook_id}/deliveries/{}/redeliver", deliveries[0]["id"]))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		deliver_all().await;
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/webhook/{webhook_id}/deliveries"))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		let deliveries = &response["items"];
+		assert_eq!(response["total"], 2);
+		assert_eq!(deliveries[0]["is_redelivery"], true);
+		assert_eq!(deliveries[0]["event"]["id"], events[0].id.to_string());
+	});
+}
+
+#[test]
+fn webhook_event_filter() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/webhooks", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "url": "http://127.0.0.1:9/", "events": ["server.updated"] }))
+			.to_request();
+		let webhook: Value = call_and_read_body_json(&app, request).await;
+
+		let invitee = fixtures::user().await;
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/memberships", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "user_ids": [invitee.id] }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		deliver_all().await;
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/webhook/{}/deliveries", webhook["id"].as_str().unwrap()))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert!(response["items"].as_array().unwrap().is_empty());
+		assert_eq!(response["total"], 0);
+	});
+}
+
+#[test]
+fn create_webhook_validation_and_permissions() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+		let member = fixtures::user().await;
+		fixtures::group_member(group.id, member.id, false).await;
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/webhooks", group.id))
+			.cookie(common::session_cookie(member.id))
+			.set_json(json!({ "url": "https://example.com/" }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+
+		for payload in [
+			json!({ "url": "ftp://example.com/" }),
+			json!({ "url": "https://example.com/", "events": ["group_membership.exploded"] })
+		] {
+			let request = TestRequest::post()
+				.uri(&format!("/v1/group/{}/webhooks", group.id))
+				.cookie(common::session_cookie(owner.id))
+				.set_json(payload)
+				.to_request();
+			assert_eq!(call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+		}
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/user/{}/webhooks", owner.id))
+			.cookie(common::session_cookie(member.id))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+	});
+}
+
+#[test]
+fn webhooks_only_reach_public_addresses() {
+	common::run(async {
+		let app = common::app().await;
+		let owner = fixtures::user().await;
+		let group = fixtures::group(owner.id).await;
+
+		let create = |url: &str| TestRequest::post()
+			.uri(&format!("/v1/user/{}/webhooks", owner.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "url": url }))
+			.to_request();
+		for url in ["http://169.254.169.254/latest/meta-data/", "http://10.0.0.1/", "http://[::1]:8080/", "http://0.0.0.0/"] {
+			assert_eq!(call_service(&app, create(url)).await.status(), StatusCode::BAD_REQUEST, "{url}");
+		}
+
+		// the name could start resolving somewhere private after the webhook was made, so delivery checks again
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/webhooks", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "url": "http://127.0.0.1:9/" }))
+			.to_request();
+		let webhook: Value = call_and_read_body_json(&app, request).await;
+		let webhook_id = webhook["id"].as_str().unwrap();
+		sqlx::query("UPDATE webhooks SET url = 'http://10.0.0.1/' WHERE id = $1::uuid")
+			.bind(webhook_id)
+			.execute(Pin::static_ref(&PG_POOL).await.get_ref())
+			.await
+			.unwrap();
+
+		let request = TestRequest::post()
+			.uri(&format!("/v1/group/{}/memberships", group.id))
+			.cookie(common::session_cookie(owner.id))
+			.set_json(json!({ "user_ids": [fixtures::user().await.id] }))
+			.to_request();
+		assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+		deliver_all().await;
+
+		let request = TestRequest::get()
+			.uri(&format!("/v1/webhook/{webhook_id}/deliveries"))
+			.cookie(common::session_cookie(owner.id))
+			.to_request();
+		let response: Value = call_and_read_body_json(&app, request).await;
+		assert_eq!(response["items"][0]["status_code"], Value::Null);
+		assert_eq!(response["items"][0]["error"], "Forbidden Address");
+	});
+}
\ No newline at end of file
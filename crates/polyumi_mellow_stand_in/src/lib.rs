@@ -0,0 +1,91 @@
+use actix_web::{
+	dev::Server,
+	post, web,
+	App, HttpRequest, HttpResponse, HttpServer
+};
+use log::{ info, warn };
+use polyumi_models::mellow::model_event::{
+	envelope::{ verify, SIGNATURE_HEADER },
+	ModelEventEnvelope
+};
+use std::{
+	net::{ SocketAddr, ToSocketAddrs },
+	sync::{ Arc, Mutex }
+};
+
+/// Accepts model events the same way mellow does, verifying them with the shared secret,
+/// and keeps everything it received in memory so it can be inspected.
+#[derive(Clone)]
+pub struct StandIn {
+	secret: Arc<String>,
+	events: Arc<Mutex<Vec<ModelEventEnvelope>>>
+}
+
+impl StandIn {
+	pub fn new(secret: impl Into<String>) -> Self {
+		Self {
+			secret: Arc::new(secret.into()),
+			events: Default::default()
+		}
+	}
+
+	pub fn events(&self) -> Vec<ModelEventEnvelope> {
+		self.events
+			.lock()
+			.unwrap()
+			.clone()
+	}
+
+	/// Forgets every event received so far.
+	pub fn clear(&self) {
+		self.events
+			.lock()
+			.unwrap()
+			.clear();
+	}
+
+	pub fn config(&self, config: &mut web::ServiceConfig) {
+		config
+			.app_data(web::Data::new(self.clone()))
+			.service(model_event);
+	}
+
+	pub fn server(&self, address: impl ToSocketAddrs) -> std::io::Result<(Server, SocketAddr)> {
+		let stand_in = self.clone();
+		let server = HttpServer::new(move || App::new().configure(|config| stand_in.config(config)))
+			.workers(1)
+			.bind(address)?;
+		let address = server.addrs()[0];
+
+		Ok((server.run(), address))
+	}
+}
+
+#[post("internal/model_event")]
+async fn model_event(request: HttpRequest, stand_in: web::Data<StandIn>, body: web::Bytes) -> HttpResponse {
+	let signature = request
+		.headers()
+		.get(SIGNATURE_HEADER)
+		.and_then(|x| x.to_str().ok())
+		.unwrap_or_default();
+	let envelope = match verify(stand_in.secret.as_bytes(), signature, &body) {
+		Ok(x) => x,
+		Err(error) => {
+			warn!("rejected model event: {error}");
+			return HttpResponse::Unauthorized().finish();
+		}
+	};
+
+	let mut events = stand_in.events
+		.lock()
+		.unwrap();
+	// redeliveries keep their id, so anything we've seen already is acknowledged and dropped
+	if events.iter().any(|x| x.id == envelope.id) {
+		info!("ignoring duplicate model event {}", envelope.id);
+	} else {
+		info!("received model event #{}: {:?} {:?}", envelope.sequence, envelope.kind, envelope.model);
+		events.push(envelope);
+	}
+
+	HttpResponse::Ok().finish()
+}
\ No newline at end of file
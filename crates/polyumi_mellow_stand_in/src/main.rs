@@ -0,0 +1,18 @@
+use env_logger::Env;
+use log::info;
+use polyumi_mellow_stand_in::StandIn;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> std::io::Result<()> {
+	env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+
+	let bind_addr = std::env::var("BIND_ADDRESS")
+		.unwrap_or_else(|_| "127.0.0.1:8081".into());
+	let secret = std::env::var("ABSOLUTESOLVER")
+		.expect("ABSOLUTESOLVER is not defined");
+
+	let (server, address) = StandIn::new(secret).server(bind_addr)?;
+	info!("starting mellow stand-in on {address}, point MODEL_EVENT_ENDPOINTS at http://{address}/internal/model_event");
+
+	server.await
+}
\ No newline at end of file
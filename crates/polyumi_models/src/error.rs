@@ -9,7 +9,10 @@ pub enum Error {
 	#[error("ECDSA Error: {0}")]
 	EcdsaError(#[from] p384::ecdsa::Error),
 
-	#[error("Reqwest")]
+	#[error("IO Error: {0}")]
+	Io(#[from] std::io::Error),
+
+	#[error("Reqwest Error: {0}")]
 	Reqwest(#[from] reqwest::Error),
 
 	#[error("Serde JSON")]
@@ -25,7 +28,22 @@ pub enum Error {
 	Sqlx(#[from] sqlx::Error),
 
 	#[error("Missing Signatuer")]
-	MissingSignature
+	MissingSignature,
+
+	#[error("Invalid Signature")]
+	InvalidSignature,
+
+	#[error("Stale Signature")]
+	StaleSignature,
+
+	#[error("Forbidden Address")]
+	ForbiddenAddress,
+
+	#[error("Unexpected Status: {0}")]
+	UnexpectedStatus(u16),
+
+	#[error("Unsupported Model Event Version: {0}")]
+	UnsupportedModelEventVersion(u16)
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
\ No newline at end of file
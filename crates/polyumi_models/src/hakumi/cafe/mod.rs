@@ -1,5 +1,5 @@
 use std::pin::Pin;
-use serde::Serialize;
+use serde::{ Deserialize, Serialize };
 use chrono::{ Utc, DateTime };
 use futures::TryStreamExt;
 use polyumi_util::{
@@ -7,9 +7,13 @@ use polyumi_util::{
 	PG_POOL
 };
 
-use crate::Result;
+use crate::{
+	hakumi::GroupModel,
+	Result
+};
 
 pub mod order;
+pub mod report;
 pub use order::CafeOrderModel;
 
 #[derive(Serialize)]
@@ -20,10 +24,21 @@ pub struct CafeModel {
 	pub owner_user_id: Option<Id<UserMarker>>,
 
 	pub kind: CafeKind,
+	pub name: Option<String>,
+	pub settings: CafeSettings,
 
 	pub created_at: DateTime<Utc>
 }
 
+/// A cafe as its moderators see it, word filter included.
+#[derive(Serialize)]
+pub struct CafeModeratorView<'a> {
+	#[serde(flatten)]
+	pub cafe: &'a CafeModel,
+	pub word_filter: &'a [String],
+	pub word_filter_action: CafeWordFilterAction
+}
+
 impl CafeModel {
 	pub async fn get(cafe_ref: u64) -> Result<Option<Self>> {
 		Self::get_many(&[cafe_ref])
@@ -44,7 +59,7 @@ impl CafeModel {
 			.collect();
 		Ok(sqlx::query!(
 			"
-			SELECT id, creator_user_id, owner_group_id, owner_user_id, kind, created_at
+			SELECT id, creator_user_id, owner_group_id, owner_user_id, kind, name, post_permission, slow_mode_seconds, locked, word_filter, word_filter_action, created_at
 			FROM cafes
 			WHERE id = ANY($1)
 			",
@@ -58,7 +73,15 @@ impl CafeModel {
 					owner_group_id: u.owner_group_id.map(Into::into),
 					owner_user_id: u.owner_user_id.map(Into::into),
 
-					kind: u.kind.as_str().into(),
+					kind: u.kind.into(),
+					name: u.name,
+					settings: CafeSettings {
+						post_permission: u.post_permission.as_str().into(),
+						slow_mode_seconds: u.slow_mode_seconds as u32,
+						locked: u.locked,
+						word_filter: u.word_filter,
+						word_filter_action: u.word_filter_action.as_str().into()
+					},
 
 					created_at: u.created_at
 				});
@@ -68,20 +91,181 @@ impl CafeModel {
 			.await?
 		)
 	}
+
+	pub fn moderator_view(&self) -> CafeModeratorView<'_> {
+		CafeModeratorView {
+			cafe: self,
+			word_filter: &self.settings.word_filter,
+			word_filter_action: self.settings.word_filter_action
+		}
+	}
+
+	/// Cafes that belong to a group can only be seen by whoever can see the group, see [`GroupModel::is_visible_to`].
+	pub async fn is_visible_to(&self, viewer_id: Option<Id<UserMarker>>) -> Result<bool> {
+		match self.owner_group_id {
+			Some(group_id) => match GroupModel::get(&group_id.to_string()).await? {
+				Some(group) => group.is_visible_to(viewer_id).await,
+				None => Ok(false)
+			},
+			None => Ok(true)
+		}
+	}
+
+	/// The discussion board of a group, if it has one.
+	pub async fn get_group(group_id: Id<GroupMarker>) -> Result<Option<Self>> {
+		let record = sqlx::query!(
+			"
+			SELECT id
+			FROM cafes
+			WHERE owner_group_id = $1 AND kind = 'group'
+			",
+			group_id.value
+		)
+			.fetch_optional(&*Pin::static_ref(&PG_POOL).await)
+			.await?;
+		match record {
+			Some(record) => Self::get(record.id as u64).await,
+			None => Ok(None)
+		}
+	}
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "snake_case")]
+/// What a cafe is for, kinds this version doesn't know about are kept as they are, so they make it back to the database untouched.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(from = "String", into = "String")]
 pub enum CafeKind {
-	Unknown,
-	Profile
+	/// a user's guestbook, one each.
+	Profile,
+	/// a group's discussion board, one each.
+	Group,
+	/// a named cafe about something, owned by a user or a group.
+	Topic,
+	Unknown(String)
+}
+
+impl CafeKind {
+	/// What's stored in the `kind` column.
+	pub fn name(&self) -> &str {
+		match self {
+			Self::Profile => "profile",
+			Self::Group => "group",
+			Self::Topic => "topic",
+			Self::Unknown(kind) => kind
+		}
+	}
 }
 
-impl From<&str> for CafeKind {
+impl From<String> for CafeKind {
+	fn from(value: String) -> Self {
+		match value.as_str() {
+			"profile" => Self::Profile,
+			"group" => Self::Group,
+			"topic" => Self::Topic,
+			_ => Self::Unknown(value)
+		}
+	}
+}
+
+impl From<CafeKind> for String {
+	fn from(value: CafeKind) -> Self {
+		value
+			.name()
+			.to_string()
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CafeSettings {
+	pub post_permission: CafePostPermission,
+	/// how long everyone but moderators has to wait between orders, reactions aside.
+	pub slow_mode_seconds: u32,
+	/// nobody but moderators can place or edit orders.
+	pub locked: bool,
+	/// words and phrases that moderators want to know about, or keep out entirely.
+	/// left out for everyone else, knowing what's filtered is how you get around it, see [`CafeModeratorView`].
+	#[serde(skip_serializing)]
+	pub word_filter: Vec<String>,
+	#[serde(skip_serializing)]
+	pub word_filter_action: CafeWordFilterAction
+}
+
+impl CafeSettings {
+	/// The first entry in the word filter that shows up in `content` as a whole word, ignoring case.
+	pub fn filtered_word(&self, content: &str) -> Option<&str> {
+		let content = content.to_lowercase();
+		self.word_filter
+			.iter()
+			.find(|word| {
+				let word = word.to_lowercase();
+				!word.is_empty() && content
+					.match_indices(&word)
+					.any(|(start, _)| {
+						let before = content[..start].chars().next_back();
+						let after = content[start + word.len()..].chars().next();
+						!before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+					})
+			})
+			.map(String::as_str)
+	}
+}
+
+/// Who can place orders in a cafe, moderators always can.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CafePostPermission {
+	Everyone,
+	/// members of the group that owns the cafe, the same as moderators for cafes owned by a user.
+	Members,
+	Moderators
+}
+
+impl CafePostPermission {
+	/// What's stored in the `post_permission` column.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Everyone => "everyone",
+			Self::Members => "members",
+			Self::Moderators => "moderators"
+		}
+	}
+}
+
+// anything unexpected is treated as strictly as possible
+impl From<&str> for CafePostPermission {
 	fn from(value: &str) -> Self {
 		match value {
-			"profile" => Self::Profile,
-			_ => Self::Unknown
+			"everyone" => Self::Everyone,
+			"members" => Self::Members,
+			_ => Self::Moderators
+		}
+	}
+}
+
+/// What happens to orders that trip the word filter, moderators' own orders are let through either way.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CafeWordFilterAction {
+	/// placed as usual, with a report for moderators to look at.
+	Flag,
+	/// not placed at all.
+	Reject
+}
+
+impl CafeWordFilterAction {
+	/// What's stored in the `word_filter_action` column.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Flag => "flag",
+			Self::Reject => "reject"
+		}
+	}
+}
+
+impl From<&str> for CafeWordFilterAction {
+	fn from(value: &str) -> Self {
+		match value {
+			"reject" => Self::Reject,
+			_ => Self::Flag
 		}
 	}
 }
\ No newline at end of file
@@ -1,34 +1,155 @@
 use std::pin::Pin;
+use log::warn;
 use serde::{ Serialize, Deserialize };
+use serde_json::{ json, Value };
 use chrono::{ Utc, DateTime };
 use futures::TryStreamExt;
 use polyumi_util::{ id::{ marker::UserMarker, Id }, PG_POOL };
 
-use crate::Result;
+use crate::{
+	hakumi::UserModel,
+	pagination::SortDirection,
+	Result
+};
 
 #[derive(Serialize)]
 pub struct CafeOrderModel {
 	pub id: u64,
 	pub cafe_id: u64,
 	pub author_id: Option<Id<UserMarker>>,
+	pub author: Option<UserModel>,
 
 	#[serde(flatten)]
 	pub kind: CafeOrderKind,
+	pub reactions: Vec<CafeOrderReactions>,
+	pub reply_count: i64,
 
-	pub created_at: DateTime<Utc>
+	pub created_at: DateTime<Utc>,
+	pub edited_at: Option<DateTime<Utc>>
+}
+
+/// Everyone who reacted to an order with the same emoji, `reacted` being whether the viewer is one of them.
+#[derive(Serialize)]
+pub struct CafeOrderReactions {
+	pub emoji: String,
+	pub count: i64,
+	pub reacted: bool
 }
 
 impl CafeOrderModel {
-	pub async fn get_cafe_many(cafe_id: u64) -> Result<Vec<Self>> {
+	pub async fn get(cafe_id: u64, order_id: u64, viewer_id: Option<Id<UserMarker>>) -> Result<Option<Self>> {
+		Self::get_many(cafe_id, &[order_id], viewer_id)
+			.await
+			.map(|x| x.into_iter().next())
+	}
+
+	pub async fn get_many(cafe_id: u64, order_ids: &[u64], viewer_id: Option<Id<UserMarker>>) -> Result<Vec<Self>> {
+		if order_ids.is_empty() {
+			return Ok(vec![]);
+		}
+
 		let pinned = Pin::static_ref(&PG_POOL).await;
 
-		Ok(sqlx::query!(
+		let order_ids: Vec<i64> = order_ids
+			.iter()
+			.map(|x| *x as i64)
+			.collect();
+		let orders = sqlx::query!(
+			"
+			SELECT id, cafe_id, author_id, kind, payload, created_at, edited_at
+			FROM cafe_orders
+			WHERE cafe_id = $1 AND id = ANY($2)
+			",
+			cafe_id as i64,
+			&order_ids
+		)
+			.fetch(pinned.get_ref())
+			.try_fold(Vec::new(), |mut acc, u| {
+				acc.push(Self {
+					id: u.id as u64,
+					cafe_id: u.cafe_id as u64,
+					author_id: u.author_id.map(Into::into),
+					author: None,
+
+					kind: CafeOrderKind::decode(u.kind, u.payload),
+					reactions: vec![],
+					reply_count: 0,
+
+					created_at: u.created_at,
+					edited_at: u.edited_at
+				});
+
+				async move { Ok(acc) }
+			})
+			.await?;
+
+		Self::hydrate(orders, viewer_id).await
+	}
+
+	/// One page of a cafe's orders, starting after the `cursor` order.
+	/// Replies and reactions aren't included, they come with the order they belong to, and neither is anything from someone the viewer has blocked.
+	pub async fn get_cafe_page(cafe_id: u64, viewer_id: Option<Id<UserMarker>>, direction: SortDirection, cursor: Option<u64>, limit: i64) -> Result<Vec<Self>> {
+		let pinned = Pin::static_ref(&PG_POOL).await;
+
+		let orders = sqlx::query!(
+			"
+			SELECT id, cafe_id, author_id, kind, payload, created_at, edited_at
+			FROM cafe_orders
+			WHERE cafe_id = $1 AND kind != 'reaction' AND payload->'parent_id' IS NULL
+			AND NOT EXISTS (SELECT 1 FROM user_blocks WHERE user_id = $2 AND blocked_user_id = author_id)
+			AND ($3::int8 IS NULL OR CASE WHEN $4 THEN id < $3 ELSE id > $3 END)
+			ORDER BY CASE WHEN $4 THEN id END DESC, id
+			LIMIT $5
+			",
+			cafe_id as i64,
+			viewer_id.map(|x| x.value),
+			cursor.map(|x| x as i64),
+			direction.is_descending(),
+			limit
+		)
+			.fetch(pinned.get_ref())
+			.try_fold(Vec::new(), |mut acc, u| {
+				acc.push(Self {
+					id: u.id as u64,
+					cafe_id: u.cafe_id as u64,
+					author_id: u.author_id.map(Into::into),
+					author: None,
+
+					kind: CafeOrderKind::decode(u.kind, u.payload),
+					reactions: vec![],
+					reply_count: 0,
+
+					created_at: u.created_at,
+					edited_at: u.edited_at
+				});
+
+				async move { Ok(acc) }
+			})
+			.await?;
+
+		Self::hydrate(orders, viewer_id).await
+	}
+
+	/// One page of the replies to an order, starting after the `cursor` reply.
+	pub async fn get_reply_page(cafe_id: u64, parent_id: u64, viewer_id: Option<Id<UserMarker>>, direction: SortDirection, cursor: Option<u64>, limit: i64) -> Result<Vec<Self>> {
+		let pinned = Pin::static_ref(&PG_POOL).await;
+
+		let orders = sqlx::query!(
 			"
-			SELECT id, cafe_id, author_id, kind, payload, created_at
+			SELECT id, cafe_id, author_id, kind, payload, created_at, edited_at
 			FROM cafe_orders
-			WHERE id = $1
+			WHERE cafe_id = $1 AND kind != 'reaction' AND (payload->>'parent_id')::int8 = $2
+			AND NOT EXISTS (SELECT 1 FROM user_blocks WHERE user_id = $3 AND blocked_user_id = author_id)
+			AND ($4::int8 IS NULL OR CASE WHEN $5 THEN id < $4 ELSE id > $4 END)
+			ORDER BY CASE WHEN $5 THEN id END DESC, id
+			LIMIT $6
 			",
-			cafe_id as i64
+			cafe_id as i64,
+			parent_id as i64,
+			viewer_id.map(|x| x.value),
+			cursor.map(|x| x as i64),
+			direction.is_descending(),
+			limit
 		)
 			.fetch(pinned.get_ref())
 			.try_fold(Vec::new(), |mut acc, u| {
@@ -36,19 +157,137 @@ impl CafeOrderModel {
 					id: u.id as u64,
 					cafe_id: u.cafe_id as u64,
 					author_id: u.author_id.map(Into::into),
+					author: None,
 
-					// unsure if there's a better way to do this...
-					kind: serde_json::from_value(serde_json::json!({
-						"kind": u.kind,
-						"payload": u.payload
-					})).unwrap(),
+					kind: CafeOrderKind::decode(u.kind, u.payload),
+					reactions: vec![],
+					reply_count: 0,
 
-					created_at: u.created_at
+					created_at: u.created_at,
+					edited_at: u.edited_at
 				});
 
 				async move { Ok(acc) }
 			})
+			.await?;
+
+		Self::hydrate(orders, viewer_id).await
+	}
+
+	/// Fills in who placed each order, along with its reactions and how many replies it has.
+	/// Orders from deleted users are left without an author.
+	async fn hydrate(mut orders: Vec<Self>, viewer_id: Option<Id<UserMarker>>) -> Result<Vec<Self>> {
+		let author_ids: Vec<Id<UserMarker>> = orders
+			.iter()
+			.filter_map(|x| x.author_id)
+			.collect();
+		let authors = UserModel::get_many(&author_ids).await?;
+		for order in &mut orders {
+			order.author = authors
+				.iter()
+				.find(|x| Some(x.id) == order.author_id)
+				.cloned();
+		}
+
+		let order_ids: Vec<i64> = orders
+			.iter()
+			.map(|x| x.id as i64)
+			.collect();
+		let pinned = Pin::static_ref(&PG_POOL).await;
+		let reactions = sqlx::query!(
+			r#"
+			SELECT (payload->>'parent_id')::int8 AS "parent_id!", payload->>'emoji' AS "emoji!", COUNT(*) AS "count!", COALESCE(bool_or(author_id = $2), false) AS "reacted!"
+			FROM cafe_orders
+			WHERE kind = 'reaction' AND (payload->>'parent_id')::int8 = ANY($1)
+			GROUP BY 1, 2
+			ORDER BY MIN(id)
+			"#,
+			&order_ids,
+			viewer_id.map(|x| x.value)
+		)
+			.fetch_all(pinned.get_ref())
+			.await?;
+		for reaction in reactions {
+			if let Some(order) = orders.iter_mut().find(|x| x.id == reaction.parent_id as u64) {
+				order.reactions.push(CafeOrderReactions {
+					emoji: reaction.emoji,
+					count: reaction.count,
+					reacted: reaction.reacted
+				});
+			}
+		}
+
+		let reply_counts = sqlx::query!(
+			r#"
+			SELECT (payload->>'parent_id')::int8 AS "parent_id!", COUNT(*) AS "count!"
+			FROM cafe_orders
+			WHERE kind != 'reaction' AND (payload->>'parent_id')::int8 = ANY($1)
+			AND NOT EXISTS (SELECT 1 FROM user_blocks WHERE user_id = $2 AND blocked_user_id = author_id)
+			GROUP BY 1
+			"#,
+			&order_ids,
+			viewer_id.map(|x| x.value)
+		)
+			.fetch_all(pinned.get_ref())
+			.await?;
+		for reply_count in reply_counts {
+			if let Some(order) = orders.iter_mut().find(|x| x.id == reply_count.parent_id as u64) {
+				order.reply_count = reply_count.count;
+			}
+		}
+
+		Ok(orders)
+	}
+
+	/// When the user last placed an order in the cafe, for slow mode.
+	pub async fn last_placed_at(cafe_id: u64, author_id: Id<UserMarker>) -> Result<Option<DateTime<Utc>>> {
+		Ok(sqlx::query!(
+			"
+			SELECT MAX(created_at)
+			FROM cafe_orders
+			WHERE cafe_id = $1 AND author_id = $2 AND kind != 'reaction'
+			",
+			cafe_id as i64,
+			author_id.value
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
 			.await?
+			.max
+		)
+	}
+
+	pub async fn count_cafe(cafe_id: u64, viewer_id: Option<Id<UserMarker>>) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM cafe_orders
+			WHERE cafe_id = $1 AND kind != 'reaction' AND payload->'parent_id' IS NULL
+			AND NOT EXISTS (SELECT 1 FROM user_blocks WHERE user_id = $2 AND blocked_user_id = author_id)
+			"#,
+			cafe_id as i64,
+			viewer_id.map(|x| x.value)
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
+
+	pub async fn count_replies(cafe_id: u64, parent_id: u64, viewer_id: Option<Id<UserMarker>>) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM cafe_orders
+			WHERE cafe_id = $1 AND kind != 'reaction' AND (payload->>'parent_id')::int8 = $2
+			AND NOT EXISTS (SELECT 1 FROM user_blocks WHERE user_id = $3 AND blocked_user_id = author_id)
+			"#,
+			cafe_id as i64,
+			parent_id as i64,
+			viewer_id.map(|x| x.value)
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
 		)
 	}
 }
@@ -56,7 +295,63 @@ impl CafeOrderModel {
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "kind", content = "payload", rename_all = "snake_case")]
 pub enum CafeOrderKind {
-	Message(CafeOrderMessage)
+	Message(CafeOrderMessage),
+	Reaction {
+		parent_id: u64,
+		emoji: String
+	},
+	Attachment(CafeOrderAttachment),
+	Embed(CafeOrderEmbed),
+	/// anything this version doesn't understand, so one odd order doesn't take the whole cafe down with it.
+	#[serde(other)]
+	Unknown
+}
+
+impl CafeOrderKind {
+	pub fn decode(kind: String, payload: Value) -> Self {
+		serde_json::from_value(json!({
+			"kind": kind,
+			"payload": payload
+		}))
+			.unwrap_or_else(|error| {
+				warn!("failed to decode {kind} cafe order: {error}");
+				Self::Unknown
+			})
+	}
+
+	/// What's stored in the `kind` column.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Message(..) => "message",
+			Self::Reaction { .. } => "reaction",
+			Self::Attachment(..) => "attachment",
+			Self::Embed(..) => "embed",
+			Self::Unknown => "unknown"
+		}
+	}
+
+	/// What's stored in the `payload` column.
+	pub fn payload(&self) -> Result<Value> {
+		Ok(match self {
+			Self::Message(message) => serde_json::to_value(message)?,
+			Self::Reaction { parent_id, emoji } => json!({
+				"parent_id": parent_id,
+				"emoji": emoji
+			}),
+			Self::Attachment(attachment) => serde_json::to_value(attachment)?,
+			Self::Embed(embed) => serde_json::to_value(embed)?,
+			Self::Unknown => json!({})
+		})
+	}
+
+	/// The order this one is a reply or reaction to.
+	pub fn parent_id(&self) -> Option<u64> {
+		match self {
+			Self::Message(CafeOrderMessage::Reply { parent_id, .. }) |
+			Self::Reaction { parent_id, .. } => Some(*parent_id),
+			_ => None
+		}
+	}
 }
 
 #[derive(Serialize, Deserialize)]
@@ -64,5 +359,82 @@ pub enum CafeOrderKind {
 pub enum CafeOrderMessage {
 	Basic {
 		content: String
+	},
+	Reply {
+		content: String,
+		parent_id: u64
+	}
+}
+
+impl CafeOrderMessage {
+	pub fn content(&self) -> &str {
+		match self {
+			Self::Basic { content } |
+			Self::Reply { content, .. } => content
+		}
+	}
+}
+
+/// An image uploaded to storage, `key` being where it's kept and `url` where it can be fetched from.
+#[derive(Serialize, Deserialize)]
+pub struct CafeOrderAttachment {
+	pub key: String,
+	pub url: String,
+	pub content_type: String,
+	pub size: u64
+}
+
+/// A link, along with whatever opengraph metadata it had when it was embedded.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CafeOrderEmbed {
+	pub url: String,
+	pub title: Option<String>,
+	pub description: Option<String>,
+	pub image_url: Option<String>,
+	pub site_name: Option<String>
+}
+
+impl CafeOrderEmbed {
+	/// Metadata fetched for `url` within the last `max_age`, if there is any.
+	pub async fn get_cached(url: &str, max_age: chrono::Duration) -> Result<Option<Self>> {
+		Ok(sqlx::query!(
+			"
+			SELECT url, title, description, image_url, site_name
+			FROM cafe_embeds
+			WHERE url = $1 AND fetched_at > $2
+			",
+			url,
+			Utc::now() - max_age
+		)
+			.fetch_optional(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.map(|x| Self {
+				url: x.url,
+				title: x.title,
+				description: x.description,
+				image_url: x.image_url,
+				site_name: x.site_name
+			})
+		)
+	}
+
+	pub async fn cache(&self) -> Result<()> {
+		sqlx::query!(
+			"
+			INSERT INTO cafe_embeds (url, title, description, image_url, site_name)
+			VALUES ($1, $2, $3, $4, $5)
+			ON CONFLICT (url) DO UPDATE
+			SET title = $2, description = $3, image_url = $4, site_name = $5, fetched_at = now()
+			",
+			self.url,
+			self.title,
+			self.description,
+			self.image_url,
+			self.site_name
+		)
+			.execute(&*Pin::static_ref(&PG_POOL).await)
+			.await?;
+
+		Ok(())
 	}
 }
\ No newline at end of file
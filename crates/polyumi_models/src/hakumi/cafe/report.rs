@@ -0,0 +1,168 @@
+use std::pin::Pin;
+use serde::{ Deserialize, Serialize };
+use chrono::{ Utc, DateTime };
+use futures::TryStreamExt;
+use polyumi_util::{ id::{ marker::UserMarker, Id }, PG_POOL };
+
+use crate::{
+	hakumi::cafe::CafeOrderModel,
+	pagination::SortDirection,
+	Result
+};
+
+/// Someone asking a cafe's moderators to look at an order, or the word filter doing so on its own.
+#[derive(Serialize)]
+pub struct CafeOrderReportModel {
+	pub id: u64,
+	pub cafe_id: u64,
+	/// the order is gone once it's been deleted, but the report sticks around.
+	pub order_id: Option<u64>,
+	pub order: Option<CafeOrderModel>,
+	/// nobody when the word filter flagged it.
+	pub reporter_id: Option<Id<UserMarker>>,
+	pub reason: String,
+	pub created_at: DateTime<Utc>,
+
+	pub resolver_id: Option<Id<UserMarker>>,
+	pub resolution: Option<CafeOrderReportResolution>,
+	pub resolved_at: Option<DateTime<Utc>>
+}
+
+impl CafeOrderReportModel {
+	pub async fn get(cafe_id: u64, report_id: u64) -> Result<Option<Self>> {
+		let record = sqlx::query!(
+			"
+			SELECT id, cafe_id, order_id, reporter_id, reason, created_at, resolver_id, resolution, resolved_at
+			FROM cafe_order_reports
+			WHERE cafe_id = $1 AND id = $2
+			",
+			cafe_id as i64,
+			report_id as i64
+		)
+			.fetch_optional(&*Pin::static_ref(&PG_POOL).await)
+			.await?;
+		let Some(report) = record.map(|x| Self {
+			id: x.id as u64,
+			cafe_id: x.cafe_id as u64,
+			order_id: x.order_id.map(|x| x as u64),
+			order: None,
+			reporter_id: x.reporter_id.map(Into::into),
+			reason: x.reason,
+			created_at: x.created_at,
+
+			resolver_id: x.resolver_id.map(Into::into),
+			resolution: x.resolution.as_deref().map(Into::into),
+			resolved_at: x.resolved_at
+		}) else {
+			return Ok(None);
+		};
+
+		Ok(Self::with_orders(cafe_id, vec![report])
+			.await?
+			.pop()
+		)
+	}
+
+	/// One page of a cafe's reports, either those still waiting on a moderator or those that have been dealt with.
+	pub async fn get_cafe_page(cafe_id: u64, resolved: bool, direction: SortDirection, cursor: Option<u64>, limit: i64) -> Result<Vec<Self>> {
+		let pinned = Pin::static_ref(&PG_POOL).await;
+
+		let reports = sqlx::query!(
+			"
+			SELECT id, cafe_id, order_id, reporter_id, reason, created_at, resolver_id, resolution, resolved_at
+			FROM cafe_order_reports
+			WHERE cafe_id = $1 AND (resolved_at IS NOT NULL) = $2
+			AND ($3::int8 IS NULL OR CASE WHEN $4 THEN id < $3 ELSE id > $3 END)
+			ORDER BY CASE WHEN $4 THEN id END DESC, id
+			LIMIT $5
+			",
+			cafe_id as i64,
+			resolved,
+			cursor.map(|x| x as i64),
+			direction.is_descending(),
+			limit
+		)
+			.fetch(pinned.get_ref())
+			.try_fold(Vec::new(), |mut acc, x| {
+				acc.push(Self {
+					id: x.id as u64,
+					cafe_id: x.cafe_id as u64,
+					order_id: x.order_id.map(|x| x as u64),
+					order: None,
+					reporter_id: x.reporter_id.map(Into::into),
+					reason: x.reason,
+					created_at: x.created_at,
+
+					resolver_id: x.resolver_id.map(Into::into),
+					resolution: x.resolution.as_deref().map(Into::into),
+					resolved_at: x.resolved_at
+				});
+
+				async move { Ok(acc) }
+			})
+			.await?;
+
+		Self::with_orders(cafe_id, reports).await
+	}
+
+	/// Fills in the orders being reported, so moderators can see what they're deciding on.
+	async fn with_orders(cafe_id: u64, mut reports: Vec<Self>) -> Result<Vec<Self>> {
+		let order_ids: Vec<u64> = reports
+			.iter()
+			.filter_map(|x| x.order_id)
+			.collect();
+		let mut orders = CafeOrderModel::get_many(cafe_id, &order_ids, None).await?;
+		for report in &mut reports {
+			report.order = orders
+				.iter()
+				.position(|x| Some(x.id) == report.order_id)
+				.map(|x| orders.swap_remove(x));
+		}
+
+		Ok(reports)
+	}
+
+	pub async fn count_cafe(cafe_id: u64, resolved: bool) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM cafe_order_reports
+			WHERE cafe_id = $1 AND (resolved_at IS NOT NULL) = $2
+			"#,
+			cafe_id as i64,
+			resolved
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CafeOrderReportResolution {
+	/// a moderator looked and left the order be.
+	Dismissed,
+	/// the order was taken down, by a moderator or whoever placed it.
+	OrderDeleted
+}
+
+impl CafeOrderReportResolution {
+	/// What's stored in the `resolution` column.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Dismissed => "dismissed",
+			Self::OrderDeleted => "order_deleted"
+		}
+	}
+}
+
+impl From<&str> for CafeOrderReportResolution {
+	fn from(value: &str) -> Self {
+		match value {
+			"order_deleted" => Self::OrderDeleted,
+			_ => Self::Dismissed
+		}
+	}
+}
\ No newline at end of file
@@ -1,14 +1,18 @@
 use std::pin::Pin;
 use uuid::Uuid;
-use serde::Serialize;
+use serde::{ Deserialize, Serialize };
 use chrono::{ Utc, DateTime };
 use futures::TryStreamExt;
 use polyumi_util::{
-	id::{ marker::{ GroupMarker, UserMarker }, Id },
+	id::{ marker::{ GroupMarker, GroupRoleMarker, UserMarker }, Id },
 	PG_POOL
 };
 
-use crate::Result;
+use crate::{
+	hakumi::{ group::GroupPermissions, UserModel },
+	pagination::SortDirection,
+	Result
+};
 
 #[derive(Serialize)]
 pub struct GroupMembershipModel {
@@ -19,10 +23,158 @@ pub struct GroupMembershipModel {
 	pub is_pending: bool,
 
 	pub group_id: Id<GroupMarker>,
+	pub user_id: Id<UserMarker>,
+
+	pub inviter_id: Option<Id<UserMarker>>,
+	pub invite_expires_at: Option<DateTime<Utc>>,
+
+	pub role_ids: Vec<Id<GroupRoleMarker>>
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupMembershipFilter {
+	/// everyone who has joined, owners included.
+	#[default]
+	Members,
+	Owners,
+	/// members who joined through an invite.
+	Invited,
+	/// invites that haven't been accepted yet.
+	Pending,
+	/// requests to join that haven't been approved yet.
+	Requested
+}
+
+impl GroupMembershipFilter {
+	fn is_pending(self) -> bool {
+		matches!(self, Self::Pending | Self::Requested)
+	}
+
+	fn is_invited(self) -> Option<bool> {
+		match self {
+			Self::Invited | Self::Pending => Some(true),
+			Self::Requested => Some(false),
+			_ => None
+		}
+	}
+}
+
+/// Where a page of memberships left off, memberships are ordered by when they were created and then by user.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GroupMembershipCursor {
+	pub created_at: DateTime<Utc>,
 	pub user_id: Id<UserMarker>
 }
 
 impl GroupMembershipModel {
+	/// Whether someone asked to join, rather than being invited.
+	pub fn is_join_request(&self) -> bool {
+		self.is_pending && !self.is_invited
+	}
+
+	/// Whether this is an invite that ran out before it was accepted.
+	pub fn is_expired(&self) -> bool {
+		self.invite_expires_at.is_some_and(|x| x <= Utc::now())
+	}
+
+	pub fn cursor(&self) -> GroupMembershipCursor {
+		GroupMembershipCursor {
+			created_at: self.created_at,
+			user_id: self.user_id
+		}
+	}
+
+	/// One page of a group's memberships alongside their users, starting after `cursor`.
+	pub async fn get_group_page(group_id: Id<GroupMarker>, filter: GroupMembershipFilter, direction: SortDirection, cursor: Option<GroupMembershipCursor>, limit: i64) -> Result<Vec<(Self, UserModel)>> {
+		let pinned = Pin::static_ref(&PG_POOL).await;
+		Ok(sqlx::query!(
+			r#"
+			SELECT m.joined_at, m.is_invited, m.is_owner, m.is_pending, m.team_id, m.user_id, m.inviter_id, m.invite_expires_at, ARRAY(SELECT r.role_id FROM team_member_roles r WHERE (r.team_id, r.user_id) = (m.team_id, m.user_id)) AS "role_ids!", u.bio, u.name, u.flags, u.username, u.avatar_url, u.banner_url, u.created_at, u.profile_status, c.id as "profile_cafe_id?", u.theme_accent_colour, u.theme_primary_colour
+			FROM team_members m
+			INNER JOIN users u ON u.id = m.user_id
+			LEFT JOIN cafes c ON c.owner_user_id = u.id AND c.kind = 'profile'
+			WHERE m.team_id = $1 AND m.is_pending = $2 AND (NOT $3 OR m.is_owner) AND ($4::bool IS NULL OR m.is_invited = $4)
+			AND (m.invite_expires_at IS NULL OR m.invite_expires_at > now())
+			AND (
+				$5::timestamptz IS NULL OR
+				CASE WHEN $7
+					THEN (m.joined_at, m.user_id) < ($5, $6::uuid)
+					ELSE (m.joined_at, m.user_id) > ($5, $6::uuid)
+				END
+			)
+			ORDER BY
+				CASE WHEN $7 THEN m.joined_at END DESC, CASE WHEN $7 THEN m.user_id END DESC,
+				m.joined_at, m.user_id
+			LIMIT $8
+			"#,
+			group_id.value,
+			filter.is_pending(),
+			filter == GroupMembershipFilter::Owners,
+			filter.is_invited(),
+			cursor.map(|x| x.created_at),
+			cursor.map(|x| x.user_id.value),
+			direction.is_descending(),
+			limit
+		)
+			.fetch(pinned.get_ref())
+			.try_fold(Vec::new(), |mut acc, u| {
+				acc.push((Self {
+					created_at: u.joined_at,
+
+					is_invited: u.is_invited,
+					is_owner: u.is_owner,
+					is_pending: u.is_pending,
+
+					group_id: u.team_id.into(),
+					user_id: u.user_id.into(),
+
+					inviter_id: u.inviter_id.map(Into::into),
+					invite_expires_at: u.invite_expires_at,
+
+					role_ids: u.role_ids
+						.into_iter()
+						.map(Into::into)
+						.collect()
+				}, UserModel {
+					id: u.user_id.into(),
+					bio: u.bio,
+					name: u.name,
+					flags: u.flags as u8,
+					username: u.username,
+					avatar_url: u.avatar_url,
+					banner_url: u.banner_url,
+					created_at: u.created_at,
+					profile_status: u.profile_status,
+					profile_cafe_id: u.profile_cafe_id.map(|x| x as u64),
+					profile_theme_accent_colour: u.theme_accent_colour as u32,
+					profile_theme_primary_colour: u.theme_primary_colour as u32
+				}));
+				async move { Ok(acc) }
+			})
+			.await?
+		)
+	}
+
+	pub async fn count_group(group_id: Id<GroupMarker>, filter: GroupMembershipFilter) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM team_members
+			WHERE team_id = $1 AND is_pending = $2 AND (NOT $3 OR is_owner) AND ($4::bool IS NULL OR is_invited = $4)
+			AND (invite_expires_at IS NULL OR invite_expires_at > now())
+			"#,
+			group_id.value,
+			filter.is_pending(),
+			filter == GroupMembershipFilter::Owners,
+			filter.is_invited()
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
+
 	pub async fn get_group(group_id: Id<GroupMarker>) -> Result<Vec<Self>> {
 		Self::get_group_many(&[group_id]).await
 	}
@@ -34,11 +186,11 @@ impl GroupMembershipModel {
 			.map(|x| x.value)
 			.collect();
 		Ok(sqlx::query!(
-			"
-			SELECT joined_at, is_invited, is_owner, is_pending, team_id, user_id
+			r#"
+			SELECT joined_at, is_invited, is_owner, is_pending, team_id, user_id, inviter_id, invite_expires_at, ARRAY(SELECT r.role_id FROM team_member_roles r WHERE (r.team_id, r.user_id) = (team_members.team_id, team_members.user_id)) AS "role_ids!"
 			FROM team_members
 			WHERE team_id = ANY($1)
-			",
+			"#,
 			&group_ids
 		)
 			.fetch(pinned.get_ref())
@@ -51,7 +203,15 @@ impl GroupMembershipModel {
 					is_pending: u.is_pending,
 
 					group_id: u.team_id.into(),
-					user_id: u.user_id.into()
+					user_id: u.user_id.into(),
+
+					inviter_id: u.inviter_id.map(Into::into),
+					invite_expires_at: u.invite_expires_at,
+
+					role_ids: u.role_ids
+						.into_iter()
+						.map(Into::into)
+						.collect()
 				});
 				async move { Ok(acc) }
 			})
@@ -65,14 +225,38 @@ impl GroupMembershipModel {
 			.map(|x| x.into_iter().next())
 	}
 
+	/// What a member can do in a group, `None` if they haven't joined it.
+	pub async fn get_permissions(group_id: Id<GroupMarker>, user_id: Id<UserMarker>) -> Result<Option<GroupPermissions>> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT m.is_owner, COALESCE(BIT_OR(r.permissions), 0) AS "permissions!"
+			FROM team_members m
+			LEFT JOIN team_member_roles mr ON mr.team_id = m.team_id AND mr.user_id = m.user_id
+			LEFT JOIN team_roles r ON r.id = mr.role_id
+			WHERE m.team_id = $1 AND m.user_id = $2 AND NOT m.is_pending
+			GROUP BY m.is_owner
+			"#,
+			group_id.value,
+			user_id.value
+		)
+			.fetch_optional(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.map(|x| if x.is_owner {
+				GroupPermissions::ALL
+			} else {
+				GroupPermissions::from_bits_truncate(x.permissions as u64)
+			})
+		)
+	}
+
 	pub async fn get_user_all(user_id: Id<UserMarker>) -> Result<Vec<Self>> {
 		let pinned = Pin::static_ref(&PG_POOL).await;
 		Ok(sqlx::query!(
-			"
-			SELECT joined_at, is_invited, is_owner, is_pending, team_id, user_id
+			r#"
+			SELECT joined_at, is_invited, is_owner, is_pending, team_id, user_id, inviter_id, invite_expires_at, ARRAY(SELECT r.role_id FROM team_member_roles r WHERE (r.team_id, r.user_id) = (team_members.team_id, team_members.user_id)) AS "role_ids!"
 			FROM team_members
 			WHERE user_id = $1
-			",
+			"#,
 			user_id.value
 		)
 			.fetch(pinned.get_ref())
@@ -85,7 +269,15 @@ impl GroupMembershipModel {
 					is_pending: u.is_pending,
 
 					group_id: u.team_id.into(),
-					user_id: u.user_id.into()
+					user_id: u.user_id.into(),
+
+					inviter_id: u.inviter_id.map(Into::into),
+					invite_expires_at: u.invite_expires_at,
+
+					role_ids: u.role_ids
+						.into_iter()
+						.map(Into::into)
+						.collect()
 				});
 				async move { Ok(acc) }
 			})
@@ -100,11 +292,11 @@ impl GroupMembershipModel {
 			.map(|x| x.value)
 			.collect();
 		Ok(sqlx::query!(
-			"
-			SELECT joined_at, is_invited, is_owner, is_pending, team_id, user_id
+			r#"
+			SELECT joined_at, is_invited, is_owner, is_pending, team_id, user_id, inviter_id, invite_expires_at, ARRAY(SELECT r.role_id FROM team_member_roles r WHERE (r.team_id, r.user_id) = (team_members.team_id, team_members.user_id)) AS "role_ids!"
 			FROM team_members
 			WHERE team_id = ANY($1) and user_id = $2
-			",
+			"#,
 			&group_ids,
 			user_id.value
 		)
@@ -118,7 +310,15 @@ impl GroupMembershipModel {
 					is_pending: u.is_pending,
 					
 					group_id: u.team_id.into(),
-					user_id: u.user_id.into()
+					user_id: u.user_id.into(),
+
+					inviter_id: u.inviter_id.map(Into::into),
+					invite_expires_at: u.invite_expires_at,
+
+					role_ids: u.role_ids
+						.into_iter()
+						.map(Into::into)
+						.collect()
 				});
 				async move { Ok(acc) }
 			})
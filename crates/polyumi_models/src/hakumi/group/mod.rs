@@ -4,7 +4,7 @@ use std::{
 	pin::Pin
 };
 use uuid::Uuid;
-use serde::Serialize;
+use serde::{ Deserialize, Serialize };
 use chrono::{ Utc, DateTime };
 use futures::TryStreamExt;
 use polyumi_util::{
@@ -12,11 +12,56 @@ use polyumi_util::{
 	PG_POOL
 };
 
-use crate::Result;
+use crate::{
+	pagination::SortDirection,
+	Result
+};
 
 pub mod membership;
 pub use membership::GroupMembershipModel;
 
+pub mod role;
+pub use role::{ GroupPermissions, GroupRoleModel };
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupVisibility {
+	/// anyone can see it, and ask to join.
+	#[default]
+	Public,
+	/// anyone who knows about it can see it, but it isn't listed on profiles.
+	Unlisted,
+	/// only its members, and whoever they've invited, can see it.
+	Private
+}
+
+impl GroupVisibility {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Public => "public",
+			Self::Unlisted => "unlisted",
+			Self::Private => "private"
+		}
+	}
+}
+
+impl From<&str> for GroupVisibility {
+	fn from(value: &str) -> Self {
+		match value {
+			"public" => Self::Public,
+			"unlisted" => Self::Unlisted,
+			_ => Self::Private
+		}
+	}
+}
+
+/// Where a page of groups left off, groups are ordered by their lowercased name and then by id.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GroupCursor {
+	pub lower_name: String,
+	pub id: Id<GroupMarker>
+}
+
 #[derive(Serialize)]
 pub struct GroupModel {
 	pub id: Id<GroupMarker>,
@@ -26,6 +71,7 @@ pub struct GroupModel {
 	pub bio: Option<String>,
 	pub name: String,
 	pub display_name: Option<String>,
+	pub visibility: GroupVisibility,
 
 	pub avatar_url: Option<String>,
 	pub banner_url: Option<String>,
@@ -40,6 +86,27 @@ impl GroupModel {
 			.map(|x| x.into_iter().next())
 	}
 
+	pub fn cursor(&self) -> GroupCursor {
+		GroupCursor {
+			// names are ascii-only, so this matches LOWER() in postgres
+			lower_name: self.name.to_ascii_lowercase(),
+			id: self.id
+		}
+	}
+
+	/// Private groups are hidden from everyone but their members and invitees, join requests and expired invites don't count.
+	pub async fn is_visible_to(&self, viewer_id: Option<Id<UserMarker>>) -> Result<bool> {
+		if self.visibility != GroupVisibility::Private {
+			return Ok(true);
+		}
+
+		let membership = match viewer_id {
+			Some(viewer_id) => GroupMembershipModel::get_user(self.id, viewer_id).await?,
+			None => None
+		};
+		Ok(membership.is_some_and(|x| !x.is_expired() && !x.is_join_request()))
+	}
+
 	pub async fn get_many<T: Display + Hash + Eq + PartialEq + Clone + Debug>(group_refs: &[T]) -> Result<Vec<Self>> {
 		if group_refs.is_empty() {
 			return Ok(vec![]);
@@ -58,7 +125,7 @@ impl GroupModel {
 
 		Ok(sqlx::query!(
 			"
-			SELECT id, created_at, creator_id, bio, name, display_name, avatar_url, banner_url, profile_theme_accent_colour, profile_theme_primary_colour
+			SELECT id, created_at, creator_id, bio, name, display_name, avatar_url, banner_url, profile_theme_accent_colour, profile_theme_primary_colour, visibility
 			FROM teams
 			WHERE id = ANY($1) OR LOWER(name) = ANY($2)
 			",
@@ -75,6 +142,7 @@ impl GroupModel {
 					bio: u.bio,
 					name: u.name,
 					display_name: u.display_name,
+					visibility: u.visibility.as_str().into(),
 
 					avatar_url: u.avatar_url,
 					banner_url: u.banner_url,
@@ -87,4 +155,82 @@ impl GroupModel {
 			.await?
 		)
 	}
+
+	/// One page of the groups a user is a member of, ordered by name and starting after `cursor`.
+	/// Only public groups are listed, unless `viewer_id` is the user themself or a member of the group too.
+	pub async fn get_user_page(user_id: Id<UserMarker>, viewer_id: Option<Id<UserMarker>>, direction: SortDirection, cursor: Option<GroupCursor>, limit: i64) -> Result<Vec<Self>> {
+		let pinned = Pin::static_ref(&PG_POOL).await;
+		Ok(sqlx::query!(
+			"
+			SELECT g.id, g.created_at, g.creator_id, g.bio, g.name, g.display_name, g.avatar_url, g.banner_url, g.profile_theme_accent_colour, g.profile_theme_primary_colour, g.visibility
+			FROM teams g
+			INNER JOIN team_members gm ON gm.team_id = g.id AND NOT gm.is_pending
+			WHERE gm.user_id = $1
+			AND (
+				g.visibility = 'public' OR $1 = $5 OR
+				EXISTS (SELECT 1 FROM team_members v WHERE v.team_id = g.id AND v.user_id = $5 AND NOT v.is_pending)
+			)
+			AND (
+				$2::text IS NULL OR
+				CASE WHEN $3
+					THEN (LOWER(g.name), g.id) < ($2, $6::uuid)
+					ELSE (LOWER(g.name), g.id) > ($2, $6::uuid)
+				END
+			)
+			ORDER BY
+				CASE WHEN $3 THEN LOWER(g.name) END DESC, CASE WHEN $3 THEN g.id END DESC,
+				LOWER(g.name), g.id
+			LIMIT $4
+			",
+			user_id.value,
+			cursor.as_ref().map(|x| x.lower_name.as_str()),
+			direction.is_descending(),
+			limit,
+			viewer_id.map(|x| x.value),
+			cursor.as_ref().map(|x| x.id.value)
+		)
+			.fetch(pinned.get_ref())
+			.try_fold(Vec::new(), |mut acc, u| {
+				acc.push(Self {
+					id: u.id.into(),
+					created_at: u.created_at,
+					creator_id: u.creator_id.map(Into::into),
+
+					bio: u.bio,
+					name: u.name,
+					display_name: u.display_name,
+					visibility: u.visibility.as_str().into(),
+
+					avatar_url: u.avatar_url,
+					banner_url: u.banner_url,
+					profile_theme_accent_colour: u.profile_theme_accent_colour as u32,
+					profile_theme_primary_colour: u.profile_theme_primary_colour as u32
+				});
+
+				async move { Ok(acc) }
+			})
+			.await?
+		)
+	}
+
+	pub async fn count_user(user_id: Id<UserMarker>, viewer_id: Option<Id<UserMarker>>) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM teams g
+			INNER JOIN team_members gm ON gm.team_id = g.id AND NOT gm.is_pending
+			WHERE gm.user_id = $1
+			AND (
+				g.visibility = 'public' OR $1 = $2 OR
+				EXISTS (SELECT 1 FROM team_members v WHERE v.team_id = g.id AND v.user_id = $2 AND NOT v.is_pending)
+			)
+			"#,
+			user_id.value,
+			viewer_id.map(|x| x.value)
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
 }
\ No newline at end of file
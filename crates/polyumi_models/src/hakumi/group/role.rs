@@ -0,0 +1,159 @@
+use std::{
+	ops::{ BitOr, BitOrAssign },
+	pin::Pin
+};
+use serde::{ Deserialize, Serialize };
+use chrono::{ Utc, DateTime };
+use futures::TryStreamExt;
+use polyumi_util::{
+	id::{ marker::{ GroupMarker, GroupRoleMarker }, Id },
+	PG_POOL
+};
+
+use crate::{
+	pagination::SortDirection,
+	Result
+};
+
+/// What a group member is allowed to do, the union of every role they've been given.
+/// Owners can always do everything.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct GroupPermissions(u64);
+
+impl GroupPermissions {
+	pub const NONE: Self = Self(0);
+	/// inviting people, cancelling invites and handing out roles.
+	pub const MANAGE_MEMBERS: Self = Self(1 << 0);
+	pub const MANAGE_MELLOW_SERVERS: Self = Self(1 << 1);
+	pub const EDIT_VISUAL_SCRIPTS: Self = Self(1 << 2);
+	pub const MANAGE_CAFE: Self = Self(1 << 3);
+	pub const ALL: Self = Self(
+		Self::MANAGE_MEMBERS.0 |
+		Self::MANAGE_MELLOW_SERVERS.0 |
+		Self::EDIT_VISUAL_SCRIPTS.0 |
+		Self::MANAGE_CAFE.0
+	);
+
+	/// Drops any bits that don't mean anything (yet).
+	pub const fn from_bits_truncate(bits: u64) -> Self {
+		Self(bits & Self::ALL.0)
+	}
+
+	pub const fn bits(self) -> u64 {
+		self.0
+	}
+
+	pub const fn contains(self, other: Self) -> bool {
+		self.0 & other.0 == other.0
+	}
+
+	pub const fn is_valid(self) -> bool {
+		self.0 & !Self::ALL.0 == 0
+	}
+}
+
+impl BitOr for GroupPermissions {
+	type Output = Self;
+
+	fn bitor(self, rhs: Self) -> Self {
+		Self(self.0 | rhs.0)
+	}
+}
+
+impl BitOrAssign for GroupPermissions {
+	fn bitor_assign(&mut self, rhs: Self) {
+		self.0 |= rhs.0;
+	}
+}
+
+#[derive(Serialize)]
+pub struct GroupRoleModel {
+	pub id: Id<GroupRoleMarker>,
+	pub created_at: DateTime<Utc>,
+	pub group_id: Id<GroupMarker>,
+
+	pub name: String,
+	pub permissions: GroupPermissions
+}
+
+impl GroupRoleModel {
+	pub async fn get(group_id: Id<GroupMarker>, role_id: Id<GroupRoleMarker>) -> Result<Option<Self>> {
+		Ok(sqlx::query!(
+			"
+			SELECT id, created_at, team_id, name, permissions
+			FROM team_roles
+			WHERE team_id = $1 AND id = $2
+			",
+			group_id.value,
+			role_id.value
+		)
+			.fetch_optional(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.map(|x| Self {
+				id: x.id.into(),
+				created_at: x.created_at,
+				group_id: x.team_id.into(),
+
+				name: x.name,
+				permissions: GroupPermissions::from_bits_truncate(x.permissions as u64)
+			})
+		)
+	}
+
+	/// One page of a group's roles in the order they were created, starting after the `cursor` role.
+	pub async fn get_group_page(group_id: Id<GroupMarker>, direction: SortDirection, cursor: Option<Id<GroupRoleMarker>>, limit: i64) -> Result<Vec<Self>> {
+		let pinned = Pin::static_ref(&PG_POOL).await;
+		Ok(sqlx::query!(
+			"
+			SELECT id, created_at, team_id, name, permissions
+			FROM team_roles
+			WHERE team_id = $1
+			AND (
+				$2::uuid IS NULL OR
+				CASE WHEN $3
+					THEN (created_at, id) < ((SELECT created_at FROM team_roles WHERE id = $2), $2)
+					ELSE (created_at, id) > ((SELECT created_at FROM team_roles WHERE id = $2), $2)
+				END
+			)
+			ORDER BY
+				CASE WHEN $3 THEN created_at END DESC, CASE WHEN $3 THEN id END DESC,
+				created_at, id
+			LIMIT $4
+			",
+			group_id.value,
+			cursor.map(|x| x.value),
+			direction.is_descending(),
+			limit
+		)
+			.fetch(pinned.get_ref())
+			.try_fold(Vec::new(), |mut acc, x| {
+				acc.push(Self {
+					id: x.id.into(),
+					created_at: x.created_at,
+					group_id: x.team_id.into(),
+
+					name: x.name,
+					permissions: GroupPermissions::from_bits_truncate(x.permissions as u64)
+				});
+				async move { Ok(acc) }
+			})
+			.await?
+		)
+	}
+
+	pub async fn count_group(group_id: Id<GroupMarker>) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM team_roles
+			WHERE team_id = $1
+			"#,
+			group_id.value
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
+}
\ No newline at end of file
@@ -10,4 +10,7 @@ pub use oauth_authorisation::OAuthAuthorisationModel;
 pub mod user;
 pub use user::UserModel;
 
-pub mod visual_scripting;
\ No newline at end of file
+pub mod visual_scripting;
+
+pub mod webhook;
+pub use webhook::WebhookModel;
\ No newline at end of file
@@ -1,5 +1,6 @@
 use chrono::{ DateTime, Utc };
 
+#[derive(Clone)]
 pub struct OAuthAuthorisationModel {
 	pub id: u64,
 	pub expires_at: DateTime<Utc>,
@@ -0,0 +1,108 @@
+use std::pin::Pin;
+use serde::Serialize;
+use chrono::{ Utc, DateTime };
+use futures::TryStreamExt;
+use polyumi_util::{
+	id::{ marker::UserMarker, Id },
+	PG_POOL
+};
+
+use crate::{
+	hakumi::UserModel,
+	pagination::SortDirection,
+	Result
+};
+
+/// Someone a user doesn't want to hear from, their orders are hidden from the user and they can't place any in the user's profile cafe.
+#[derive(Serialize)]
+pub struct UserBlockModel {
+	pub user_id: Id<UserMarker>,
+	pub blocked_user_id: Id<UserMarker>,
+	pub blocked_user: Option<UserModel>,
+	pub created_at: DateTime<Utc>
+}
+
+impl UserBlockModel {
+	pub async fn is_blocked(user_id: Id<UserMarker>, blocked_user_id: Id<UserMarker>) -> Result<bool> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT EXISTS (
+				SELECT 1 FROM user_blocks WHERE user_id = $1 AND blocked_user_id = $2
+			) AS "exists!"
+			"#,
+			user_id.value,
+			blocked_user_id.value
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.exists
+		)
+	}
+
+	/// One page of the users someone has blocked in the order they were blocked, starting after the `cursor` user.
+	pub async fn get_user_page(user_id: Id<UserMarker>, direction: SortDirection, cursor: Option<Id<UserMarker>>, limit: i64) -> Result<Vec<Self>> {
+		let pinned = Pin::static_ref(&PG_POOL).await;
+		let mut blocks = sqlx::query!(
+			"
+			SELECT user_id, blocked_user_id, created_at
+			FROM user_blocks
+			WHERE user_id = $1
+			AND (
+				$2::uuid IS NULL OR
+				CASE WHEN $3
+					THEN (created_at, blocked_user_id) < ((SELECT created_at FROM user_blocks WHERE user_id = $1 AND blocked_user_id = $2), $2)
+					ELSE (created_at, blocked_user_id) > ((SELECT created_at FROM user_blocks WHERE user_id = $1 AND blocked_user_id = $2), $2)
+				END
+			)
+			ORDER BY
+				CASE WHEN $3 THEN created_at END DESC, CASE WHEN $3 THEN blocked_user_id END DESC,
+				created_at, blocked_user_id
+			LIMIT $4
+			",
+			user_id.value,
+			cursor.map(|x| x.value),
+			direction.is_descending(),
+			limit
+		)
+			.fetch(pinned.get_ref())
+			.try_fold(Vec::new(), |mut acc, x| {
+				acc.push(Self {
+					user_id: x.user_id.into(),
+					blocked_user_id: x.blocked_user_id.into(),
+					blocked_user: None,
+					created_at: x.created_at
+				});
+				async move { Ok(acc) }
+			})
+			.await?;
+
+		let blocked_user_ids: Vec<Id<UserMarker>> = blocks
+			.iter()
+			.map(|x| x.blocked_user_id)
+			.collect();
+		let blocked_users = UserModel::get_many(&blocked_user_ids).await?;
+		for block in &mut blocks {
+			block.blocked_user = blocked_users
+				.iter()
+				.find(|x| x.id == block.blocked_user_id)
+				.cloned();
+		}
+
+		Ok(blocks)
+	}
+
+	pub async fn count_user(user_id: Id<UserMarker>) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM user_blocks
+			WHERE user_id = $1
+			"#,
+			user_id.value
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
+}
\ No newline at end of file
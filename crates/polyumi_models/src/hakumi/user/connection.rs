@@ -15,10 +15,11 @@ use std::pin::Pin;
 
 use crate::{
 	hakumi::OAuthAuthorisationModel,
+	pagination::SortDirection,
 	Result
 };
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct ConnectionModel {
 	pub id: Id<ConnectionMarker>,
 	pub sub: String,
@@ -179,6 +180,57 @@ impl ConnectionModel {
 		Ok(connections)
 	}
 
+	/// One page of a user's connections in the order they were made, starting after the `cursor` connection.
+	pub async fn get_user_page(user_id: Id<UserMarker>, public_only: bool, direction: SortDirection, cursor: Option<Id<ConnectionMarker>>, limit: i64) -> Result<Vec<Self>> {
+		let connection_ids: Vec<Id<ConnectionMarker>> = sqlx::query!(
+			"
+			SELECT id
+			FROM user_connections
+			WHERE user_id = $1 AND (is_public OR NOT $2)
+			AND (
+				$3::uuid IS NULL OR
+				CASE WHEN $4
+					THEN (created_at, id) < ((SELECT created_at FROM user_connections WHERE id = $3), $3)
+					ELSE (created_at, id) > ((SELECT created_at FROM user_connections WHERE id = $3), $3)
+				END
+			)
+			ORDER BY CASE WHEN $4 THEN created_at END DESC, CASE WHEN $4 THEN id END DESC, created_at, id
+			LIMIT $5
+			",
+			user_id.value,
+			public_only,
+			cursor.map(|x| x.value),
+			direction.is_descending(),
+			limit
+		)
+			.fetch_all(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.into_iter()
+			.map(|x| x.id.into())
+			.collect();
+
+		let mut connections = Self::get_many(&connection_ids).await?;
+		connections.sort_by_key(|x| connection_ids.iter().position(|y| *y == x.id));
+
+		Ok(connections)
+	}
+
+	pub async fn count_user(user_id: Id<UserMarker>, public_only: bool) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM user_connections
+			WHERE user_id = $1 AND (is_public OR NOT $2)
+			"#,
+			user_id.value,
+			public_only
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
+
 	pub async fn user_discord(user_id: Id<UserMarker>) -> Result<Option<Id<UserMarker>>> {
 		Ok(sqlx::query!(
 			"
@@ -2,31 +2,41 @@ use std::pin::Pin;
 use serde::Serialize;
 use chrono::{ Utc, DateTime };
 use polyumi_util::{
-	id::{ marker::UserMarker, Id },
+	id::{ marker::{ GroupMarker, UserMarker }, Id },
 	PG_POOL
 };
 
-use crate::Result;
+use crate::{
+	pagination::SortDirection,
+	Result
+};
 use super::UserModel;
 
 #[derive(Serialize)]
 pub struct InboxItemModel {
-	id: u64,
+	pub id: u64,
 	kind: String,
 	related_users: Vec<UserModel>,
+	related_group_id: Option<Id<GroupMarker>>,
 	created_at: DateTime<Utc>
 }
 
 impl InboxItemModel {
-	pub async fn get_user_many(user_id: Id<UserMarker>) -> Result<Vec<Self>> {
+	/// One page of a user's inbox, starting after the `cursor` item.
+	pub async fn get_user_page(user_id: Id<UserMarker>, direction: SortDirection, cursor: Option<u64>, limit: i64) -> Result<Vec<Self>> {
 		let pinned = Pin::static_ref(&PG_POOL).await;
 		let records = sqlx::query!(
 			"
-			SELECT id, kind, related_user_ids, created_at
+			SELECT id, kind, related_user_ids, related_group_id, created_at
 			FROM user_inbox_items
-			WHERE user_id = $1
+			WHERE user_id = $1 AND ($2::int8 IS NULL OR CASE WHEN $3 THEN id < $2 ELSE id > $2 END)
+			ORDER BY CASE WHEN $3 THEN id END DESC, id
+			LIMIT $4
 			",
-			user_id.value
+			user_id.value,
+			cursor.map(|x| x as i64),
+			direction.is_descending(),
+			limit
 		)
 			.fetch_all(pinned.get_ref())
 			.await?;
@@ -38,10 +48,26 @@ impl InboxItemModel {
 				id: record.id as u64,
 				kind: record.kind,
 				related_users,
+				related_group_id: record.related_group_id.map(Into::into),
 				created_at: record.created_at
 			});
 		}
 
 		Ok(items)
 	}
+
+	pub async fn count_user(user_id: Id<UserMarker>) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM user_inbox_items
+			WHERE user_id = $1
+			"#,
+			user_id.value
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
 }
\ No newline at end of file
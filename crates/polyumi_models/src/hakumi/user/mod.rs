@@ -8,12 +8,13 @@ use serde::Serialize;
 use chrono::{ Utc, DateTime };
 use futures::TryStreamExt;
 use polyumi_util::{
-	id::{ marker::{ GroupMarker, UserMarker }, Id },
+	id::{ marker::UserMarker, Id },
 	PG_POOL
 };
 
 use crate::Result;
 
+pub mod block;
 pub mod connection;
 pub mod inbox;
 
@@ -94,24 +95,4 @@ impl UserModel {
 			.await?
 		)
 	}
-
-	pub async fn get_groups(user_id: Id<UserMarker>) -> Result<Vec<Id<GroupMarker>>> {
-		let pinned = Pin::static_ref(&PG_POOL).await;
-		Ok(sqlx::query!(
-			"
-			SELECT g.id FROM teams g
-			INNER JOIN team_members gm ON gm.team_id = g.id AND NOT gm.is_pending
-			WHERE gm.user_id = $1
-			ORDER BY g.display_name DESC, g.name DESC
-			",
-			user_id.value
-		)
-			.fetch(pinned.get_ref())
-			.try_fold(Vec::new(), |mut acc, u| {
-				acc.push(u.id.into());
-				async move { Ok(acc) }
-			})
-			.await?
-		)
-	}
 }
\ No newline at end of file
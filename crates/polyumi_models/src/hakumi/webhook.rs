@@ -0,0 +1,257 @@
+use chrono::{ Utc, DateTime };
+use once_cell::sync::Lazy;
+use polyumi_util::{
+	id::{
+		marker::{ GroupMarker, UserMarker, WebhookMarker },
+		Id
+	},
+	public_addresses, PG_POOL
+};
+use rand::Rng;
+use reqwest::Url;
+use serde::Serialize;
+use sqlx::PgConnection;
+use std::{
+	net::{ IpAddr, SocketAddr },
+	pin::Pin
+};
+
+use crate::{
+	mellow::model_event::outbox,
+	pagination::SortDirection,
+	Result
+};
+
+#[derive(Serialize)]
+pub struct WebhookModel {
+	pub id: Id<WebhookMarker>,
+	pub created_at: DateTime<Utc>,
+	pub creator_id: Option<Id<UserMarker>>,
+
+	pub owner_group_id: Option<Id<GroupMarker>>,
+	pub owner_user_id: Option<Id<UserMarker>>,
+
+	pub url: String,
+	pub events: Vec<String>,
+	pub is_enabled: bool,
+
+	#[serde(skip)]
+	pub secret: String
+}
+
+/// Comma-separated addresses that webhooks may point at despite not being on the public internet, e.g. for a self-hosted receiver.
+pub static WEBHOOK_ALLOWED_ADDRESSES: Lazy<Vec<IpAddr>> = Lazy::new(|| std::env::var("WEBHOOK_ALLOWED_ADDRESSES")
+	.map(|x| x
+		.split(',')
+		.filter_map(|x| x.trim().parse().ok())
+		.collect()
+	)
+	.unwrap_or_default()
+);
+
+/// Where a webhook pointed at `url` would be delivered to, nothing at all if that's anywhere inside our own network.
+/// Checked when a webhook is created or changed, and again before every delivery since DNS can change in between.
+pub async fn resolve_url(url: &str) -> Option<(Url, Vec<SocketAddr>)> {
+	let url = Url::parse(url).ok()?;
+	let addresses = public_addresses(&url, &WEBHOOK_ALLOWED_ADDRESSES).await?;
+	Some((url, addresses))
+}
+
+impl WebhookModel {
+	pub fn generate_secret() -> String {
+		hex::encode(rand::thread_rng().r#gen::<[u8; 32]>())
+	}
+
+	pub async fn get(webhook_id: Id<WebhookMarker>) -> Result<Option<Self>> {
+		Ok(sqlx::query!(
+			"
+			SELECT id, created_at, creator_id, owner_group_id, owner_user_id, url, events, is_enabled, secret
+			FROM webhooks
+			WHERE id = $1
+			",
+			webhook_id.value
+		)
+			.fetch_optional(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.map(|record| Self {
+				id: record.id.into(),
+				created_at: record.created_at,
+				creator_id: record.creator_id.map(Into::into),
+
+				owner_group_id: record.owner_group_id.map(Into::into),
+				owner_user_id: record.owner_user_id.map(Into::into),
+
+				url: record.url,
+				events: record.events,
+				is_enabled: record.is_enabled,
+
+				secret: record.secret
+			})
+		)
+	}
+
+	/// One page of a group's webhooks in the order they were created, starting after the `cursor` webhook.
+	pub async fn get_group_page(group_id: Id<GroupMarker>, direction: SortDirection, cursor: Option<Id<WebhookMarker>>, limit: i64) -> Result<Vec<Self>> {
+		Self::get_owner_page(Some(group_id), None, direction, cursor, limit).await
+	}
+
+	/// One page of a user's webhooks in the order they were created, starting after the `cursor` webhook.
+	pub async fn get_user_page(user_id: Id<UserMarker>, direction: SortDirection, cursor: Option<Id<WebhookMarker>>, limit: i64) -> Result<Vec<Self>> {
+		Self::get_owner_page(None, Some(user_id), direction, cursor, limit).await
+	}
+
+	async fn get_owner_page(group_id: Option<Id<GroupMarker>>, user_id: Option<Id<UserMarker>>, direction: SortDirection, cursor: Option<Id<WebhookMarker>>, limit: i64) -> Result<Vec<Self>> {
+		Ok(sqlx::query!(
+			"
+			SELECT id, created_at, creator_id, owner_group_id, owner_user_id, url, events, is_enabled, secret
+			FROM webhooks
+			WHERE (owner_group_id = $1 OR owner_user_id = $2)
+			AND (
+				$3::uuid IS NULL OR
+				CASE WHEN $4
+					THEN (created_at, id) < ((SELECT created_at FROM webhooks WHERE id = $3), $3)
+					ELSE (created_at, id) > ((SELECT created_at FROM webhooks WHERE id = $3), $3)
+				END
+			)
+			ORDER BY CASE WHEN $4 THEN created_at END DESC, CASE WHEN $4 THEN id END DESC, created_at, id
+			LIMIT $5
+			",
+			group_id.map(|x| x.value),
+			user_id.map(|x| x.value),
+			cursor.map(|x| x.value),
+			direction.is_descending(),
+			limit
+		)
+			.fetch_all(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.into_iter()
+			.map(|record| Self {
+				id: record.id.into(),
+				created_at: record.created_at,
+				creator_id: record.creator_id.map(Into::into),
+
+				owner_group_id: record.owner_group_id.map(Into::into),
+				owner_user_id: record.owner_user_id.map(Into::into),
+
+				url: record.url,
+				events: record.events,
+				is_enabled: record.is_enabled,
+
+				secret: record.secret
+			})
+			.collect()
+		)
+	}
+
+	pub async fn count_group(group_id: Id<GroupMarker>) -> Result<i64> {
+		Self::count_owner(Some(group_id), None).await
+	}
+
+	pub async fn count_user(user_id: Id<UserMarker>) -> Result<i64> {
+		Self::count_owner(None, Some(user_id)).await
+	}
+
+	async fn count_owner(group_id: Option<Id<GroupMarker>>, user_id: Option<Id<UserMarker>>) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM webhooks
+			WHERE owner_group_id = $1 OR owner_user_id = $2
+			"#,
+			group_id.map(|x| x.value),
+			user_id.map(|x| x.value)
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
+}
+
+#[derive(Serialize)]
+pub struct WebhookDeliveryModel {
+	pub id: u64,
+	pub webhook_id: Id<WebhookMarker>,
+	pub event: serde_json::Value,
+	pub attempt: u32,
+	pub is_redelivery: bool,
+	pub status_code: Option<u16>,
+	pub error: Option<String>,
+	pub created_at: DateTime<Utc>
+}
+
+impl WebhookDeliveryModel {
+	/// One page of a webhook's delivery attempts, starting after the `cursor` delivery.
+	pub async fn get_webhook_page(webhook_id: Id<WebhookMarker>, direction: SortDirection, cursor: Option<u64>, limit: i64) -> Result<Vec<Self>> {
+		Ok(sqlx::query!(
+			"
+			SELECT id, webhook_id, body, attempt, is_redelivery, status_code, error, created_at
+			FROM webhook_deliveries
+			WHERE webhook_id = $1 AND ($2::int8 IS NULL OR CASE WHEN $3 THEN id < $2 ELSE id > $2 END)
+			ORDER BY CASE WHEN $3 THEN id END DESC, id
+			LIMIT $4
+			",
+			webhook_id.value,
+			cursor.map(|x| x as i64),
+			direction.is_descending(),
+			limit
+		)
+			.fetch_all(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.into_iter()
+			.map(|record| Self {
+				id: record.id as u64,
+				webhook_id: record.webhook_id.into(),
+				event: record.body,
+				attempt: record.attempt as u32,
+				is_redelivery: record.is_redelivery,
+				status_code: record.status_code.map(|x| x as u16),
+				error: record.error,
+				created_at: record.created_at
+			})
+			.collect()
+		)
+	}
+
+	pub async fn count_webhook(webhook_id: Id<WebhookMarker>) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM webhook_deliveries
+			WHERE webhook_id = $1
+			"#,
+			webhook_id.value
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
+
+	/// Queues the event from a previous delivery to be sent again, returning false if there was no such delivery.
+	///
+	/// The event keeps its original id, receivers that discard duplicates will only see it again if they never processed it.
+	pub async fn redeliver(webhook_id: Id<WebhookMarker>, delivery_id: u64, connection: &mut PgConnection) -> Result<bool> {
+		let result = sqlx::query!(
+			"
+			INSERT INTO model_event_outbox (endpoint, body, webhook_id, is_redelivery)
+			SELECT w.url, d.body, w.id, true
+			FROM webhook_deliveries d
+			INNER JOIN webhooks w ON w.id = d.webhook_id
+			WHERE d.id = $1 AND d.webhook_id = $2
+			",
+			delivery_id as i64,
+			webhook_id.value
+		)
+			.execute(&mut *connection)
+			.await?;
+		if result.rows_affected() == 0 {
+			return Ok(false);
+		}
+
+		outbox::notify(connection)
+			.await?;
+
+		Ok(true)
+	}
+}
\ No newline at end of file
@@ -2,6 +2,7 @@
 pub mod error;
 pub mod hakumi;
 pub mod mellow;
+pub mod pagination;
 pub mod polyumi;
 
 pub use error::{ Error, Result };
\ No newline at end of file
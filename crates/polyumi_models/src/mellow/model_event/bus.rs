@@ -0,0 +1,128 @@
+use futures::future::BoxFuture;
+use log::error;
+use once_cell::sync::Lazy;
+use polyumi_util::PG_POOL;
+use sqlx::postgres::PgListener;
+use std::{
+	pin::Pin,
+	sync::{ Arc, RwLock },
+	time::Duration
+};
+use tokio::sync::{ broadcast, OnceCell };
+
+use crate::Result;
+use super::{
+	stream::{ self, StreamedEvent, STREAM_CHANNEL },
+	ModelEventKind, ModelKind
+};
+
+const CHANNEL_CAPACITY: usize = 1024;
+const REPLAY_LIMIT: i64 = 1000;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+type Handler = Box<dyn Fn(&ModelEventKind, &ModelKind) -> BoxFuture<'static, ()> + Send + Sync>;
+
+pub static MODEL_EVENT_BUS: Lazy<ModelEventBus> = Lazy::new(|| ModelEventBus {
+	sender: broadcast::channel(CHANNEL_CAPACITY).0,
+	handlers: RwLock::default(),
+	listener: OnceCell::new()
+});
+
+/// Fans out model events to everything on this instance that cares about them, like the gateway and the cache.
+pub struct ModelEventBus {
+	sender: broadcast::Sender<Arc<StreamedEvent>>,
+	handlers: RwLock<Vec<Handler>>,
+	listener: OnceCell<()>
+}
+
+impl ModelEventBus {
+	/// Starts listening for committed model events, if this instance isn't already,
+	/// and waits until it is so nothing committed afterwards can be missed.
+	pub async fn listen(&self) -> Result<()> {
+		self.listener
+			.get_or_try_init(|| async {
+				let mut listener = PgListener::connect_with(Pin::static_ref(&PG_POOL).await.get_ref())
+					.await?;
+				listener
+					.listen(STREAM_CHANNEL)
+					.await?;
+				// only what's published after listening is dispatched
+				let last_sequence = StreamedEvent::latest_sequence()
+					.await?
+					.unwrap_or_default();
+				tokio::spawn(run_listener(listener, last_sequence));
+
+				Ok::<_, crate::Error>(())
+			})
+			.await?;
+
+		Ok(())
+	}
+
+	/// Every model event committed from now on, made on any instance.
+	pub async fn subscribe(&self) -> Result<broadcast::Receiver<Arc<StreamedEvent>>> {
+		self.listen()
+			.await?;
+
+		Ok(self.sender.subscribe())
+	}
+
+	/// Registers a handler that's called with every model event, which needs to be quick as it's awaited inline.
+	///
+	/// Handlers are called twice for events made on this instance, once when enqueued so the writer can read its own writes,
+	/// and again once committed, in case something read the old row back in between the two.
+	pub fn on_event(&self, handler: impl Fn(&ModelEventKind, &ModelKind) -> BoxFuture<'static, ()> + Send + Sync + 'static) {
+		self.handlers
+			.write()
+			.unwrap()
+			.push(Box::new(handler));
+	}
+
+	pub(crate) async fn dispatch(&self, kind: &ModelEventKind, model: &ModelKind) {
+		// collected first, the lock can't be held across an await
+		let futures: Vec<_> = self.handlers
+			.read()
+			.unwrap()
+			.iter()
+			.map(|handler| handler(kind, model))
+			.collect();
+		futures::future::join_all(futures).await;
+	}
+}
+
+// every notification, or reconnect (after which try_recv returns None), is treated the same way:
+// publish whatever has been committed, then catch up on everything after the last sequence seen.
+// sequences are only ever published in order, so that can't skip anything.
+async fn run_listener(mut listener: PgListener, mut last_sequence: i64) {
+	loop {
+		if let Err(error) = listener.try_recv().await {
+			error!("model event bus listener failed: {error}");
+			tokio::time::sleep(RECONNECT_DELAY).await;
+			continue;
+		}
+		if let Err(error) = stream::publish().await {
+			error!("failed to publish model events: {error}");
+		}
+
+		loop {
+			let events = match StreamedEvent::get_since(last_sequence, REPLAY_LIMIT).await {
+				Ok(x) => x,
+				Err(error) => {
+					error!("failed to fetch model events for the bus: {error}");
+					break;
+				}
+			};
+			let is_last_page = (events.len() as i64) < REPLAY_LIMIT;
+			for event in events {
+				last_sequence = event.envelope.sequence;
+				MODEL_EVENT_BUS
+					.dispatch(&event.envelope.kind, &event.envelope.model)
+					.await;
+				MODEL_EVENT_BUS.sender.send(Arc::new(event)).ok();
+			}
+			if is_last_page {
+				break;
+			}
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,96 @@
+use chrono::{ Utc, DateTime };
+use hmac::Mac;
+use polyumi_util::id::{ marker::UserMarker, Id };
+use serde::{ Deserialize, Serialize };
+use uuid::Uuid;
+
+use crate::{ Error, Result };
+use super::{ HmacSha256, ModelEventKind, ModelKind };
+
+pub const MODEL_EVENT_VERSION: u16 = 1;
+
+/// Header carrying the delivery signature, formatted as `t=<unix timestamp>,v1=<hex signature>`.
+pub const SIGNATURE_HEADER: &str = "absolutesolver";
+
+/// How far the signed timestamp may drift from the receiver's clock before a delivery is rejected.
+pub const SIGNATURE_TOLERANCE_SECS: i64 = 300;
+
+/// What is actually delivered for every model event.
+///
+/// `id` is unique per event and stays the same across redeliveries, so receivers can use it to discard duplicates,
+/// `sequence` only ever goes up, and `actionee_id` is whoever caused the event, or `null` when nobody did (e.g. background jobs).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ModelEventEnvelope {
+	pub version: u16,
+	pub id: Uuid,
+	pub sequence: i64,
+	pub occurred_at: DateTime<Utc>,
+	pub actionee_id: Option<Id<UserMarker>>,
+	pub kind: ModelEventKind,
+	pub model: ModelKind
+}
+
+fn mac(secret: &[u8], timestamp: i64, body: &[u8]) -> Result<HmacSha256> {
+	let mut mac = HmacSha256::new_from_slice(secret)?;
+	mac.update(timestamp.to_string().as_bytes());
+	mac.update(b".");
+	mac.update(body);
+
+	Ok(mac)
+}
+
+/// Signs `body` along with `timestamp`, returning the value for `SIGNATURE_HEADER`.
+pub fn sign(secret: &[u8], timestamp: i64, body: &[u8]) -> Result<String> {
+	let signature = mac(secret, timestamp, body)?
+		.finalize()
+		.into_bytes();
+	Ok(format!("t={timestamp},v1={}", hex::encode(signature)))
+}
+
+/// Checks the `SIGNATURE_HEADER` of a delivery against `body`, and returns the event it contains.
+///
+/// Deliveries signed more than `SIGNATURE_TOLERANCE_SECS` away from now are rejected, which stops old deliveries from being replayed,
+/// anything replayed within that window still has the same `id` as the original.
+pub fn verify(secret: &[u8], signature_header: &str, body: &[u8]) -> Result<ModelEventEnvelope> {
+	let mut timestamp = None;
+	let mut signatures = Vec::new();
+	for (key, value) in signature_header
+		.split(',')
+		.filter_map(|x| x.trim().split_once('='))
+	{
+		match key {
+			"t" => timestamp = value.parse::<i64>().ok(),
+			"v1" => signatures.extend(hex::decode(value).ok()),
+			_ => ()
+		}
+	}
+
+	let timestamp = timestamp.ok_or(Error::MissingSignature)?;
+	if signatures.is_empty() {
+		return Err(Error::MissingSignature);
+	}
+	if (Utc::now().timestamp() - timestamp).abs() > SIGNATURE_TOLERANCE_SECS {
+		return Err(Error::StaleSignature);
+	}
+
+	let mac = mac(secret, timestamp, body)?;
+	if !signatures
+		.iter()
+		.any(|x| mac.clone().verify_slice(x).is_ok())
+	{
+		return Err(Error::InvalidSignature);
+	}
+
+	// check the version on its own first, a newer envelope might not deserialise as this one
+	#[derive(Deserialize)]
+	struct Versioned {
+		version: u16
+	}
+
+	let Versioned { version } = serde_json::from_slice(body)?;
+	if version != MODEL_EVENT_VERSION {
+		return Err(Error::UnsupportedModelEventVersion(version));
+	}
+
+	Ok(serde_json::from_slice(body)?)
+}
\ No newline at end of file
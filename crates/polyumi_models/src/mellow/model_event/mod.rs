@@ -0,0 +1,317 @@
+use chrono::Utc;
+use hmac::Hmac;
+use once_cell::sync::Lazy;
+use polyumi_util::id::{
+	marker::{ ConnectionMarker, DocumentMarker, GroupMarker, GroupRoleMarker, UserMarker },
+	Id
+};
+use serde::{ Deserialize, Serialize };
+use sha2::Sha256;
+use sqlx::PgConnection;
+use twilight_model::id::{
+	marker::GuildMarker,
+	Id as DiscordId
+};
+use uuid::Uuid;
+
+use crate::Result;
+
+pub mod bus;
+pub use bus::MODEL_EVENT_BUS;
+
+pub mod envelope;
+pub use envelope::{ ModelEventEnvelope, MODEL_EVENT_VERSION };
+
+pub mod outbox;
+
+pub mod stream;
+use stream::{ Topic, STREAM_CHANNEL };
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub static ABSOLUTESOLVER: Lazy<String> = Lazy::new(|| std::env::var("ABSOLUTESOLVER").expect("ABSOLUTESOLVER is not defined"));
+
+/// Where model events go when `MODEL_EVENT_ENDPOINTS` isn't defined at all.
+pub const DEFAULT_MODEL_EVENT_ENDPOINTS: &[&str] = &[
+	"https://mellow-internal-api.hakumi.cafe/internal/model_event",
+	"https://local-mellow.hakumi.cafe/internal/model_event"
+];
+
+/// Comma-separated list of URLs that every model event is delivered to, e.g. mellow's internal api.
+/// Defined but empty means model events only go to webhooks.
+pub static MODEL_EVENT_ENDPOINTS: Lazy<Vec<String>> = Lazy::new(|| std::env::var("MODEL_EVENT_ENDPOINTS")
+	.map(|x| x
+		.split(',')
+		.map(|x| x.trim().to_string())
+		.filter(|x| !x.is_empty())
+		.collect()
+	)
+	.unwrap_or_else(|_| DEFAULT_MODEL_EVENT_ENDPOINTS
+		.iter()
+		.map(|x| x.to_string())
+		.collect()
+	)
+);
+
+#[derive(Debug)]
+pub struct ModelEventModel {
+	pub actionee_id: Option<Id<UserMarker>>,
+	pub kind: ModelEventKind,
+	pub model: ModelKind
+}
+
+impl ModelEventModel {
+	/// Writes this event to the outbox, once for every model event endpoint and every webhook subscribed to it,
+	/// and to the event stream for the gateway. It's given a sequence and delivered once the surrounding transaction
+	/// commits and it's been [published](stream::publish), though handlers on the bus hear about it straight away.
+	pub async fn enqueue(self, connection: &mut PgConnection) -> Result<()> {
+		let envelope = ModelEventEnvelope {
+			version: MODEL_EVENT_VERSION,
+			id: Uuid::new_v4(),
+			// filled in when it's published
+			sequence: 0,
+			occurred_at: Utc::now(),
+			actionee_id: self.actionee_id,
+			kind: self.kind,
+			model: self.model
+		};
+		let body = serde_json::to_value(&envelope)?;
+
+		let (group_ids, user_ids) = envelope.model
+			.owners(&mut *connection)
+			.await?;
+		let topics: Vec<String> = envelope.model
+			.topics(&group_ids, &user_ids)
+			.iter()
+			.map(ToString::to_string)
+			.collect();
+		sqlx::query!(
+			"
+			INSERT INTO model_events (id, body, topics)
+			VALUES ($1, $2, $3)
+			",
+			envelope.id,
+			body,
+			&topics
+		)
+			.execute(&mut *connection)
+			.await?;
+		sqlx::query!(
+			"
+			SELECT pg_notify($1, NULL)
+			",
+			STREAM_CHANNEL
+		)
+			.execute(&mut *connection)
+			.await?;
+
+		let mut enqueued = 0;
+		if !MODEL_EVENT_ENDPOINTS.is_empty() {
+			enqueued += sqlx::query!(
+				"
+				INSERT INTO model_event_outbox (endpoint, body, event_id)
+				SELECT UNNEST($1::text[]), $2, $3
+				",
+				&MODEL_EVENT_ENDPOINTS[..],
+				body,
+				envelope.id
+			)
+				.execute(&mut *connection)
+				.await?
+				.rows_affected();
+		}
+
+		if !group_ids.is_empty() || !user_ids.is_empty() {
+			enqueued += sqlx::query!(
+				"
+				INSERT INTO model_event_outbox (endpoint, body, webhook_id, event_id)
+				SELECT url, $3, id, $5
+				FROM webhooks
+				WHERE is_enabled AND (owner_group_id = ANY($1) OR owner_user_id = ANY($2)) AND (cardinality(events) = 0 OR $4 = ANY(events))
+				",
+				&group_ids.iter().map(|x| x.value).collect::<Vec<_>>(),
+				&user_ids.iter().map(|x| x.value).collect::<Vec<_>>(),
+				body,
+				event_type(&envelope.kind, &envelope.model),
+				envelope.id
+			)
+				.execute(&mut *connection)
+				.await?
+				.rows_affected();
+		}
+
+		if enqueued > 0 {
+			outbox::notify(connection)
+				.await?;
+		}
+
+		MODEL_EVENT_BUS
+			.dispatch(&envelope.kind, &envelope.model)
+			.await;
+
+		Ok(())
+	}
+}
+
+/// The name webhooks filter events by, e.g. `group_membership.created`.
+pub fn event_type(kind: &ModelEventKind, model: &ModelKind) -> String {
+	format!("{}.{}", model.name(), kind.name())
+}
+
+pub fn is_event_type(value: &str) -> bool {
+	value
+		.split_once('.')
+		.is_some_and(|(model, kind)| ModelKind::NAMES.contains(&model) && ModelEventKind::NAMES.contains(&kind))
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ModelEventKind {
+	Created,
+	Updated,
+	Deleted
+}
+
+impl ModelEventKind {
+	pub const NAMES: &[&str] = &["created", "updated", "deleted"];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Created => "created",
+			Self::Updated => "updated",
+			Self::Deleted => "deleted"
+		}
+	}
+
+	/// Starts an event, which can't be enqueued until it's known who caused it.
+	pub fn build(self, model_kind: ModelKind) -> ModelEventBuilder {
+		ModelEventBuilder {
+			kind: self,
+			model: model_kind
+		}
+	}
+}
+
+#[must_use]
+pub struct ModelEventBuilder {
+	kind: ModelEventKind,
+	model: ModelKind
+}
+
+impl ModelEventBuilder {
+	pub fn actionee(self, user_id: Id<UserMarker>) -> ModelEventModel {
+		ModelEventModel {
+			actionee_id: Some(user_id),
+			kind: self.kind,
+			model: self.model
+		}
+	}
+
+	/// For events nobody in particular caused, like those from background jobs.
+	pub fn system(self) -> ModelEventModel {
+		ModelEventModel {
+			actionee_id: None,
+			kind: self.kind,
+			model: self.model
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ModelKind {
+	Cafe(u64),
+	CafeOrder(u64, u64),
+	Group(Id<GroupMarker>),
+	GroupMembership(Id<GroupMarker>, Id<UserMarker>),
+	GroupRole(Id<GroupMarker>, Id<GroupRoleMarker>),
+	Server(DiscordId<GuildMarker>),
+	UserConnection(Id<UserMarker>, Id<ConnectionMarker>),
+	UserSettings(DiscordId<GuildMarker>, Id<UserMarker>),
+	VisualScriptingDocument(Option<DiscordId<GuildMarker>>, Id<DocumentMarker>)
+}
+
+impl ModelKind {
+	pub const NAMES: &[&str] = &["cafe", "cafe_order", "group", "group_membership", "group_role", "server", "user_connection", "user_settings", "visual_scripting_document"];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Cafe(..) => "cafe",
+			Self::CafeOrder(..) => "cafe_order",
+			Self::Group(..) => "group",
+			Self::GroupMembership(..) => "group_membership",
+			Self::GroupRole(..) => "group_role",
+			Self::Server(..) => "server",
+			Self::UserConnection(..) => "user_connection",
+			Self::UserSettings(..) => "user_settings",
+			Self::VisualScriptingDocument(..) => "visual_scripting_document"
+		}
+	}
+
+	/// The groups and users that own this model, and whose webhooks get to hear about it.
+	pub async fn owners(&self, connection: &mut PgConnection) -> Result<(Vec<Id<GroupMarker>>, Vec<Id<UserMarker>>)> {
+		Ok(match self {
+			Self::Cafe(cafe_id) |
+			Self::CafeOrder(cafe_id, _) => {
+				let record = sqlx::query!(
+					"
+					SELECT owner_group_id, owner_user_id
+					FROM cafes
+					WHERE id = $1
+					",
+					*cafe_id as i64
+				)
+					.fetch_optional(&mut *connection)
+					.await?;
+				match record {
+					Some(record) => (
+						record.owner_group_id.map(Id::new).into_iter().collect(),
+						record.owner_user_id.map(Id::new).into_iter().collect()
+					),
+					None => (vec![], vec![])
+				}
+			},
+			Self::Group(group_id) |
+			Self::GroupRole(group_id, _) => (vec![*group_id], vec![]),
+			Self::GroupMembership(group_id, user_id) => (vec![*group_id], vec![*user_id]),
+			Self::Server(server_id) |
+			Self::VisualScriptingDocument(Some(server_id), _) => {
+				let record = sqlx::query!(
+					"
+					SELECT owner_team_id, owner_user_id
+					FROM mellow_servers
+					WHERE id = $1
+					",
+					server_id.get() as i64
+				)
+					.fetch_optional(&mut *connection)
+					.await?;
+				match record {
+					Some(record) => (
+						record.owner_team_id.map(Id::new).into_iter().collect(),
+						record.owner_user_id.map(Id::new).into_iter().collect()
+					),
+					None => (vec![], vec![])
+				}
+			},
+			Self::UserConnection(user_id, _) |
+			Self::UserSettings(_, user_id) => (vec![], vec![*user_id]),
+			Self::VisualScriptingDocument(None, _) => (vec![], vec![])
+		})
+	}
+
+	/// The gateway topics this model is published to, everything it belongs to plus the model itself where it has a topic.
+	pub fn topics(&self, group_ids: &[Id<GroupMarker>], user_ids: &[Id<UserMarker>]) -> Vec<Topic> {
+		let mut topics: Vec<Topic> = group_ids
+			.iter()
+			.map(|x| Topic::Group(*x))
+			.chain(user_ids.iter().map(|x| Topic::User(*x)))
+			.collect();
+		if let Self::Server(server_id) | Self::VisualScriptingDocument(Some(server_id), _) = self {
+			topics.push(Topic::MellowServer(*server_id));
+		}
+		if let Self::Cafe(cafe_id) | Self::CafeOrder(cafe_id, _) = self {
+			topics.push(Topic::Cafe(*cafe_id));
+		}
+
+		topics
+	}
+}
\ No newline at end of file
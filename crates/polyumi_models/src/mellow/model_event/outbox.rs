@@ -0,0 +1,254 @@
+use chrono::Utc;
+use log::error;
+use polyumi_util::{ pinned_client, HTTP, PG_POOL };
+use reqwest::RequestBuilder;
+use sqlx::{
+	postgres::PgListener,
+	PgConnection
+};
+use std::{
+	pin::Pin,
+	time::{ Duration, Instant }
+};
+
+use crate::{
+	hakumi::webhook,
+	Error, Result
+};
+use super::{
+	envelope::{ self, SIGNATURE_HEADER },
+	stream::{ self, StreamedEvent },
+	ABSOLUTESOLVER
+};
+
+pub const OUTBOX_CHANNEL: &str = "model_event_outbox";
+
+const BATCH_SIZE: i64 = 32;
+pub const MAX_ATTEMPTS: i32 = 12;
+const MAX_BACKOFF_SECS: f64 = 3600.0;
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// how long a claimed batch is kept from other workers, sent one by one it can take up to BATCH_SIZE timeouts
+const LEASE_SECS: f64 = 2.0 * BATCH_SIZE as f64 * REQUEST_TIMEOUT.as_secs() as f64;
+
+/// Delivers to one of our own `MODEL_EVENT_ENDPOINTS`.
+pub async fn send(url: &str, body: Vec<u8>, signature: &str) -> Result<u16> {
+	post(HTTP.post(url), body, signature).await
+}
+
+/// Delivers to a webhook, whose url is resolved again right before sending and has to lead somewhere public.
+/// Redirects aren't followed, they count as a failed delivery like any other status that isn't a success.
+pub async fn send_webhook(url: &str, body: Vec<u8>, signature: &str) -> Result<u16> {
+	let (url, addresses) = webhook::resolve_url(url)
+		.await
+		.ok_or(Error::ForbiddenAddress)?;
+	let client = pinned_client(&url, &addresses)
+		.build()?;
+	post(client.post(url), body, signature).await
+}
+
+async fn post(request: RequestBuilder, body: Vec<u8>, signature: &str) -> Result<u16> {
+	let response = request
+		.body(body)
+		.header(SIGNATURE_HEADER, signature)
+		.header("content-type", "application/json")
+		.timeout(REQUEST_TIMEOUT)
+		.send()
+		.await?;
+	let status = response.status();
+	if !status.is_success() {
+		return Err(Error::UnexpectedStatus(status.as_u16()));
+	}
+
+	Ok(status.as_u16())
+}
+
+/// Wakes up the outbox worker, since notifications are transactional too, that only happens after the commit.
+pub async fn notify(connection: &mut PgConnection) -> Result<()> {
+	sqlx::query!(
+		"
+		SELECT pg_notify($1, NULL)
+		",
+		OUTBOX_CHANNEL
+	)
+		.execute(connection)
+		.await?;
+
+	Ok(())
+}
+
+/// Attempts to deliver one batch of due events, returning how many were attempted.
+/// Anything committed but not yet published is published first, events can't go out without a sequence.
+///
+/// The batch is claimed by pushing its `next_attempt_at` out by `LEASE_SECS`, so nothing is locked
+/// or held open while sending, if the worker dies halfway through, the events just come due again.
+///
+/// Delivered events are removed from the outbox, failed ones are retried with exponential backoff,
+/// and after `MAX_ATTEMPTS` they're marked as failed, which moves them into `model_event_dead_letters`.
+/// Every attempt at delivering to a webhook is also recorded in `webhook_deliveries`.
+pub async fn deliver_outbox() -> Result<usize> {
+	stream::publish().await?;
+
+	// returning doesn't keep the order rows were claimed in, so they're put back in order afterwards
+	let records = sqlx::query!(
+		"
+		WITH claimed AS (
+			UPDATE model_event_outbox o
+			SET next_attempt_at = now() + make_interval(secs => $2)
+			WHERE o.id IN (
+				SELECT id
+				FROM model_event_outbox
+				WHERE failed_at IS NULL AND event_id IS NULL AND next_attempt_at <= now()
+				ORDER BY id
+				LIMIT $1
+				FOR UPDATE SKIP LOCKED
+			)
+			RETURNING o.id, o.endpoint, o.body, o.attempts, o.webhook_id, o.is_redelivery
+		)
+		SELECT c.id AS \"id!\", c.endpoint AS \"endpoint!\", c.body AS \"body!\", c.attempts AS \"attempts!\", c.webhook_id, c.is_redelivery AS \"is_redelivery!\", w.secret AS \"secret?\"
+		FROM claimed c
+		LEFT JOIN webhooks w ON w.id = c.webhook_id
+		ORDER BY c.id
+		",
+		BATCH_SIZE,
+		LEASE_SECS
+	)
+		.fetch_all(&*Pin::static_ref(&PG_POOL).await)
+		.await?;
+
+	// one at a time and in order, endpoints shouldn't see events out of order
+	for record in &records {
+		let body = serde_json::to_vec(&record.body)?;
+		let secret = record.secret
+			.as_deref()
+			.unwrap_or(&ABSOLUTESOLVER);
+		// signed right before sending, so retries don't go stale
+		let result = match envelope::sign(secret.as_bytes(), Utc::now().timestamp(), &body) {
+			Ok(signature) => match record.webhook_id {
+				Some(_) => send_webhook(&record.endpoint, body, &signature).await,
+				None => send(&record.endpoint, body, &signature).await
+			},
+			Err(error) => Err(error)
+		};
+		let attempts = record.attempts + 1;
+		let mut transaction = Pin::static_ref(&PG_POOL)
+			.await
+			.begin()
+			.await?;
+
+		if let Some(webhook_id) = record.webhook_id {
+			let status_code = match &result {
+				Ok(x) => Some(*x),
+				Err(Error::UnexpectedStatus(x)) => Some(*x),
+				Err(_) => None
+			};
+			sqlx::query!(
+				"
+				INSERT INTO webhook_deliveries (webhook_id, body, attempt, is_redelivery, status_code, error)
+				VALUES ($1, $2, $3, $4, $5, $6)
+				",
+				webhook_id,
+				record.body,
+				attempts,
+				record.is_redelivery,
+				status_code.map(|x| x as i16),
+				result.as_ref().err().map(|x| x.to_string())
+			)
+				.execute(&mut *transaction)
+				.await?;
+		}
+
+		match result {
+			Ok(_) => {
+				sqlx::query!(
+					"
+					DELETE FROM model_event_outbox
+					WHERE id = $1
+					",
+					record.id
+				)
+					.execute(&mut *transaction)
+					.await?;
+			},
+			Err(error) => {
+				sqlx::query!(
+					"
+					UPDATE model_event_outbox
+					SET attempts = $2, last_error = $3, next_attempt_at = now() + make_interval(secs => $4), failed_at = CASE WHEN $2::int4 >= $5 THEN now() END
+					WHERE id = $1
+					",
+					record.id,
+					attempts,
+					error.to_string(),
+					2f64.powi(attempts).min(MAX_BACKOFF_SECS),
+					MAX_ATTEMPTS
+				)
+					.execute(&mut *transaction)
+					.await?;
+			}
+		}
+
+		transaction
+			.commit()
+			.await?;
+	}
+
+	Ok(records.len())
+}
+
+/// Delivers events forever, waking up whenever something is enqueued, and every `POLL_INTERVAL` for retries.
+/// Also prunes the event stream every `PRUNE_INTERVAL`.
+pub async fn run_outbox() {
+	let mut listener = None;
+	let mut last_pruned_at: Option<Instant> = None;
+	loop {
+		if last_pruned_at.is_none_or(|x| x.elapsed() >= PRUNE_INTERVAL) {
+			if let Err(error) = StreamedEvent::prune().await {
+				error!("failed to prune model events: {error}");
+			}
+			last_pruned_at = Some(Instant::now());
+		}
+
+		if listener.is_none() {
+			listener = match connect_listener().await {
+				Ok(x) => Some(x),
+				Err(error) => {
+					error!("failed to listen for model events: {error}");
+					None
+				}
+			};
+		}
+
+		loop {
+			match deliver_outbox().await {
+				Ok(x) if x as i64 == BATCH_SIZE => continue,
+				Ok(_) => break,
+				Err(error) => {
+					error!("failed to deliver model events: {error}");
+					break;
+				}
+			}
+		}
+
+		match &mut listener {
+			Some(x) => {
+				if let Ok(Err(error)) = tokio::time::timeout(POLL_INTERVAL, x.recv()).await {
+					error!("model event listener failed: {error}");
+					listener = None;
+				}
+			},
+			None => tokio::time::sleep(POLL_INTERVAL).await
+		}
+	}
+}
+
+async fn connect_listener() -> Result<PgListener> {
+	let mut listener = PgListener::connect_with(Pin::static_ref(&PG_POOL).await.get_ref())
+		.await?;
+	listener
+		.listen(OUTBOX_CHANNEL)
+		.await?;
+
+	Ok(listener)
+}
\ No newline at end of file
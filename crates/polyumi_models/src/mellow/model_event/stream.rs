@@ -0,0 +1,235 @@
+use polyumi_util::{
+	id::{
+		marker::{ GroupMarker, UserMarker },
+		Id
+	},
+	PG_POOL
+};
+use serde::{ Deserialize, Deserializer, Serialize, Serializer };
+use std::{
+	fmt::Display,
+	pin::Pin,
+	str::FromStr
+};
+use twilight_model::id::{
+	marker::GuildMarker,
+	Id as DiscordId
+};
+use uuid::Uuid;
+
+use crate::Result;
+use super::ModelEventEnvelope;
+
+/// Notified whenever model events are committed, and again once they've been published.
+pub const STREAM_CHANNEL: &str = "model_events";
+
+// any constant works, it only needs to be the same everywhere
+const PUBLISH_LOCK: i64 = 0x6d6f64656c;
+
+/// How long model events are kept around for clients to resume from.
+pub const RETENTION_HOURS: i32 = 24;
+
+/// Something a gateway client can subscribe to, written as `<kind>:<id>`, e.g. `group:<uuid>`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Topic {
+	Cafe(u64),
+	Group(Id<GroupMarker>),
+	MellowServer(DiscordId<GuildMarker>),
+	User(Id<UserMarker>)
+}
+
+impl Display for Topic {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Cafe(id) => write!(f, "cafe:{id}"),
+			Self::Group(id) => write!(f, "group:{id}"),
+			Self::MellowServer(id) => write!(f, "mellow_server:{id}"),
+			Self::User(id) => write!(f, "user:{id}")
+		}
+	}
+}
+
+impl FromStr for Topic {
+	type Err = ();
+
+	fn from_str(value: &str) -> core::result::Result<Self, Self::Err> {
+		let (kind, id) = value
+			.split_once(':')
+			.ok_or(())?;
+		Ok(match kind {
+			"cafe" => Self::Cafe(id.parse().map_err(|_| ())?),
+			"group" => Self::Group(Uuid::parse_str(id).map_err(|_| ())?.into()),
+			"mellow_server" => Self::MellowServer(id.parse().map_err(|_| ())?),
+			"user" => Self::User(Uuid::parse_str(id).map_err(|_| ())?.into()),
+			_ => return Err(())
+		})
+	}
+}
+
+impl Serialize for Topic {
+	fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
+impl<'de> Deserialize<'de> for Topic {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+		let value = String::deserialize(deserializer)?;
+		value
+			.parse()
+			.map_err(|_| serde::de::Error::custom(format!("invalid topic: {value}")))
+	}
+}
+
+/// A model event along with the topics it was published to.
+#[derive(Clone, Debug)]
+pub struct StreamedEvent {
+	pub topics: Vec<Topic>,
+	pub envelope: ModelEventEnvelope
+}
+
+impl StreamedEvent {
+	/// Events after `sequence`, oldest first.
+	pub async fn get_since(sequence: i64, limit: i64) -> Result<Vec<Self>> {
+		sqlx::query!(
+			"
+			SELECT body, topics
+			FROM model_events
+			WHERE sequence > $1
+			ORDER BY sequence
+			LIMIT $2
+			",
+			sequence,
+			limit
+		)
+			.fetch_all(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.into_iter()
+			.map(|record| Self::from_record(record.body, record.topics))
+			.collect()
+	}
+
+	/// The newest sequence that's been published, if anything has been.
+	pub async fn latest_sequence() -> Result<Option<i64>> {
+		Ok(sqlx::query!(
+			"
+			SELECT MAX(sequence) AS sequence
+			FROM model_events
+			"
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.sequence
+		)
+	}
+
+	/// The oldest sequence that can still be resumed from, events before it have been pruned.
+	pub async fn oldest_sequence() -> Result<Option<i64>> {
+		Ok(sqlx::query!(
+			"
+			SELECT MIN(sequence) AS sequence
+			FROM model_events
+			"
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.sequence
+		)
+	}
+
+	pub async fn prune() -> Result<u64> {
+		Ok(sqlx::query!(
+			"
+			DELETE FROM model_events
+			WHERE sequence IS NOT NULL AND created_at < now() - make_interval(hours => $1)
+			",
+			RETENTION_HOURS
+		)
+			.execute(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.rows_affected()
+		)
+	}
+
+	fn from_record(body: serde_json::Value, topics: Vec<String>) -> Result<Self> {
+		Ok(Self {
+			// topics are only ever written by us, so anything unparseable can safely be skipped
+			topics: topics
+				.iter()
+				.flat_map(|x| x.parse())
+				.collect(),
+			envelope: serde_json::from_value(body)?
+		})
+	}
+}
+
+/// Gives every committed event that doesn't have a sequence yet the next one, in the order they were enqueued,
+/// and lets their outbox rows be delivered. Returns how many were published.
+///
+/// Only one instance publishes at a time, and the lock is held until the sequences are committed,
+/// so they become visible in order and nothing can be left behind by someone resuming after the latest one.
+pub async fn publish() -> Result<u64> {
+	let mut transaction = Pin::static_ref(&PG_POOL)
+		.await
+		.begin()
+		.await?;
+	sqlx::query!(
+		"
+		SELECT pg_advisory_xact_lock($1)
+		",
+		PUBLISH_LOCK
+	)
+		.execute(&mut *transaction)
+		.await?;
+
+	let published = sqlx::query!(
+		r#"
+		WITH pending AS (
+			SELECT id, row_number() OVER (ORDER BY created_at, id) AS position
+			FROM model_events
+			WHERE sequence IS NULL
+		), reserved AS (
+			-- nextval doesn't have to follow the order rows come out in, so a whole block is taken at once instead,
+			-- nobody else can take any in the meantime since we're holding the lock
+			SELECT setval('model_event_sequence', nextval('model_event_sequence') + COUNT(*) - 1) - COUNT(*) AS base
+			FROM pending
+			HAVING COUNT(*) > 0
+		), sequenced AS (
+			SELECT p.id, r.base + p.position AS sequence
+			FROM pending p, reserved r
+		), published AS (
+			UPDATE model_events e
+			SET sequence = s.sequence, body = jsonb_set(e.body, '{sequence}', to_jsonb(s.sequence))
+			FROM sequenced s
+			WHERE e.id = s.id
+			RETURNING e.id, e.sequence
+		), released AS (
+			UPDATE model_event_outbox o
+			SET body = jsonb_set(o.body, '{sequence}', to_jsonb(p.sequence)), event_id = NULL
+			FROM published p
+			WHERE o.event_id = p.id
+		)
+		SELECT COUNT(*) AS "count!"
+		FROM published
+		"#
+	)
+		.fetch_one(&mut *transaction)
+		.await?
+		.count as u64;
+	if published > 0 {
+		sqlx::query!(
+			"
+			SELECT pg_notify($1, NULL)
+			",
+			STREAM_CHANNEL
+		)
+			.execute(&mut *transaction)
+			.await?;
+	}
+
+	transaction
+		.commit()
+		.await?;
+
+	Ok(published)
+}
\ No newline at end of file
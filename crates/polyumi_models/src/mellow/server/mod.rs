@@ -6,6 +6,7 @@ use polyumi_util::{
 	},
 	PG_POOL
 };
+use serde::{ Deserialize, Serialize };
 use std::pin::Pin;
 use twilight_model::id::{
 	marker::GuildMarker as DiscordGuildMarker,
@@ -14,7 +15,9 @@ use twilight_model::id::{
 
 use crate::Result;
 
+#[derive(Clone, Deserialize, Serialize)]
 pub struct ServerModel {
+	pub id: DiscordId<DiscordGuildMarker>,
 	pub name: String,
 	pub avatar_url: Option<String>,
 	pub owner_group_id: Option<Id<GroupMarker>>,
@@ -39,7 +42,7 @@ impl ServerModel {
 			.collect();
 		Ok(sqlx::query!(
 			"
-			SELECT name, avatar_url, owner_team_id, owner_user_id
+			SELECT id, name, avatar_url, owner_team_id, owner_user_id
 			FROM mellow_servers
 			WHERE id = ANY($1)
 			",
@@ -48,6 +51,7 @@ impl ServerModel {
 			.fetch(&*Pin::static_ref(&PG_POOL).await)
 			.try_fold(Vec::new(), |mut acc, record| {
 				acc.push(Self {
+					id: DiscordId::new(record.id as u64),
 					name: record.name,
 					avatar_url: record.avatar_url,
 					owner_group_id: record.owner_team_id.map(Id::new),
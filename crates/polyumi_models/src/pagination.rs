@@ -0,0 +1,15 @@
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+	#[default]
+	Asc,
+	Desc
+}
+
+impl SortDirection {
+	pub fn is_descending(self) -> bool {
+		self == Self::Desc
+	}
+}
\ No newline at end of file
@@ -0,0 +1,413 @@
+use chrono::{ DateTime, Utc };
+use futures::TryStreamExt;
+use polyumi_util::{
+	id::{
+		marker::{ GroupMarker, UserMarker, WebhookMarker },
+		Id
+	},
+	PG_POOL
+};
+use serde::{ Deserialize, Serialize };
+use serde_json::{ json, Map, Value };
+use sha2::{ Digest, Sha256 };
+use sqlx::PgConnection;
+use std::pin::Pin;
+use twilight_model::id::{
+	marker::GuildMarker,
+	Id as DiscordId
+};
+
+use crate::{
+	mellow::model_event::ModelKind,
+	pagination::SortDirection,
+	Result
+};
+
+/// Sessions don't have ids of their own, so they're told apart by a hash of their token.
+pub fn session_id(token: &str) -> String {
+	hex::encode(&Sha256::digest(token.as_bytes())[..16])
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditLogAction {
+	CafeCreated,
+	CafeUpdated,
+	CafeOrderCreated,
+	CafeOrderUpdated,
+	CafeOrderDeleted,
+	CafeOrderReported,
+	CafeOrderReportResolved,
+	GroupCreated,
+	GroupUpdated,
+	GroupDeleted,
+	GroupMembersInvited,
+	GroupInviteAccepted,
+	GroupInviteDeclined,
+	GroupInviteCancelled,
+	GroupJoinRequested,
+	GroupJoinRequestApproved,
+	GroupJoinRequestRejected,
+	GroupLeft,
+	GroupMemberRemoved,
+	GroupOwnerAdded,
+	GroupOwnerRemoved,
+	GroupOwnershipTransferred,
+	GroupRoleCreated,
+	GroupRoleUpdated,
+	GroupRoleDeleted,
+	GroupRoleAssigned,
+	GroupRoleUnassigned,
+	MellowServerSettingsUpdated,
+	MellowUserSettingsUpdated,
+	UserConnectionCreated,
+	UserConnectionDeleted,
+	UserBlocked,
+	UserUnblocked,
+	VisualScriptingDocumentUpdated,
+	WebhookCreated,
+	WebhookUpdated,
+	WebhookDeleted,
+	WebhookRedelivered
+}
+
+impl AuditLogAction {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::CafeCreated => "cafe_created",
+			Self::CafeUpdated => "cafe_updated",
+			Self::CafeOrderCreated => "cafe_order_created",
+			Self::CafeOrderUpdated => "cafe_order_updated",
+			Self::CafeOrderDeleted => "cafe_order_deleted",
+			Self::CafeOrderReported => "cafe_order_reported",
+			Self::CafeOrderReportResolved => "cafe_order_report_resolved",
+			Self::GroupCreated => "group_created",
+			Self::GroupUpdated => "group_updated",
+			Self::GroupDeleted => "group_deleted",
+			Self::GroupMembersInvited => "group_members_invited",
+			Self::GroupInviteAccepted => "group_invite_accepted",
+			Self::GroupInviteDeclined => "group_invite_declined",
+			Self::GroupInviteCancelled => "group_invite_cancelled",
+			Self::GroupJoinRequested => "group_join_requested",
+			Self::GroupJoinRequestApproved => "group_join_request_approved",
+			Self::GroupJoinRequestRejected => "group_join_request_rejected",
+			Self::GroupLeft => "group_left",
+			Self::GroupMemberRemoved => "group_member_removed",
+			Self::GroupOwnerAdded => "group_owner_added",
+			Self::GroupOwnerRemoved => "group_owner_removed",
+			Self::GroupOwnershipTransferred => "group_ownership_transferred",
+			Self::GroupRoleCreated => "group_role_created",
+			Self::GroupRoleUpdated => "group_role_updated",
+			Self::GroupRoleDeleted => "group_role_deleted",
+			Self::GroupRoleAssigned => "group_role_assigned",
+			Self::GroupRoleUnassigned => "group_role_unassigned",
+			Self::MellowServerSettingsUpdated => "mellow_server_settings_updated",
+			Self::MellowUserSettingsUpdated => "mellow_user_settings_updated",
+			Self::UserConnectionCreated => "user_connection_created",
+			Self::UserConnectionDeleted => "user_connection_deleted",
+			Self::UserBlocked => "user_blocked",
+			Self::UserUnblocked => "user_unblocked",
+			Self::VisualScriptingDocumentUpdated => "visual_scripting_document_updated",
+			Self::WebhookCreated => "webhook_created",
+			Self::WebhookUpdated => "webhook_updated",
+			Self::WebhookDeleted => "webhook_deleted",
+			Self::WebhookRedelivered => "webhook_redelivered"
+		}
+	}
+
+	pub fn build(self, actor_id: Id<UserMarker>) -> AuditLogEntry {
+		AuditLogEntry {
+			actor_id,
+			session_id: None,
+			ip_address: None,
+			action: self,
+			target: None,
+			group_id: None,
+			target_user_id: None,
+			diff: None
+		}
+	}
+}
+
+/// What an entry is about, most things are models with events of their own.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum AuditLogTarget {
+	Model(ModelKind),
+	Webhook {
+		webhook_id: Id<WebhookMarker>
+	}
+}
+
+impl AuditLogTarget {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Model(model) => model.name(),
+			Self::Webhook { .. } => "webhook"
+		}
+	}
+
+	fn cafe_id(&self) -> Option<u64> {
+		match self {
+			Self::Model(ModelKind::Cafe(cafe_id)) |
+			Self::Model(ModelKind::CafeOrder(cafe_id, _)) => Some(*cafe_id),
+			_ => None
+		}
+	}
+
+	fn mellow_server_id(&self) -> Option<DiscordId<GuildMarker>> {
+		match self {
+			Self::Model(ModelKind::Server(server_id)) |
+			Self::Model(ModelKind::UserSettings(server_id, _)) |
+			Self::Model(ModelKind::VisualScriptingDocument(Some(server_id), _)) => Some(*server_id),
+			_ => None
+		}
+	}
+}
+
+/// A record of someone doing something, written alongside whatever they did.
+#[derive(Debug)]
+pub struct AuditLogEntry {
+	pub actor_id: Id<UserMarker>,
+	pub session_id: Option<String>,
+	pub ip_address: Option<String>,
+	pub action: AuditLogAction,
+	pub target: Option<AuditLogTarget>,
+	pub group_id: Option<Id<GroupMarker>>,
+	pub target_user_id: Option<Id<UserMarker>>,
+	pub diff: Option<Value>
+}
+
+impl AuditLogEntry {
+	pub fn session(mut self, session_id: Option<String>, ip_address: Option<String>) -> Self {
+		self.session_id = session_id;
+		self.ip_address = ip_address;
+		self
+	}
+
+	/// The model this is about, the group and user it belongs to are filled in from it unless they're given.
+	pub fn target(mut self, model: ModelKind) -> Self {
+		self.target = Some(AuditLogTarget::Model(model));
+		self
+	}
+
+	pub fn webhook(mut self, webhook_id: Id<WebhookMarker>, group_id: Option<Id<GroupMarker>>, user_id: Option<Id<UserMarker>>) -> Self {
+		self.target = Some(AuditLogTarget::Webhook { webhook_id });
+		self.group_id = group_id;
+		self.target_user_id = user_id;
+		self
+	}
+
+	pub fn group(mut self, group_id: Id<GroupMarker>) -> Self {
+		self.group_id = Some(group_id);
+		self
+	}
+
+	pub fn target_user(mut self, user_id: Id<UserMarker>) -> Self {
+		self.target_user_id = Some(user_id);
+		self
+	}
+
+	pub fn diff(mut self, diff: Value) -> Self {
+		self.diff = Some(diff);
+		self
+	}
+
+	/// Records the fields that differ between two versions of something, as `{"field":{"old":...,"new":...}}`.
+	pub fn changes(self, before: &impl Serialize, after: &impl Serialize) -> Result<Self> {
+		Ok(self.diff(diff(&serde_json::to_value(before)?, &serde_json::to_value(after)?)))
+	}
+
+	/// Writes this entry, it should be given the same transaction as the change it's recording.
+	pub async fn insert(self, connection: &mut PgConnection) -> Result<()> {
+		let (mut group_id, mut target_user_id) = (self.group_id, self.target_user_id);
+		if let Some(AuditLogTarget::Model(model)) = &self.target && (group_id.is_none() || target_user_id.is_none()) {
+			let (group_ids, user_ids) = model
+				.owners(&mut *connection)
+				.await?;
+			group_id = group_id.or(group_ids.first().copied());
+			target_user_id = target_user_id.or(user_ids.first().copied());
+		}
+
+		sqlx::query!(
+			"
+			INSERT INTO audit_log_entries (actor_id, session_id, ip_address, action, group_id, mellow_server_id, cafe_id, target_user_id, target_kind, target, diff)
+			VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+			",
+			self.actor_id.value,
+			self.session_id,
+			self.ip_address,
+			self.action.name(),
+			group_id.map(|x| x.value),
+			self.target
+				.as_ref()
+				.and_then(AuditLogTarget::mellow_server_id)
+				.map(|x| x.get() as i64),
+			self.target
+				.as_ref()
+				.and_then(AuditLogTarget::cafe_id)
+				.map(|x| x as i64),
+			target_user_id.map(|x| x.value),
+			self.target
+				.as_ref()
+				.map(AuditLogTarget::name),
+			self.target
+				.as_ref()
+				.map(serde_json::to_value)
+				.transpose()?,
+			self.diff
+		)
+			.execute(connection)
+			.await?;
+
+		Ok(())
+	}
+}
+
+fn diff(before: &Value, after: &Value) -> Value {
+	let (Value::Object(before), Value::Object(after)) = (before, after) else {
+		return json!({ "old": before, "new": after });
+	};
+
+	let mut changes = Map::new();
+	for (key, new) in after {
+		let old = before.get(key).unwrap_or(&Value::Null);
+		if old != new {
+			changes.insert(key.clone(), json!({ "old": old, "new": new }));
+		}
+	}
+
+	Value::Object(changes)
+}
+
+/// Which entries to list, everything about a cafe, a group, a mellow server, or done by or to a user.
+#[derive(Clone, Copy, Debug)]
+pub enum AuditLogScope {
+	Cafe(u64),
+	Group(Id<GroupMarker>),
+	MellowServer(DiscordId<GuildMarker>),
+	User(Id<UserMarker>)
+}
+
+impl AuditLogScope {
+	fn cafe_id(self) -> Option<i64> {
+		match self {
+			Self::Cafe(cafe_id) => Some(cafe_id as i64),
+			_ => None
+		}
+	}
+
+	fn group_id(self) -> Option<uuid::Uuid> {
+		match self {
+			Self::Group(group_id) => Some(group_id.value),
+			_ => None
+		}
+	}
+
+	fn mellow_server_id(self) -> Option<i64> {
+		match self {
+			Self::MellowServer(server_id) => Some(server_id.get() as i64),
+			_ => None
+		}
+	}
+
+	fn user_id(self) -> Option<uuid::Uuid> {
+		match self {
+			Self::User(user_id) => Some(user_id.value),
+			_ => None
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct AuditLogEntryModel {
+	pub id: u64,
+	pub created_at: DateTime<Utc>,
+	pub actor_id: Option<Id<UserMarker>>,
+	pub session_id: Option<String>,
+	pub ip_address: Option<String>,
+
+	pub action: String,
+	pub group_id: Option<Id<GroupMarker>>,
+	pub mellow_server_id: Option<DiscordId<GuildMarker>>,
+	pub cafe_id: Option<u64>,
+	pub target_user_id: Option<Id<UserMarker>>,
+	pub target_kind: Option<String>,
+	pub target: Option<Value>,
+	pub diff: Option<Value>
+}
+
+impl AuditLogEntryModel {
+	/// Drops where the entry came from, for anyone but the actor.
+	pub fn redacted(self) -> Self {
+		Self {
+			session_id: None,
+			ip_address: None,
+			..self
+		}
+	}
+
+	/// One page of entries in `scope`, starting after the `cursor` entry.
+	pub async fn get_page(scope: AuditLogScope, direction: SortDirection, cursor: Option<u64>, limit: i64) -> Result<Vec<Self>> {
+		let pinned = Pin::static_ref(&PG_POOL).await;
+		Ok(sqlx::query!(
+			"
+			SELECT id, created_at, actor_id, session_id, ip_address, action, group_id, mellow_server_id, cafe_id, target_user_id, target_kind, target, diff
+			FROM audit_log_entries
+			WHERE ($1::uuid IS NULL OR group_id = $1) AND ($2::int8 IS NULL OR mellow_server_id = $2) AND ($3::uuid IS NULL OR actor_id = $3 OR target_user_id = $3)
+			AND ($4::int8 IS NULL OR cafe_id = $4)
+			AND ($5::int8 IS NULL OR CASE WHEN $6 THEN id < $5 ELSE id > $5 END)
+			ORDER BY CASE WHEN $6 THEN id END DESC, id
+			LIMIT $7
+			",
+			scope.group_id(),
+			scope.mellow_server_id(),
+			scope.user_id(),
+			scope.cafe_id(),
+			cursor.map(|x| x as i64),
+			direction.is_descending(),
+			limit
+		)
+			.fetch(pinned.get_ref())
+			.try_fold(Vec::new(), |mut acc, x| {
+				acc.push(Self {
+					id: x.id as u64,
+					created_at: x.created_at,
+					actor_id: x.actor_id.map(Into::into),
+					session_id: x.session_id,
+					ip_address: x.ip_address,
+
+					action: x.action,
+					group_id: x.group_id.map(Into::into),
+					mellow_server_id: x.mellow_server_id.map(|x| DiscordId::new(x as u64)),
+					cafe_id: x.cafe_id.map(|x| x as u64),
+					target_user_id: x.target_user_id.map(Into::into),
+					target_kind: x.target_kind,
+					target: x.target,
+					diff: x.diff
+				});
+				async move { Ok(acc) }
+			})
+			.await?
+		)
+	}
+
+	pub async fn count(scope: AuditLogScope) -> Result<i64> {
+		Ok(sqlx::query!(
+			r#"
+			SELECT COUNT(*) AS "count!"
+			FROM audit_log_entries
+			WHERE ($1::uuid IS NULL OR group_id = $1) AND ($2::int8 IS NULL OR mellow_server_id = $2) AND ($3::uuid IS NULL OR actor_id = $3 OR target_user_id = $3)
+			AND ($4::int8 IS NULL OR cafe_id = $4)
+			"#,
+			scope.group_id(),
+			scope.mellow_server_id(),
+			scope.user_id(),
+			scope.cafe_id()
+		)
+			.fetch_one(&*Pin::static_ref(&PG_POOL).await)
+			.await?
+			.count
+		)
+	}
+}
\ No newline at end of file
@@ -7,10 +7,12 @@ use polyumi_util::{
 	},
 	PG_POOL
 };
+use serde::{ Deserialize, Serialize };
 use std::pin::Pin;
 
 use crate::Result;
 
+#[derive(Deserialize, Serialize)]
 pub struct PasskeyModel {
 	pub public_key: Base64UrlSafeData,
 	pub user_id: Id<UserMarker>
@@ -3,7 +3,9 @@ use polyumi_util::id::{
 	Id
 };
 use rand::Rng;
+use serde::{ Deserialize, Serialize };
 
+#[derive(Deserialize, Serialize)]
 pub struct PasskeyChallengeModel {
 	pub id: Id<PasskeyMarker>,
 	pub challenge: Vec<u8>
@@ -25,13 +25,21 @@ impl actix_web::ResponseError for ErrorModel {
 			ErrorModelKind::NotFound { .. } => StatusCode::NOT_FOUND,
 			ErrorModelKind::InvalidCredentials |
 			ErrorModelKind::MissingCredentials => StatusCode::UNAUTHORIZED,
+			ErrorModelKind::CafeWordFiltered { .. } |
 			ErrorModelKind::InvalidSignature |
 			ErrorModelKind::InvalidParams |
 			ErrorModelKind::InvalidQuery |
 			ErrorModelKind::MissingSignature |
 			ErrorModelKind::UserAlreadyInGroup { .. } |
 			ErrorModelKind::UserAlreadyPendingInGroup { .. } => StatusCode::BAD_REQUEST,
-			ErrorModelKind::MissingPermission => StatusCode::FORBIDDEN
+			ErrorModelKind::GroupCafeExists { .. } |
+			ErrorModelKind::GroupNameTaken { .. } |
+			ErrorModelKind::GroupRoleNameTaken { .. } |
+			ErrorModelKind::LastGroupOwner => StatusCode::CONFLICT,
+			ErrorModelKind::GroupInviteExpired => StatusCode::GONE,
+			ErrorModelKind::CafeLocked |
+			ErrorModelKind::MissingPermission => StatusCode::FORBIDDEN,
+			ErrorModelKind::CafeSlowMode { .. } => StatusCode::TOO_MANY_REQUESTS
 		}
 	}
 
@@ -55,9 +63,15 @@ impl From<Error> for ErrorModel {
 			error: match value {
 				Error::Base64Decode(..) |
 				Error::Base64DecodeSlice(..) |
-				Error::EcdsaError(..) => ErrorModelKind::InvalidSignature,
+				Error::EcdsaError(..) |
+				Error::InvalidSignature |
+				Error::StaleSignature |
+				Error::UnsupportedModelEventVersion(..) => ErrorModelKind::InvalidSignature,
 				Error::MissingSignature => ErrorModelKind::MissingSignature,
+				Error::ForbiddenAddress |
+				Error::Io(..) |
 				Error::Reqwest(..) |
+				Error::UnexpectedStatus(..) |
 				Error::SerdeJson(..) |
 				Error::Sha2InvalidLength(..) |
 				Error::SimdJson(..) => ErrorModelKind::InternalError,
@@ -67,12 +81,24 @@ impl From<Error> for ErrorModel {
 	}
 }
 
+impl From<validator::ValidationErrors> for ErrorModel {
+	fn from(_value: validator::ValidationErrors) -> Self {
+		ErrorModelKind::InvalidParams.model()
+	}
+}
+
 impl From<jsonwebtoken::errors::Error> for ErrorModel {
 	fn from(_value: jsonwebtoken::errors::Error) -> Self {
 		ErrorModelKind::InternalError.model()
 	}
 }
 
+impl From<std::io::Error> for ErrorModel {
+	fn from(value: std::io::Error) -> Self {
+		Error::Io(value).into()
+	}
+}
+
 impl From<reqwest::Error> for ErrorModel {
 	fn from(value: reqwest::Error) -> Self {
 		Error::Reqwest(value).into()
@@ -113,6 +139,24 @@ pub enum ErrorModelKind {
 	},
 	UserAlreadyPendingInGroup {
 		user_id: Id<UserMarker>
+	},
+	GroupNameTaken {
+		name: String
+	},
+	GroupRoleNameTaken {
+		name: String
+	},
+	GroupInviteExpired,
+	LastGroupOwner,
+	GroupCafeExists {
+		cafe_id: u64
+	},
+	CafeLocked,
+	CafeSlowMode {
+		retry_after: u64
+	},
+	CafeWordFiltered {
+		word: String
 	}
 }
 
@@ -132,11 +176,20 @@ impl ErrorModelKind {
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ResourceKind {
+	Cafe,
+	CafeOrder,
+	CafeOrderReport,
 	Group,
 	GroupMembership,
+	GroupRole,
+	MellowServer,
+	Passkey,
 	PasskeyChallenge,
 	Route,
 	User,
+	UserBlock,
 	UserConnection,
-	VisualScriptingDocument
+	VisualScriptingDocument,
+	Webhook,
+	WebhookDelivery
 }
\ No newline at end of file
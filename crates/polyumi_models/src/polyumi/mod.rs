@@ -1,3 +1,5 @@
+pub mod audit_log;
+
 pub mod auth;
 
 pub mod error;
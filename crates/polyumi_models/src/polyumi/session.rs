@@ -2,14 +2,35 @@ use actix_web::HttpRequest;
 use base64::prelude::*;
 use p384::ecdsa::{ signature::Verifier, Signature, VerifyingKey };
 use polyumi_util::id::{ marker::UserMarker, Id };
+use serde::{ Deserialize, Deserializer, Serialize, Serializer };
 
 use crate::{ Error, Result };
 
+#[derive(Deserialize, Serialize)]
 pub struct SessionModel {
 	pub user_id: Id<UserMarker>,
+	#[serde(deserialize_with = "deserialize_public_key", serialize_with = "serialize_public_key")]
 	pub public_key: Option<VerifyingKey>
 }
 
+// written as the same base64 encoded sec1 bytes the device gave us
+fn serialize_public_key<S: Serializer>(public_key: &Option<VerifyingKey>, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+	public_key
+		.map(|x| BASE64_STANDARD.encode(x.to_encoded_point(false).as_bytes()))
+		.serialize(serializer)
+}
+
+fn deserialize_public_key<'de, D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Option<VerifyingKey>, D::Error> {
+	Option::<String>::deserialize(deserializer)?
+		.map(|x| BASE64_STANDARD
+			.decode(x)
+			.ok()
+			.and_then(|x| VerifyingKey::from_sec1_bytes(&x).ok())
+			.ok_or_else(|| serde::de::Error::custom("invalid public key"))
+		)
+		.transpose()
+}
+
 impl SessionModel {
 	pub fn new(user_id: Id<UserMarker>, public_key: Option<String>) -> Result<Self> {
 		Ok(Self {
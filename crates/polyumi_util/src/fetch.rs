@@ -1,16 +1,60 @@
 use once_cell::sync::Lazy;
 use reqwest::{
 	header::{ HeaderName, HeaderValue },
-	Client, Body, Error, IntoUrl, Method, RequestBuilder
+	redirect::Policy,
+	Client, ClientBuilder, Body, Error, IntoUrl, Method, RequestBuilder, Url
 };
 use serde::{ de::DeserializeOwned, Serialize };
 use std::{
 	future::IntoFuture,
-	marker::PhantomData
+	marker::PhantomData,
+	net::{ IpAddr, SocketAddr }
 };
 
 pub static HTTP: Lazy<Client> = Lazy::new(Client::new);
 
+/// Every address `url` leads to, as long as it's http(s) and all of them are on the public internet (or in `allowed`).
+/// Nothing is returned otherwise, including when the name doesn't resolve at all.
+pub async fn public_addresses(url: &Url, allowed: &[IpAddr]) -> Option<Vec<SocketAddr>> {
+	if !matches!(url.scheme(), "http" | "https") {
+		return None;
+	}
+
+	let port = url.port_or_known_default().unwrap_or(80);
+	let addresses: Vec<SocketAddr> = match url.domain() {
+		Some(domain) => match tokio::net::lookup_host((domain, port)).await {
+			Ok(addresses) => addresses.collect(),
+			Err(_) => vec![]
+		},
+		None => url
+			.host_str()
+			.and_then(|x| x
+				.trim_start_matches('[')
+				.trim_end_matches(']')
+				.parse::<IpAddr>()
+				.ok()
+			)
+			.map(|x| vec![SocketAddr::new(x, port)])
+			.unwrap_or_default()
+	};
+	if addresses.is_empty() || !addresses.iter().all(|x| x.ip().is_global() || allowed.contains(&x.ip())) {
+		return None;
+	}
+
+	Some(addresses)
+}
+
+/// A client that only connects to `addresses` for `url`'s host, and leaves redirects for the caller to deal with,
+/// so the name can't resolve (or redirect) somewhere else after [`public_addresses`] checked it.
+pub fn pinned_client(url: &Url, addresses: &[SocketAddr]) -> ClientBuilder {
+	let client = Client::builder()
+		.redirect(Policy::none());
+	match url.domain() {
+		Some(domain) => client.resolve_to_addrs(domain, addresses),
+		None => client
+	}
+}
+
 pub struct FetchJson<T: DeserializeOwned> {
 	phantom: PhantomData<T>,
 	request: RequestBuilder
@@ -4,6 +4,10 @@ pub struct DocumentMarker;
 
 pub struct GroupMarker;
 
+pub struct GroupRoleMarker;
+
 pub struct PasskeyMarker;
 
-pub struct UserMarker;
\ No newline at end of file
+pub struct UserMarker;
+
+pub struct WebhookMarker;
\ No newline at end of file
@@ -1,16 +1,20 @@
-#![feature(const_async_blocks, type_alias_impl_trait)]
+#![feature(const_async_blocks, ip, type_alias_impl_trait)]
 use actix_cors::Cors;
 use async_once_cell::Lazy;
-use sqlx::PgPool;
+use sqlx::{ migrate::Migrator, PgPool };
 
 pub mod fetch;
 pub mod id;
 pub use fetch::*;
 
+pub static MIGRATOR: Migrator = sqlx::migrate!("../../migrations");
+
 pub type PgPoolFuture = impl Future<Output = PgPool>;
 
 pub static PG_POOL: Lazy<PgPool, PgPoolFuture> = Lazy::new(async {
-	PgPool::connect(env!("DATABASE_URL"))
+	let database_url = std::env::var("DATABASE_URL")
+		.expect("DATABASE_URL is not defined");
+	PgPool::connect(&database_url)
 		.await
 		.unwrap()
 });
@@ -0,0 +1,140 @@
+-- baseline of the schema that existed before migrations were tracked.
+-- everything uses IF NOT EXISTS so this can be applied over an existing deployment.
+CREATE TABLE IF NOT EXISTS users (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	username text NOT NULL,
+	name text,
+	bio text,
+	flags int2 NOT NULL DEFAULT 0,
+	avatar_url text,
+	banner_url text,
+	profile_status text,
+	theme_accent_colour int4 NOT NULL DEFAULT 0,
+	theme_primary_colour int4 NOT NULL DEFAULT 0,
+	created_via_mellow boolean NOT NULL DEFAULT false,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE UNIQUE INDEX IF NOT EXISTS users_username_key ON users (LOWER(username));
+
+CREATE TABLE IF NOT EXISTS user_devices (
+	id text PRIMARY KEY,
+	public_key text NOT NULL,
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS user_connections (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	sub text NOT NULL,
+	type int2 NOT NULL,
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	username text,
+	display_name text,
+	avatar_url text,
+	website_url text,
+	is_public boolean NOT NULL DEFAULT false,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS user_connections_user_id_idx ON user_connections (user_id);
+CREATE INDEX IF NOT EXISTS user_connections_sub_idx ON user_connections (sub);
+
+CREATE TABLE IF NOT EXISTS user_connection_oauth_authorisations (
+	id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+	connection_id uuid NOT NULL REFERENCES user_connections (id) ON DELETE CASCADE,
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	token_type text NOT NULL,
+	access_token text NOT NULL,
+	refresh_token text NOT NULL,
+	scopes text[] NOT NULL DEFAULT '{}',
+	expires_at timestamptz NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS user_inbox_items (
+	id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	kind text NOT NULL,
+	related_user_ids uuid[] NOT NULL DEFAULT '{}',
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS teams (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	name text NOT NULL,
+	display_name text,
+	bio text,
+	avatar_url text,
+	banner_url text,
+	profile_theme_accent_colour int4 NOT NULL DEFAULT 0,
+	profile_theme_primary_colour int4 NOT NULL DEFAULT 0,
+	creator_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE UNIQUE INDEX IF NOT EXISTS teams_name_key ON teams (LOWER(name));
+
+CREATE TABLE IF NOT EXISTS team_members (
+	team_id uuid NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	inviter_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	is_invited boolean NOT NULL DEFAULT false,
+	is_owner boolean NOT NULL DEFAULT false,
+	is_pending boolean NOT NULL DEFAULT false,
+	joined_at timestamptz NOT NULL DEFAULT now(),
+	PRIMARY KEY (team_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS team_members_user_id_idx ON team_members (user_id);
+
+CREATE TABLE IF NOT EXISTS cafes (
+	id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+	kind text NOT NULL,
+	creator_user_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	owner_group_id uuid REFERENCES teams (id) ON DELETE CASCADE,
+	owner_user_id uuid REFERENCES users (id) ON DELETE CASCADE,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS cafe_orders (
+	id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+	cafe_id int8 NOT NULL REFERENCES cafes (id) ON DELETE CASCADE,
+	author_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	kind text NOT NULL,
+	payload jsonb NOT NULL DEFAULT '{}',
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS cafe_orders_cafe_id_idx ON cafe_orders (cafe_id);
+
+CREATE TABLE IF NOT EXISTS mellow_servers (
+	id int8 PRIMARY KEY,
+	name text NOT NULL,
+	avatar_url text,
+	owner_team_id uuid REFERENCES teams (id) ON DELETE SET NULL,
+	owner_user_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	allow_forced_syncing boolean NOT NULL DEFAULT false,
+	default_nickname text,
+	skip_onboarding_to int2,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS mellow_user_server_settings (
+	server_id int8 NOT NULL REFERENCES mellow_servers (id) ON DELETE CASCADE,
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	user_connections jsonb NOT NULL DEFAULT '[]',
+	PRIMARY KEY (server_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS mellow_connection_requests (
+	token text PRIMARY KEY,
+	server_id int8 NOT NULL REFERENCES mellow_servers (id) ON DELETE CASCADE,
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS visual_scripting_documents (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	name text NOT NULL,
+	kind text NOT NULL,
+	active boolean NOT NULL DEFAULT true,
+	definition jsonb NOT NULL DEFAULT '[]',
+	creator_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	mellow_server_id int8 REFERENCES mellow_servers (id) ON DELETE CASCADE,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
\ No newline at end of file
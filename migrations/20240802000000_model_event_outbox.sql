@@ -0,0 +1,18 @@
+CREATE TABLE model_event_outbox (
+	id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+	endpoint text NOT NULL,
+	body jsonb NOT NULL,
+	attempts int4 NOT NULL DEFAULT 0,
+	last_error text,
+	next_attempt_at timestamptz NOT NULL DEFAULT now(),
+	failed_at timestamptz,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX model_event_outbox_pending_idx ON model_event_outbox (next_attempt_at) WHERE failed_at IS NULL;
+
+-- events that ran out of attempts, they can be retried with:
+-- UPDATE model_event_outbox SET attempts = 0, failed_at = NULL, next_attempt_at = now() WHERE id = ...;
+CREATE VIEW model_event_dead_letters AS
+SELECT id, endpoint, body, attempts, last_error, created_at, failed_at
+FROM model_event_outbox
+WHERE failed_at IS NOT NULL;
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- gives every model event envelope a monotonic sequence number, handed out when it's published instead of when it's enqueued
+-- since transactions don't commit in the order they call nextval
+CREATE SEQUENCE model_event_sequence;
\ No newline at end of file
@@ -0,0 +1,32 @@
+CREATE TABLE webhooks (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	owner_group_id uuid REFERENCES teams (id) ON DELETE CASCADE,
+	owner_user_id uuid REFERENCES users (id) ON DELETE CASCADE,
+	creator_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	url text NOT NULL,
+	secret text NOT NULL,
+	-- event types like group_membership.created, empty means every event
+	events text[] NOT NULL DEFAULT '{}',
+	is_enabled boolean NOT NULL DEFAULT true,
+	created_at timestamptz NOT NULL DEFAULT now(),
+	CHECK ((owner_group_id IS NULL) <> (owner_user_id IS NULL))
+);
+CREATE INDEX webhooks_owner_group_id_idx ON webhooks (owner_group_id) WHERE owner_group_id IS NOT NULL;
+CREATE INDEX webhooks_owner_user_id_idx ON webhooks (owner_user_id) WHERE owner_user_id IS NOT NULL;
+
+-- one row per delivery attempt
+CREATE TABLE webhook_deliveries (
+	id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+	webhook_id uuid NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+	body jsonb NOT NULL,
+	attempt int4 NOT NULL,
+	is_redelivery boolean NOT NULL,
+	status_code int2,
+	error text,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX webhook_deliveries_webhook_id_idx ON webhook_deliveries (webhook_id, id DESC);
+
+ALTER TABLE model_event_outbox
+	ADD COLUMN webhook_id uuid REFERENCES webhooks (id) ON DELETE CASCADE,
+	ADD COLUMN is_redelivery boolean NOT NULL DEFAULT false;
\ No newline at end of file
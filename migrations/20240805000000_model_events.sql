@@ -0,0 +1,17 @@
+-- every model event, kept around for a while so gateway clients can resume from a sequence.
+-- sequence stays null until the event is published, publishing one batch at a time
+-- means an event can never show up behind a sequence that's already been seen.
+CREATE TABLE model_events (
+	id uuid PRIMARY KEY,
+	sequence int8,
+	body jsonb NOT NULL,
+	topics text[] NOT NULL,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE UNIQUE INDEX model_events_sequence_idx ON model_events (sequence);
+CREATE INDEX model_events_created_at_idx ON model_events (created_at);
+CREATE INDEX model_events_unpublished_idx ON model_events (created_at, id) WHERE sequence IS NULL;
+
+-- set until the event it carries has been published, since its body doesn't have a sequence before then
+ALTER TABLE model_event_outbox ADD COLUMN event_id uuid;
+CREATE INDEX model_event_outbox_event_id_idx ON model_event_outbox (event_id) WHERE event_id IS NOT NULL;
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- pending memberships are invites, and stop being valid once they expire
+ALTER TABLE team_members
+	ADD COLUMN invite_expires_at timestamptz;
+
+ALTER TABLE user_inbox_items
+	ADD COLUMN related_group_id uuid REFERENCES teams (id) ON DELETE CASCADE;
\ No newline at end of file
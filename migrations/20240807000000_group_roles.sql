@@ -0,0 +1,18 @@
+CREATE TABLE team_roles (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	team_id uuid NOT NULL REFERENCES teams (id) ON DELETE CASCADE,
+	name text NOT NULL,
+	-- GroupPermissions bits
+	permissions int8 NOT NULL DEFAULT 0,
+	created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE UNIQUE INDEX team_roles_name_key ON team_roles (team_id, LOWER(name));
+
+CREATE TABLE team_member_roles (
+	team_id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	role_id uuid NOT NULL REFERENCES team_roles (id) ON DELETE CASCADE,
+	PRIMARY KEY (team_id, user_id, role_id),
+	FOREIGN KEY (team_id, user_id) REFERENCES team_members (team_id, user_id) ON DELETE CASCADE
+);
+CREATE INDEX team_member_roles_role_id_idx ON team_member_roles (role_id);
\ No newline at end of file
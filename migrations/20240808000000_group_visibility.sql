@@ -0,0 +1,3 @@
+-- who can see a group, and whether anyone can ask to join it
+ALTER TABLE teams
+	ADD COLUMN visibility text NOT NULL DEFAULT 'public' CHECK (visibility IN ('public', 'unlisted', 'private'));
\ No newline at end of file
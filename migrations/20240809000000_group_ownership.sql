@@ -0,0 +1,35 @@
+-- who did what, entries outlive whatever they're about so nothing here is a foreign key
+CREATE TABLE audit_log_entries (
+	id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+	created_at timestamptz NOT NULL DEFAULT now(),
+	actor_id uuid,
+	action text NOT NULL,
+	group_id uuid,
+	target_user_id uuid
+);
+CREATE INDEX audit_log_entries_group_id_idx ON audit_log_entries (group_id, id);
+
+-- when a group's last owner goes without handing it over (e.g. their account is deleted),
+-- whoever has been a member the longest takes over
+CREATE FUNCTION team_members_keep_owner() RETURNS trigger AS $$
+BEGIN
+	IF OLD.is_owner AND NOT OLD.is_pending AND NOT EXISTS (
+		SELECT 1 FROM team_members WHERE team_id = OLD.team_id AND is_owner AND NOT is_pending
+	) THEN
+		UPDATE team_members
+		SET is_owner = true
+		WHERE (team_id, user_id) = (
+			SELECT team_id, user_id
+			FROM team_members
+			WHERE team_id = OLD.team_id AND NOT is_pending
+			ORDER BY joined_at, user_id
+			LIMIT 1
+		);
+	END IF;
+	RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER team_members_keep_owner
+AFTER DELETE ON team_members
+FOR EACH ROW EXECUTE FUNCTION team_members_keep_owner();
\ No newline at end of file
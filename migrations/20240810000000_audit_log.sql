@@ -0,0 +1,23 @@
+ALTER TABLE audit_log_entries
+	ADD COLUMN session_id text,
+	ADD COLUMN ip_address text,
+	ADD COLUMN mellow_server_id int8,
+	ADD COLUMN cafe_id int8,
+	ADD COLUMN target_kind text,
+	ADD COLUMN target jsonb,
+	ADD COLUMN diff jsonb;
+CREATE INDEX audit_log_entries_mellow_server_id_idx ON audit_log_entries (mellow_server_id, id);
+CREATE INDEX audit_log_entries_cafe_id_idx ON audit_log_entries (cafe_id, id);
+CREATE INDEX audit_log_entries_actor_id_idx ON audit_log_entries (actor_id, id);
+CREATE INDEX audit_log_entries_target_user_id_idx ON audit_log_entries (target_user_id, id);
+
+-- entries are only ever added
+CREATE FUNCTION audit_log_entries_append_only() RETURNS trigger AS $$
+BEGIN
+	RAISE EXCEPTION 'audit log entries can''t be changed or removed';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_entries_append_only
+BEFORE UPDATE OR DELETE ON audit_log_entries
+FOR EACH ROW EXECUTE FUNCTION audit_log_entries_append_only();
\ No newline at end of file
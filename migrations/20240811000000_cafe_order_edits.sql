@@ -0,0 +1,3 @@
+-- orders can be edited by whoever placed them, and show when they were
+ALTER TABLE cafe_orders
+	ADD COLUMN edited_at timestamptz;
\ No newline at end of file
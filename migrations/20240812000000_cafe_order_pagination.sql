@@ -0,0 +1,3 @@
+-- cafes are read a page at a time, newest first
+DROP INDEX IF EXISTS cafe_orders_cafe_id_idx;
+CREATE INDEX IF NOT EXISTS cafe_orders_cafe_id_id_idx ON cafe_orders (cafe_id, id);
\ No newline at end of file
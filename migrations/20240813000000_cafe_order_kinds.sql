@@ -0,0 +1,16 @@
+-- replies and reactions point at the order they belong to through payload->'parent_id'
+CREATE INDEX IF NOT EXISTS cafe_orders_parent_id_idx ON cafe_orders (((payload->>'parent_id')::int8), id);
+
+-- everyone gets one of each emoji per order
+CREATE UNIQUE INDEX IF NOT EXISTS cafe_orders_reaction_idx ON cafe_orders (((payload->>'parent_id')::int8), author_id, (payload->>'emoji'))
+	WHERE kind = 'reaction';
+
+-- opengraph metadata of links that have been embedded, so popular links aren't fetched over and over
+CREATE TABLE IF NOT EXISTS cafe_embeds (
+	url text PRIMARY KEY,
+	title text,
+	description text,
+	image_url text,
+	site_name text,
+	fetched_at timestamptz NOT NULL DEFAULT now()
+);
\ No newline at end of file
@@ -0,0 +1,13 @@
+-- topic cafes are named, profile and group cafes go by whoever owns them
+ALTER TABLE cafes
+	ADD COLUMN name text,
+	ADD COLUMN post_permission text NOT NULL DEFAULT 'everyone',
+	ADD COLUMN slow_mode_seconds int4 NOT NULL DEFAULT 0,
+	ADD COLUMN locked boolean NOT NULL DEFAULT false;
+
+-- each group gets one discussion board
+CREATE UNIQUE INDEX IF NOT EXISTS cafes_owner_group_id_idx ON cafes (owner_group_id)
+	WHERE kind = 'group';
+
+-- slow mode looks up when someone last placed an order
+CREATE INDEX IF NOT EXISTS cafe_orders_cafe_id_author_id_idx ON cafe_orders (cafe_id, author_id, created_at);
\ No newline at end of file
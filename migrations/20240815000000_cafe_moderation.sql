@@ -0,0 +1,31 @@
+-- orders someone wants a moderator to look at, reporter_id is null when the word filter flagged it.
+-- reports outlive the orders they're about, so what was done about them can still be looked back on
+CREATE TABLE IF NOT EXISTS cafe_order_reports (
+	id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+	cafe_id int8 NOT NULL REFERENCES cafes (id) ON DELETE CASCADE,
+	order_id int8 REFERENCES cafe_orders (id) ON DELETE SET NULL,
+	reporter_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	reason text NOT NULL,
+	created_at timestamptz NOT NULL DEFAULT now(),
+	resolver_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	resolution text,
+	resolved_at timestamptz
+);
+CREATE INDEX IF NOT EXISTS cafe_order_reports_cafe_id_idx ON cafe_order_reports (cafe_id, id);
+CREATE INDEX IF NOT EXISTS cafe_order_reports_order_id_idx ON cafe_order_reports (order_id)
+	WHERE resolved_at IS NULL;
+
+-- one open report per person per order
+CREATE UNIQUE INDEX IF NOT EXISTS cafe_order_reports_reporter_id_idx ON cafe_order_reports (order_id, reporter_id)
+	WHERE resolved_at IS NULL;
+
+CREATE TABLE IF NOT EXISTS user_blocks (
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	blocked_user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	created_at timestamptz NOT NULL DEFAULT now(),
+	PRIMARY KEY (user_id, blocked_user_id)
+);
+
+ALTER TABLE cafes
+	ADD COLUMN word_filter text[] NOT NULL DEFAULT '{}',
+	ADD COLUMN word_filter_action text NOT NULL DEFAULT 'flag';
\ No newline at end of file
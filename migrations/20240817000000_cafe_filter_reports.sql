@@ -0,0 +1,11 @@
+-- the word filter reports as nobody, and nulls never clash in cafe_order_reports_reporter_id_idx,
+-- so it gets an index of its own, one open report per order
+DELETE FROM cafe_order_reports r
+WHERE r.reporter_id IS NULL AND r.resolved_at IS NULL AND EXISTS (
+	SELECT 1
+	FROM cafe_order_reports
+	WHERE order_id = r.order_id AND reporter_id IS NULL AND resolved_at IS NULL AND id < r.id
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS cafe_order_reports_filter_idx ON cafe_order_reports (order_id)
+	WHERE reporter_id IS NULL AND resolved_at IS NULL;
\ No newline at end of file